@@ -2,16 +2,25 @@
 use crate::{
     app_state::AppState,
     config::{AppConfig, PlatformConfig}, // PlatformConfig'i de import ediyoruz
-    grpc::service::MySipSignalingService,
+    grpc::{client, media_pool, service::MySipSignalingService, tls},
+    telemetry,
     sip::handler::handle_sip_request,
-    state::cleanup_old_transactions,
+    sip::register::sweep_expired_registrations,
+    sip::session_timer::supervise_dialogs,
+    trunk,
 };
 use anyhow::Result;
+use futures_util::Stream;
 use rustls::crypto::{ring::default_provider, CryptoProvider};
 use sentiric_contracts::sentiric::sip::v1::sip_signaling_service_server::SipSignalingServiceServer;
-use std::{env, panic, process, sync::Arc};
-use tokio::{net::UdpSocket, select, signal};
-use tonic::transport::{Certificate, Identity, Server as GrpcServer, ServerTlsConfig};
+use std::{env, net::SocketAddr, panic, process, sync::atomic::Ordering, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    select, signal,
+    sync::{oneshot, watch},
+    time::Instant,
+};
+use tonic::transport::{server::Router, Server as GrpcServer};
 use tracing::{error, info, warn};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter, Registry};
 
@@ -47,14 +56,28 @@ impl App {
         let sock = Arc::new(UdpSocket::bind(self.config.sip_listen_addr).await?);
         info!(address = %self.config.sip_listen_addr, "✅ UDP SIP dinleyici başlatıldı.");
 
-        tokio::spawn(cleanup_old_transactions(self.state.active_calls.clone()));
-        let grpc_server_task = spawn_grpc_server(self.state.clone(), sock.clone(), self.config.clone());
+        if let Err(e) = telemetry::install_exporter(self.config.metrics_listen_addr) {
+            warn!(error = %e, "Prometheus metrik dinleyicisi başlatılamadı.");
+        } else {
+            info!(address = %self.config.metrics_listen_addr, "✅ Prometheus metrik dinleyicisi başlatıldı.");
+        }
+
+        tokio::spawn(supervise_dialogs(self.state.clone(), sock.clone()));
+        trunk::registration::spawn_all(self.state.clone(), sock.clone());
+        tokio::spawn(sweep_expired_registrations(self.state.clone()));
+        tokio::spawn(media_pool::run_maintenance(self.state.clone()));
+        let tls_reload = tls::spawn_reload_watcher(self.config.clone());
+        client::spawn_reloader(self.state.clone(), tls_reload.clone());
+        let grpc_server_task = spawn_grpc_server(self.state.clone(), sock.clone(), self.config.clone(), tls_reload);
         let udp_listener_task = spawn_udp_listener(self.state.clone(), sock);
 
         select! {
             res = udp_listener_task => { if let Err(e) = res { error!(error = ?e, "UDP dinleyici görevi hatayla sonlandı."); } },
             res = grpc_server_task => { if let Err(e) = res { error!(error = ?e, "gRPC sunucu görevi hatayla sonlandı."); } },
-            _ = signal::ctrl_c() => { warn!("Kapatma sinyali (Ctrl+C) alındı. Servis kapatılıyor..."); }
+            _ = signal::ctrl_c() => {
+                warn!("Kapatma sinyali (Ctrl+C) alındı. Yeni çağrılar reddediliyor, aktif çağrılar bekleniyor...");
+                drain(&self.state).await;
+            }
         }
 
         info!("✅ Servis başarıyla kapatıldı.");
@@ -64,6 +87,25 @@ impl App {
 
 // --- Yardımcı Fonksiyonlar ---
 
+/// Servisi kapanış moduna alır (yeni INVITE'lar ve OPTIONS yoklamaları 503 alır) ve aktif çağrıların
+/// bitmesini `drain_timeout` kadar bekler. SIP dinleyicisi bu sürede çalışmaya devam eder.
+async fn drain(state: &AppState) {
+    state.draining.store(true, Ordering::Relaxed);
+    let deadline = Instant::now() + state.config.drain_timeout;
+    loop {
+        let remaining = state.active_calls.lock().await.len();
+        if remaining == 0 {
+            info!("Tüm aktif çağrılar sona erdi.");
+            return;
+        }
+        if Instant::now() >= deadline {
+            warn!(remaining, "Bekleme süresi doldu, aktif çağrılar sonlandırılmadan kapatılıyor.");
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
 fn setup_panic_hook() {
     let default_panic_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -85,7 +127,13 @@ fn initialize_config_and_logging() -> Result<Arc<AppConfig>> {
     };
     
     // Adım 2: Bu servise özel yapılandırmayı, platform yapılandırmasından türet.
-    let config = Arc::new(AppConfig::from(platform_config));
+    let config = match AppConfig::try_from(platform_config) {
+        Ok(cfg) => Arc::new(cfg),
+        Err(e) => {
+            eprintln!("### BAŞLANGIÇ HATASI: Servis yapılandırması geçersiz: {:#}", e);
+            process::exit(1);
+        }
+    };
     
     // Adım 3: Loglamayı başlat.
     let env_filter = EnvFilter::try_from_default_env()
@@ -123,34 +171,90 @@ fn spawn_udp_listener(app_state: Arc<AppState>, sock: Arc<UdpSocket>) -> tokio::
     })
 }
 
-fn spawn_grpc_server(app_state: Arc<AppState>, sock: Arc<UdpSocket>, config: Arc<AppConfig>) -> tokio::task::JoinHandle<()> {
+fn spawn_grpc_server(
+    app_state: Arc<AppState>,
+    sock: Arc<UdpSocket>,
+    config: Arc<AppConfig>,
+    mut tls_reload: watch::Receiver<u64>,
+) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        let grpc_service = MySipSignalingService { app_state, sock };
+        let grpc_service = Arc::new(MySipSignalingService { app_state, sock });
         let grpc_port_str = env::var("SIP_SIGNALING_SERVICE_GRPC_PORT").unwrap_or_else(|_| "13021".to_string());
-        let addr = format!("[::]:{}", grpc_port_str).parse().unwrap();
-        
-        let tls_config = match load_tls_config(&config).await {
-            Ok(cfg) => cfg,
+        let addr: SocketAddr = format!("[::]:{}", grpc_port_str).parse().unwrap();
+
+        // Dinleyici tek sefer açılır; sertifika yenilendiğinde yalnızca üzerindeki TLS sunucusu değişir.
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => Arc::new(listener),
+            Err(e) => {
+                error!(error = %e, address = %addr, "gRPC portu dinlenemedi.");
+                return;
+            }
+        };
+        let mut router = match build_grpc_router(&config, &grpc_service).await {
+            Ok(router) => router,
             Err(e) => {
                 error!(error = %e, "mTLS yapılandırması yüklenemedi.");
                 return;
             }
         };
+        if config.grpc_allow_list.is_empty() {
+            warn!("gRPC izin listesi tanımlı değil; CA tarafından imzalanmış her istemci tüm metodları çağırabilir.");
+        }
         info!(address = %addr, "gRPC sunucusu (mTLS ile) başlatılıyor...");
-        if let Err(e) = GrpcServer::builder()
-            .tls_config(tls_config).expect("TLS yapılandırması başarısız olmamalı")
-            .add_service(SipSignalingServiceServer::new(grpc_service))
-            .serve(addr).await {
-            error!(error = %e, "gRPC sunucusu çöktü.");
+
+        loop {
+            let (stop_tx, stop_rx) = oneshot::channel::<()>();
+            let mut server = tokio::spawn(router.serve_with_incoming_shutdown(accept_connections(listener.clone()), async {
+                stop_rx.await.ok();
+            }));
+
+            router = loop {
+                select! {
+                    res = &mut server => {
+                        match res {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => error!(error = %e, "gRPC sunucusu çöktü."),
+                            Err(e) => error!(error = %e, "gRPC sunucu görevi beklenmedik şekilde sonlandı."),
+                        }
+                        return;
+                    }
+                    changed = tls_reload.changed() => {
+                        if changed.is_err() {
+                            // İzleyici sonlandı; sunucu mevcut sertifikalarla çalışmaya devam eder.
+                            let _ = (&mut server).await;
+                            return;
+                        }
+                        match build_grpc_router(&config, &grpc_service).await {
+                            Ok(router) => break router,
+                            Err(e) => error!(error = %e, "Yeni TLS yapılandırması yüklenemedi; mevcut sertifikalarla devam ediliyor."),
+                        }
+                    }
+                }
+            };
+            // Eski sunucu yeni bağlantı kabul etmeyi bırakır; açık bağlantılar tamamlanana kadar arka planda çalışır.
+            let _ = stop_tx.send(());
+            info!("gRPC sunucusu yeni TLS kimliğiyle yeniden başlatıldı.");
         }
     })
 }
 
-async fn load_tls_config(config: &AppConfig) -> Result<tonic::transport::ServerTlsConfig> {
-    let cert = tokio::fs::read(&config.cert_path).await?;
-    let key = tokio::fs::read(&config.key_path).await?;
-    let identity = Identity::from_pem(cert, key);
-    let ca_cert = tokio::fs::read(&config.ca_path).await?;
-    let client_ca_cert = Certificate::from_pem(ca_cert);
-    Ok(ServerTlsConfig::new().identity(identity).client_ca_root(client_ca_cert))
-}
\ No newline at end of file
+async fn build_grpc_router(config: &AppConfig, grpc_service: &Arc<MySipSignalingService>) -> Result<Router> {
+    let tls_config = tls::load_server_tls_config(config).await.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(GrpcServer::builder()
+        .tls_config(tls_config)?
+        .add_service(SipSignalingServiceServer::from_arc(grpc_service.clone())))
+}
+
+fn accept_connections(listener: Arc<TcpListener>) -> impl Stream<Item = Result<TcpStream, std::io::Error>> {
+    futures_util::stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    stream.set_nodelay(true).ok();
+                    return Some((Ok(stream), listener));
+                }
+                Err(e) => warn!(error = %e, "gRPC bağlantısı kabul edilemedi."),
+            }
+        }
+    })
+}
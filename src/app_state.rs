@@ -1,26 +1,34 @@
 // File: src/app_state.rs
 use crate::config::AppConfig;
 use crate::error::ServiceError;
+use crate::events::{self, CallEventBus};
 use crate::grpc::client::create_all_grpc_clients;
+use crate::grpc::media_pool::MediaPool;
+use crate::grpc::resilience::CircuitBreakers;
 use crate::rabbitmq;
 use crate::redis;
+use crate::sip::invite::server_transaction::PendingInvites;
+use crate::sip::transaction::ClientTransactions;
 use crate::state::ActiveCalls;
+use crate::trunk::registration::TrunkRegistrations;
+use crate::trunk::routing::TrunkAttempts;
 use lapin::Channel as LapinChannel;
 use redis::Client as RedisClient;
 use sentiric_contracts::sentiric::{
     dialplan::v1::dialplan_service_client::DialplanServiceClient,
-    media::v1::media_service_client::MediaServiceClient,
     user::v1::user_service_client::UserServiceClient,
 };
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration; // YENİ
 use tonic::transport::Channel as GrpcChannel;
 use tracing::{info, warn}; // YENİ
 
+#[derive(Clone)]
 pub struct GrpcClients {
     pub user: UserServiceClient<GrpcChannel>,
     pub dialplan: DialplanServiceClient<GrpcChannel>,
-    pub media: MediaServiceClient<GrpcChannel>,
+    pub media: Arc<MediaPool>,
 }
 
 pub struct AppState {
@@ -28,7 +36,16 @@ pub struct AppState {
     pub active_calls: ActiveCalls,
     pub redis: Arc<RedisClient>,
     pub rabbit: Option<Arc<LapinChannel>>,
-    pub grpc: GrpcClients,
+    /// Sertifika rotasyonunda yeniden kurulan istemciler; bkz. `grpc_clients`.
+    pub grpc: RwLock<GrpcClients>,
+    pub client_transactions: ClientTransactions,
+    pub pending_invites: PendingInvites,
+    pub trunk_registrations: TrunkRegistrations,
+    pub trunk_attempts: TrunkAttempts,
+    pub call_events: CallEventBus,
+    pub circuit_breakers: CircuitBreakers,
+    /// Kapatma sinyali alındı; yeni çağrılar reddedilir, aktif çağrıların bitmesi beklenir.
+    pub draining: AtomicBool,
 }
 
 impl AppState {
@@ -69,14 +86,83 @@ impl AppState {
             active_calls: Arc::new(Default::default()),
             redis: redis_client,
             rabbit: None,
-            grpc: grpc_clients.expect("gRPC başlatma döngüsü `None` ile bitemez."),
+            grpc: RwLock::new(grpc_clients.expect("gRPC başlatma döngüsü `None` ile bitemez.")),
+            client_transactions: Arc::new(Default::default()),
+            pending_invites: Arc::new(Default::default()),
+            trunk_registrations: Arc::new(Default::default()),
+            trunk_attempts: Arc::new(Default::default()),
+            call_events: events::new_bus(),
+            circuit_breakers: CircuitBreakers::default(),
+            draining: AtomicBool::new(false),
         })
     }
     
+    /// Güncel gRPC istemcilerinin bir kopyası. Kanallar paylaşımlıdır; kopyalamak ucuzdur ve devam eden
+    /// istekler, istemciler değiştirilse bile eski kanal üzerinden tamamlanır.
+    pub fn grpc_clients(&self) -> GrpcClients {
+        self.grpc.read().unwrap().clone()
+    }
+
+    pub fn replace_grpc_clients(&self, clients: GrpcClients) {
+        *self.grpc.write().unwrap() = clients;
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
     pub async fn connect_rabbitmq(&mut self) {
         if let Ok(rabbit_channel) = rabbitmq::connection::try_connect(&self.config.rabbitmq_url).await {
             rabbitmq::connection::declare_exchange(&rabbit_channel).await.ok();
             self.rabbit = Some(Arc::new(rabbit_channel));
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Hiçbir bağımlılığa bağlanmayan bir durum: gRPC kanalları tembeldir, media havuzu boştur,
+    /// Redis istemcisi bağlantı kurmaz ve RabbitMQ yoktur.
+    pub fn for_tests() -> Self {
+        use crate::config::PlatformConfig;
+        use tonic::transport::Endpoint;
+
+        let platform_config: PlatformConfig = serde_json::from_value(serde_json::json!({
+            "grpc_tls_ca_path": "",
+            "redis_url": "redis://127.0.0.1/",
+            "rabbitmq_url": "amqp://127.0.0.1",
+            "media_service_target_grpc_url": "http://127.0.0.1:1",
+            "user_service_target_grpc_url": "http://127.0.0.1:1",
+            "dialplan_service_target_grpc_url": "http://127.0.0.1:1",
+            "sip_signaling_service_sip_port": 5060,
+            "sip_signaling_service_realm": "sentiric.test",
+            "sip_signaling_service_public_ip": "127.0.0.1",
+            "sip_signaling_service_nat_traversal": "none",
+            "sip_signaling_service_cert_path": "",
+            "sip_signaling_service_key_path": "",
+            "media_service_public_ip": "127.0.0.1",
+        }))
+        .expect("Test platform yapılandırması geçersiz");
+        let config = AppConfig::try_from(Arc::new(platform_config)).expect("Test yapılandırması geçersiz");
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy();
+
+        AppState {
+            config: Arc::new(config),
+            active_calls: Arc::new(Default::default()),
+            redis: Arc::new(RedisClient::open("redis://127.0.0.1/").expect("Geçersiz Redis URL'si")),
+            rabbit: None,
+            grpc: RwLock::new(GrpcClients {
+                user: UserServiceClient::new(channel.clone()),
+                dialplan: DialplanServiceClient::new(channel),
+                media: Arc::new(MediaPool::default()),
+            }),
+            client_transactions: Arc::new(Default::default()),
+            pending_invites: Arc::new(Default::default()),
+            trunk_registrations: Arc::new(Default::default()),
+            trunk_attempts: Arc::new(Default::default()),
+            call_events: events::new_bus(),
+            circuit_breakers: CircuitBreakers::default(),
+            draining: AtomicBool::new(false),
+        }
+    }
+}
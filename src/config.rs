@@ -1,7 +1,9 @@
 // sentiric-sip-signaling-service/src/config.rs
-use anyhow::Result;
+use crate::sip::responses::parse_status_line;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
-use std::{net::SocketAddr, sync::Arc}; 
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 // ===================================================================
 //  Bölüm 1: Platform Seviyesi Yapılandırma
@@ -29,6 +31,102 @@ pub struct PlatformConfig {
     // [KRİTİK] Dış IP
     pub sip_signaling_service_public_ip: String,
     
+    // INVITE'ları 407 ile doğrulanacak realm'ler (virgülle ayrılmış). Boşsa INVITE doğrulaması kapalıdır.
+    #[serde(default)]
+    pub sip_signaling_service_invite_auth_realms: Option<String>,
+
+    // NAT arkasındaki istemcilerin Contact'ı, REGISTER'ın geldiği adresle yeniden yazılsın mı?
+    #[serde(default)]
+    pub sip_signaling_service_nat_contact_rewrite: bool,
+
+    #[serde(default = "default_metrics_port")]
+    pub sip_signaling_service_metrics_port: u16,
+
+    // Upstream SIP trunk tanımları (JSON dizisi). Bkz. `TrunkConfig`.
+    #[serde(default)]
+    pub sip_signaling_service_trunks: Option<String>,
+    // Giden çağrılar için prefix -> trunk yönlendirme tablosu (JSON dizisi). Bkz. `TrunkRouteConfig`.
+    #[serde(default)]
+    pub sip_signaling_service_trunk_routes: Option<String>,
+    // gRPC metodu -> izin verilen istemci kimlikleri (CN/SAN/SPIFFE ID) (JSON nesnesi). Boşsa yalnızca mTLS uygulanır.
+    #[serde(default)]
+    pub sip_signaling_service_grpc_allow_list: Option<String>,
+    // TLS dosyalarının değişiklik için kontrol aralığı (saniye). 0: yalnızca SIGHUP ile yeniden yüklenir.
+    #[serde(default = "default_tls_reload_interval")]
+    pub sip_signaling_service_tls_reload_interval_seconds: u64,
+
+    // Media-service örnekleri (virgülle ayrılmış URL'ler; `dns:host:port` girdileri DNS ile çözülür).
+    // Boşsa yalnızca MEDIA_SERVICE_TARGET_GRPC_URL kullanılır.
+    #[serde(default)]
+    pub sip_signaling_service_media_endpoints: Option<String>,
+    // least_calls | round_robin | tenant_affinity
+    #[serde(default = "default_media_selection_policy")]
+    pub sip_signaling_service_media_selection_policy: String,
+    #[serde(default = "default_media_health_interval")]
+    pub sip_signaling_service_media_health_interval_seconds: u64,
+    // Gelen çağrılarda medya NAT geçişi: auto | none | silence_burst | symmetric_rtp | comedia
+    #[serde(default = "default_nat_traversal")]
+    pub sip_signaling_service_nat_traversal: String,
+    // 100 Trying'den hemen sonra, kurulum beklenmeden gönderilecek geçici yanıt: 180 | 183 | none
+    #[serde(default = "default_immediate_provisional")]
+    pub sip_signaling_service_immediate_provisional: String,
+    // Çatallanmayan çağrılarda cevaplamadan önce çaldırma süresi (ms).
+    #[serde(default = "default_ring_duration_ms")]
+    pub sip_signaling_service_ring_duration_ms: u64,
+    // Son geçici yanıtın yeniden gönderilme aralığı (saniye). 0: yenileme yapılmaz.
+    #[serde(default = "default_provisional_refresh_seconds")]
+    pub sip_signaling_service_provisional_refresh_seconds: u64,
+    // Erken medya (183) anonsunun en uzun süresi (saniye).
+    #[serde(default = "default_early_media_max_seconds")]
+    pub sip_signaling_service_early_media_max_seconds: u64,
+    // RFC 4028 oturum süresi (Session-Expires, saniye). 0: oturum zamanlayıcısı önerilmez.
+    #[serde(default = "default_session_expires_seconds")]
+    pub sip_signaling_service_session_expires_seconds: u64,
+    // Kabul edilen en kısa oturum süresi (Min-SE, saniye); RFC 4028 alt sınırı 90'dır.
+    #[serde(default = "default_session_min_se_seconds")]
+    pub sip_signaling_service_session_min_se_seconds: u64,
+    // Oturum zamanlayıcısı olmayan diyaloglar bu süre sessiz kalırsa OPTIONS ile yoklanır (saniye).
+    #[serde(default = "default_dialog_probe_interval_seconds")]
+    pub sip_signaling_service_dialog_probe_interval_seconds: u64,
+    // Cevaplanan bir çağrının en uzun süresi (saniye). 0: sınır yok.
+    #[serde(default)]
+    pub sip_signaling_service_max_call_duration_seconds: u64,
+    // Kiracı -> en uzun çağrı süresi (saniye, JSON nesnesi); tanımlı kiracılar için genel ayarı ezer.
+    #[serde(default)]
+    pub sip_signaling_service_max_call_duration_tenants: Option<String>,
+    // Süre dolmadan önce çalınacak uyarı anonsunun URI'si; boşsa uyarı çalınmaz.
+    #[serde(default)]
+    pub sip_signaling_service_max_duration_warning_uri: Option<String>,
+    // Uyarı anonsunun süre dolmadan kaç saniye önce çalınacağı.
+    #[serde(default = "default_max_duration_warning_seconds")]
+    pub sip_signaling_service_max_duration_warning_seconds: u64,
+    // Kapatma sinyalinden sonra aktif çağrıların bitmesinin bekleneceği en uzun süre (saniye).
+    #[serde(default = "default_drain_timeout_seconds")]
+    pub sip_signaling_service_drain_timeout_seconds: u64,
+    // Kiracı -> NAT geçiş stratejisi (JSON nesnesi); tanımlı kiracılar için genel ayarı ezer.
+    #[serde(default)]
+    pub sip_signaling_service_nat_traversal_tenants: Option<String>,
+
+    // --- BAĞIMLILIK DAYANIKLILIĞI ---
+    // INVITE alındıktan sonra dialplan/medya çağrılarının tamamlanması için toplam süre (ms).
+    #[serde(default = "default_invite_setup_budget_ms")]
+    pub sip_signaling_service_invite_setup_budget_ms: u64,
+    // Tek bir gRPC denemesinin üst süre sınırı (ms).
+    #[serde(default = "default_grpc_timeout_ms")]
+    pub sip_signaling_service_grpc_timeout_ms: u64,
+    // Idempotent çağrılar için en fazla tekrar sayısı.
+    #[serde(default = "default_grpc_max_retries")]
+    pub sip_signaling_service_grpc_max_retries: u32,
+    // Devre kesicinin açılması için ardışık hata sayısı.
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub sip_signaling_service_circuit_failure_threshold: u32,
+    // Devrenin açık kalma süresi (saniye); ardından tek bir deneme isteğine izin verilir.
+    #[serde(default = "default_circuit_open_seconds")]
+    pub sip_signaling_service_circuit_open_seconds: u64,
+    // Devre açıkken INVITE'a verilecek yanıt (örn. "503 Service Unavailable", "480 Temporarily Unavailable").
+    #[serde(default = "default_circuit_open_sip_response")]
+    pub sip_signaling_service_circuit_open_sip_response: String,
+
     pub sip_signaling_service_cert_path: String,
     pub sip_signaling_service_key_path: String,
     
@@ -50,6 +148,156 @@ impl PlatformConfig {
 fn default_env() -> String { "development".to_string() }
 fn default_rust_log() -> String { "info".to_string() }
 fn default_version() -> String { "1.0.0".to_string() }
+fn default_metrics_port() -> u16 { 13022 }
+fn default_tls_reload_interval() -> u64 { 30 }
+fn default_invite_setup_budget_ms() -> u64 { 4000 }
+fn default_media_selection_policy() -> String { "least_calls".to_string() }
+fn default_media_health_interval() -> u64 { 10 }
+fn default_nat_traversal() -> String { "auto".to_string() }
+fn default_early_media_max_seconds() -> u64 { 60 }
+fn default_max_duration_warning_seconds() -> u64 { 30 }
+fn default_drain_timeout_seconds() -> u64 { 30 }
+fn default_immediate_provisional() -> String { "180".to_string() }
+fn default_ring_duration_ms() -> u64 { 50 }
+fn default_provisional_refresh_seconds() -> u64 { 60 }
+fn default_session_expires_seconds() -> u64 { 1800 }
+fn default_session_min_se_seconds() -> u64 { 90 }
+fn default_dialog_probe_interval_seconds() -> u64 { 300 }
+fn default_grpc_timeout_ms() -> u64 { 2000 }
+fn default_grpc_max_retries() -> u32 { 2 }
+fn default_circuit_failure_threshold() -> u32 { 5 }
+fn default_circuit_open_seconds() -> u64 { 30 }
+fn default_circuit_open_sip_response() -> String { "503 Service Unavailable".to_string() }
+fn default_trunk_port() -> u16 { 5060 }
+fn default_register_expires() -> u64 { 3600 }
+fn default_route_priority() -> u32 { 100 }
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrunkTransport {
+    #[default]
+    Udp,
+    Tcp,
+    Tls,
+}
+
+/// Bir upstream SIP trunk'ının (taşıyıcı) tanımı.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrunkConfig {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_trunk_port")]
+    pub port: u16,
+    /// Kayıt ve kimlik doğrulamada kullanılan alan adı. Boşsa `host` kullanılır.
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// `true` ise servis bu trunk'a UAC olarak REGISTER gönderir.
+    #[serde(default)]
+    pub register: bool,
+    #[serde(default = "default_register_expires")]
+    pub register_expires: u64,
+    /// Şu an yalnızca `udp` desteklenir; diğer taşıma türlerindeki trunk'lar yönlendirmede atlanır.
+    #[serde(default)]
+    pub transport: TrunkTransport,
+    /// Hedef numara bu önekle başlıyorsa gönderilmeden önce silinir (örn. "0").
+    #[serde(default)]
+    pub strip_prefix: Option<String>,
+    /// Hedef numaranın başına eklenir (örn. "+90").
+    #[serde(default)]
+    pub add_prefix: Option<String>,
+    /// Eşzamanlı giden çağrı sınırı. Boşsa sınırsızdır.
+    #[serde(default)]
+    pub max_channels: Option<u32>,
+}
+
+impl TrunkConfig {
+    pub fn domain(&self) -> &str {
+        self.domain.as_deref().unwrap_or(&self.host)
+    }
+
+    /// Hedef numarayı trunk'ın beklediği biçime çevirir.
+    pub fn rewrite_number(&self, number: &str) -> String {
+        let stripped = match self.strip_prefix.as_deref() {
+            Some(prefix) if !prefix.is_empty() => number.strip_prefix(prefix).unwrap_or(number),
+            _ => number,
+        };
+        format!("{}{}", self.add_prefix.as_deref().unwrap_or_default(), stripped)
+    }
+}
+
+/// Yeni bir çağrı için medya örneği seçim politikası.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaSelectionPolicy {
+    /// En az aktif çağrısı olan örnek.
+    LeastCalls,
+    RoundRobin,
+    /// Aynı kiracının çağrıları mümkün olduğunca aynı örneğe gider; kiracı bilinmiyorsa `LeastCalls`.
+    TenantAffinity,
+}
+
+impl MediaSelectionPolicy {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value.trim().to_lowercase().as_str() {
+            "least_calls" => Self::LeastCalls,
+            "round_robin" => Self::RoundRobin,
+            "tenant_affinity" => Self::TenantAffinity,
+            other => anyhow::bail!("Geçersiz SIP_SIGNALING_SERVICE_MEDIA_SELECTION_POLICY: {}", other),
+        })
+    }
+}
+
+/// Gelen çağrının medyasının NAT arkasındaki arayana ulaşması için uygulanan strateji.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatTraversal {
+    /// SDP ve paket kaynağına bakarak aşağıdakilerden biri seçilir.
+    Auto,
+    /// SDP'deki adrese doğrudan gönderilir.
+    None,
+    /// NAT'ta delik açmak için SDP adresine kısa bir sessizlik çalınır.
+    SilenceBurst,
+    /// Media-service, SDP adresi yerine ilk RTP paketinin geldiği adrese gönderir (symmetric RTP).
+    SymmetricRtp,
+    /// Arayan `a=direction:active` ile önce gönderir; cevap `a=direction:passive` ile verilir ve adres öğrenilir.
+    Comedia,
+}
+
+impl NatTraversal {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(match value.trim().to_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "none" => Self::None,
+            "silence_burst" => Self::SilenceBurst,
+            "symmetric_rtp" => Self::SymmetricRtp,
+            "comedia" => Self::Comedia,
+            other => anyhow::bail!("Geçersiz NAT geçiş stratejisi: {}", other),
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::None => "none",
+            Self::SilenceBurst => "silence_burst",
+            Self::SymmetricRtp => "symmetric_rtp",
+            Self::Comedia => "comedia",
+        }
+    }
+}
+
+/// Yönlendirme tablosunun bir satırı: `prefix` ile başlayan hedefler `trunk` üzerinden gönderilir.
+/// En uzun eşleşen önek kazanır; eşitlikte düşük `priority` önce denenir, diğerleri yedek olur.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrunkRouteConfig {
+    #[serde(default)]
+    pub prefix: String,
+    pub trunk: String,
+    #[serde(default = "default_route_priority")]
+    pub priority: u32,
+}
 
 // ===================================================================
 //  Bölüm 2: Servise Özel Yapılandırma
@@ -62,36 +310,149 @@ pub struct AppConfig {
     pub cert_path: String,
     pub key_path: String,
     pub ca_path: String,
+    pub tls_reload_interval_secs: u64,
     pub sip_listen_addr: SocketAddr,
+    pub metrics_listen_addr: SocketAddr,
     pub sip_realm: String,
     pub sip_public_ip: String, // Public IP burada saklanacak
-    pub media_service_url: String,
+    pub media_endpoints: Vec<String>,
+    pub media_selection_policy: MediaSelectionPolicy,
+    pub media_health_interval: Duration,
     pub dialplan_service_url: String,
     pub user_service_url: String,
     pub rabbitmq_url: String,
     pub redis_url: String,
-
+    pub invite_auth_realms: Vec<String>,
+    pub nat_contact_rewrite: bool,
+    pub nat_traversal: NatTraversal,
+    pub nat_traversal_tenants: HashMap<String, NatTraversal>,
+    pub early_media_max_duration: Duration,
+    pub max_call_duration: Option<Duration>,
+    /// Kiracı -> en uzun çağrı süresi; `None` değeri kiracı için sınırı kaldırır.
+    pub max_call_duration_tenants: HashMap<String, Option<Duration>>,
+    pub max_duration_warning_uri: Option<String>,
+    pub max_duration_warning_lead: Duration,
+    /// INVITE alınır alınmaz gönderilecek geçici yanıtın durum satırı (`None`: gönderilmez).
+    pub immediate_provisional: Option<&'static str>,
+    pub ring_duration: Duration,
+    pub provisional_refresh_interval: Option<Duration>,
+    /// Önerilen oturum süresi (`None`: arayan istemedikçe oturum zamanlayıcısı kullanılmaz).
+    pub session_expires: Option<Duration>,
+    pub session_min_se: Duration,
+    pub dialog_probe_interval: Duration,
+    pub drain_timeout: Duration,
+    pub trunks: Vec<TrunkConfig>,
+    pub trunk_routes: Vec<TrunkRouteConfig>,
+    /// gRPC metod adı (örn. `TerminateCall`, varsayılan için `*`) -> izin verilen kimlik desenleri.
+    pub grpc_allow_list: HashMap<String, Vec<String>>,
+    pub invite_setup_budget: Duration,
+    pub grpc_timeout: Duration,
+    pub grpc_max_retries: u32,
+    pub circuit_failure_threshold: u32,
+    pub circuit_open_duration: Duration,
+    pub circuit_open_sip_response: String,
 }
 
-impl From<Arc<PlatformConfig>> for AppConfig {
-    fn from(pc: Arc<PlatformConfig>) -> Self {
-        Self {
+impl TryFrom<Arc<PlatformConfig>> for AppConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(pc: Arc<PlatformConfig>) -> Result<Self> {
+        Ok(Self {
             env: pc.env.clone(),
             service_version: pc.service_version.clone(),
             rust_log: pc.rust_log.clone(),
             cert_path: pc.sip_signaling_service_cert_path.clone(),
             key_path: pc.sip_signaling_service_key_path.clone(),
             ca_path: pc.grpc_tls_ca_path.clone(),
+            tls_reload_interval_secs: pc.sip_signaling_service_tls_reload_interval_seconds,
             sip_listen_addr: format!("0.0.0.0:{}", pc.sip_signaling_service_sip_port)
                 .parse()
-                .expect("Geçersiz SIP portu"),
+                .context("Geçersiz SIP portu")?,
+            metrics_listen_addr: format!("0.0.0.0:{}", pc.sip_signaling_service_metrics_port)
+                .parse()
+                .context("Geçersiz metrik portu")?,
             sip_realm: pc.sip_signaling_service_realm.clone(),
             sip_public_ip: pc.sip_signaling_service_public_ip.clone(),
-            media_service_url: pc.media_service_target_grpc_url.clone(),
+            media_endpoints: Some(split_list(pc.sip_signaling_service_media_endpoints.as_deref()))
+                .filter(|endpoints| !endpoints.is_empty())
+                .unwrap_or_else(|| vec![pc.media_service_target_grpc_url.clone()]),
+            media_selection_policy: MediaSelectionPolicy::parse(&pc.sip_signaling_service_media_selection_policy)?,
+            media_health_interval: Duration::from_secs(pc.sip_signaling_service_media_health_interval_seconds.max(1)),
             dialplan_service_url: pc.dialplan_service_target_grpc_url.clone(),
             user_service_url: pc.user_service_target_grpc_url.clone(),
             rabbitmq_url: pc.rabbitmq_url.clone(),
             redis_url: pc.redis_url.clone(),
-        }
+            invite_auth_realms: split_list(pc.sip_signaling_service_invite_auth_realms.as_deref()),
+            nat_contact_rewrite: pc.sip_signaling_service_nat_contact_rewrite,
+            nat_traversal: NatTraversal::parse(&pc.sip_signaling_service_nat_traversal)
+                .context("Geçersiz SIP_SIGNALING_SERVICE_NAT_TRAVERSAL")?,
+            nat_traversal_tenants: parse_json::<HashMap<String, String>>(pc.sip_signaling_service_nat_traversal_tenants.as_deref(), "SIP_SIGNALING_SERVICE_NAT_TRAVERSAL_TENANTS")?
+                .into_iter()
+                .map(|(tenant, strategy)| {
+                    let strategy = NatTraversal::parse(&strategy)
+                        .with_context(|| format!("Geçersiz SIP_SIGNALING_SERVICE_NAT_TRAVERSAL_TENANTS: '{}' kiracısı", tenant))?;
+                    Ok((tenant, strategy))
+                })
+                .collect::<Result<_>>()?,
+            early_media_max_duration: Duration::from_secs(pc.sip_signaling_service_early_media_max_seconds.max(1)),
+            max_call_duration: Some(pc.sip_signaling_service_max_call_duration_seconds)
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            max_call_duration_tenants: parse_json::<HashMap<String, u64>>(pc.sip_signaling_service_max_call_duration_tenants.as_deref(), "SIP_SIGNALING_SERVICE_MAX_CALL_DURATION_TENANTS")?
+                .into_iter()
+                .map(|(tenant, secs)| (tenant, Some(secs).filter(|s| *s > 0).map(Duration::from_secs)))
+                .collect(),
+            max_duration_warning_uri: pc.sip_signaling_service_max_duration_warning_uri.clone().filter(|uri| !uri.is_empty()),
+            max_duration_warning_lead: Duration::from_secs(pc.sip_signaling_service_max_duration_warning_seconds),
+            immediate_provisional: match pc.sip_signaling_service_immediate_provisional.trim() {
+                "180" => Some("180 Ringing"),
+                "183" => Some("183 Session Progress"),
+                "none" | "" => None,
+                other => anyhow::bail!("Geçersiz SIP_SIGNALING_SERVICE_IMMEDIATE_PROVISIONAL: {}", other),
+            },
+            ring_duration: Duration::from_millis(pc.sip_signaling_service_ring_duration_ms),
+            provisional_refresh_interval: Some(pc.sip_signaling_service_provisional_refresh_seconds)
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            session_expires: Some(pc.sip_signaling_service_session_expires_seconds)
+                .filter(|secs| *secs > 0)
+                .map(|secs| Duration::from_secs(secs.max(pc.sip_signaling_service_session_min_se_seconds.max(90)))),
+            session_min_se: Duration::from_secs(pc.sip_signaling_service_session_min_se_seconds.max(90)),
+            dialog_probe_interval: Duration::from_secs(pc.sip_signaling_service_dialog_probe_interval_seconds.max(30)),
+            drain_timeout: Duration::from_secs(pc.sip_signaling_service_drain_timeout_seconds),
+            trunks: parse_json(pc.sip_signaling_service_trunks.as_deref(), "SIP_SIGNALING_SERVICE_TRUNKS")?,
+            trunk_routes: parse_json(pc.sip_signaling_service_trunk_routes.as_deref(), "SIP_SIGNALING_SERVICE_TRUNK_ROUTES")?,
+            grpc_allow_list: parse_json(pc.sip_signaling_service_grpc_allow_list.as_deref(), "SIP_SIGNALING_SERVICE_GRPC_ALLOW_LIST")?,
+            invite_setup_budget: Duration::from_millis(pc.sip_signaling_service_invite_setup_budget_ms),
+            grpc_timeout: Duration::from_millis(pc.sip_signaling_service_grpc_timeout_ms),
+            grpc_max_retries: pc.sip_signaling_service_grpc_max_retries,
+            circuit_failure_threshold: pc.sip_signaling_service_circuit_failure_threshold.max(1),
+            circuit_open_duration: Duration::from_secs(pc.sip_signaling_service_circuit_open_seconds),
+            // Kurulum hatalarında arayana aynen gönderildiği için başlangıçta doğrulanır.
+            circuit_open_sip_response: match parse_status_line(&pc.sip_signaling_service_circuit_open_sip_response) {
+                Some((code, line)) if code >= 300 => line,
+                _ => anyhow::bail!(
+                    "Geçersiz SIP_SIGNALING_SERVICE_CIRCUIT_OPEN_SIP_RESPONSE: {:?} ('NNN Gerekçe' biçiminde 3xx-6xx bir durum satırı olmalı)",
+                    pc.sip_signaling_service_circuit_open_sip_response
+                ),
+            },
+        })
+    }
+}
+
+/// JSON olarak verilen isteğe bağlı bir ortam değişkenini ayrıştırır; tanımsızsa varsayılan değer döner.
+fn parse_json<T: DeserializeOwned + Default>(value: Option<&str>, variable: &str) -> Result<T> {
+    match value {
+        Some(json) => serde_json::from_str(json).with_context(|| format!("Geçersiz {} JSON'u", variable)),
+        None => Ok(T::default()),
     }
-}
\ No newline at end of file
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
@@ -39,6 +39,9 @@ pub enum ServiceError {
     #[error("Loglama filtresi hatası: {0}")]
     TracingFilter(#[from] tracing_subscriber::filter::ParseError),
 
+    #[error("{0} için devre kesici açık, istek gönderilmedi.")]
+    CircuitOpen(&'static str),
+
     #[error("Beklenmedik bir hata oluştu: {0}")]
     Generic(String),
 }
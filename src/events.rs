@@ -0,0 +1,63 @@
+// File: sentiric-sip-signaling-service/src/events.rs
+// Süreç içi çağrı yaşam döngüsü olayları. RabbitMQ yayınından bağımsızdır; `WatchCalls` gRPC
+// akışı bu kanala abone olur.
+use crate::app_state::AppState;
+use crate::state::{ActiveCallInfo, CallDirection};
+use tokio::sync::broadcast;
+
+/// Yavaş bir abone bu kadar olay geride kalırsa en eski olayları kaybeder (`Lagged`).
+const CALL_EVENT_BUFFER: usize = 1024;
+
+pub type CallEventBus = broadcast::Sender<CallEvent>;
+
+#[derive(Clone, Debug)]
+pub struct CallEvent {
+    /// `call.started`, `call.ringing`, `call.early_media`, `call.answered`, `call.held`, `call.resumed`, `call.ended`, `call.failed`.
+    pub event_type: String,
+    pub call_id: String,
+    pub trace_id: String,
+    pub direction: CallDirection,
+    pub caller_uri: String,
+    pub tenant_id: Option<String>,
+    pub trunk: Option<String>,
+    pub timestamp: String,
+    /// Olaya özgü alanlar (ör. `reason`, `statusCode`); RabbitMQ olaylarındaki adlarla.
+    pub details: serde_json::Value,
+}
+
+impl CallEvent {
+    pub fn new(event_type: &str, call_id: &str, trace_id: &str, direction: CallDirection, caller_uri: &str) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            call_id: call_id.to_string(),
+            trace_id: trace_id.to_string(),
+            direction,
+            caller_uri: caller_uri.to_string(),
+            tenant_id: None,
+            trunk: None,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            details: serde_json::Value::Null,
+        }
+    }
+
+    pub fn for_call(event_type: &str, call_info: &ActiveCallInfo) -> Self {
+        let mut event = Self::new(event_type, &call_info.call_id, &call_info.trace_id, call_info.direction, call_info.caller_uri());
+        event.tenant_id = call_info.tenant_id.clone();
+        event.trunk = call_info.trunk.clone();
+        event
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+pub fn new_bus() -> CallEventBus {
+    broadcast::channel(CALL_EVENT_BUFFER).0
+}
+
+/// Olayı tüm abonelere iletir. Abone yoksa olay sessizce düşer.
+pub fn emit(state: &AppState, event: CallEvent) {
+    let _ = state.call_events.send(event);
+}
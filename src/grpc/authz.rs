@@ -0,0 +1,130 @@
+// File: sentiric-sip-signaling-service/src/grpc/authz.rs
+// mTLS istemci sertifikasına dayalı metod bazında yetkilendirme ve çağrı kontrolü denetim kaydı.
+use crate::app_state::AppState;
+use crate::rabbitmq::publisher::publish_event;
+use std::collections::HashMap;
+use tonic::{Request, Status};
+use tracing::{info, warn};
+use x509_parser::extensions::GeneralName;
+
+const AUDIT_EVENT: &str = "audit.grpc_call";
+
+/// Durum değiştiren (çağrı kontrolü) metodlar her zaman denetim kaydına yazılır.
+const CALL_CONTROL_METHODS: &[&str] = &["TerminateCall", "OriginateCall", "HoldCall", "TransferCall"];
+
+/// İstemci sertifikasından çıkarılan kimlikler.
+pub struct PeerIdentity {
+    common_name: Option<String>,
+    /// DNS ve URI (SPIFFE ID dahil) SAN değerleri.
+    sans: Vec<String>,
+}
+
+impl PeerIdentity {
+    fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(san)) => san
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::URI(uri) => Some(uri.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some(Self { common_name, sans })
+    }
+
+    /// Kayıtlarda kullanılan kimlik: SPIFFE ID, yoksa CN, yoksa ilk SAN.
+    pub fn principal(&self) -> String {
+        self.sans
+            .iter()
+            .find(|s| s.starts_with("spiffe://"))
+            .or(self.common_name.as_ref())
+            .or(self.sans.first())
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.common_name.iter().chain(self.sans.iter()).map(String::as_str)
+    }
+}
+
+/// İsteği yapan istemcinin yaprak (leaf) sertifikasındaki kimlik.
+pub fn peer_identity<T>(request: &Request<T>) -> Option<PeerIdentity> {
+    let certs = request.peer_certs()?;
+    PeerIdentity::from_der(certs.first()?.get_ref())
+}
+
+/// `method` için izin listesini uygular. Liste boşsa yetkilendirme kapalıdır (yalnızca mTLS).
+/// Metoda özel girdi yoksa `*` girdisi kullanılır; o da yoksa istek reddedilir.
+fn is_allowed(allow_list: &HashMap<String, Vec<String>>, method: &str, identity: Option<&PeerIdentity>) -> bool {
+    if allow_list.is_empty() {
+        return true;
+    }
+    let (Some(patterns), Some(identity)) = (allow_list.get(method).or_else(|| allow_list.get("*")), identity) else {
+        return false;
+    };
+    patterns.iter().any(|pattern| identity.names().any(|name| matches_pattern(pattern, name)))
+}
+
+/// Sonu `*` ile biten desenler önek olarak eşleşir (örn. `spiffe://sentiric/*`); `*` her kimliğe uyar.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+/// İstemciyi `method` için yetkilendirir ve kimliğini döndürür. Çağrı kontrolü metodları ile tüm
+/// reddedilen istekler denetim kaydına yazılır; `subject` etkilenen çağrı veya hedeftir.
+pub fn authorize<T>(state: &AppState, method: &str, request: &Request<T>, subject: &str) -> Result<String, Status> {
+    let identity = peer_identity(request);
+    let principal = identity.as_ref().map(PeerIdentity::principal).unwrap_or_else(|| "anonymous".to_string());
+    let allowed = is_allowed(&state.config.grpc_allow_list, method, identity.as_ref());
+
+    if allowed && !CALL_CONTROL_METHODS.contains(&method) {
+        return Ok(principal);
+    }
+    audit(state, method, &principal, subject, allowed);
+    if allowed {
+        Ok(principal)
+    } else {
+        Err(Status::permission_denied(format!("'{}' kimliğinin {} metodunu çağırma yetkisi yok.", principal, method)))
+    }
+}
+
+fn audit(state: &AppState, method: &str, principal: &str, subject: &str, allowed: bool) {
+    if allowed {
+        info!(target: "audit", method, principal, subject, allowed, "gRPC çağrı kontrolü isteği yetkilendirildi.");
+    } else {
+        warn!(target: "audit", method, principal, subject, allowed, "gRPC isteği yetkisiz olduğu için reddedildi.");
+    }
+
+    let Some(rabbit_channel) = state.rabbit.clone() else {
+        return;
+    };
+    let payload = serde_json::json!({
+        "eventType": AUDIT_EVENT,
+        "method": method,
+        "principal": principal,
+        "subject": subject,
+        "allowed": allowed,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    tokio::spawn(async move {
+        if let Err(e) = publish_event(&rabbit_channel, AUDIT_EVENT, &payload).await {
+            warn!(error = %e, "'{}' olayı yayınlanamadı.", AUDIT_EVENT);
+        }
+    });
+}
@@ -1,16 +1,18 @@
 // sentiric-sip-signaling-service/src/grpc/client.rs
-use crate::app_state::GrpcClients;
+use crate::app_state::{AppState, GrpcClients};
 use crate::config::AppConfig;
 use crate::error::ServiceError;
+use crate::grpc::media_pool::MediaPool;
 use sentiric_contracts::sentiric::{
     dialplan::v1::dialplan_service_client::DialplanServiceClient,
-    media::v1::media_service_client::MediaServiceClient,
     user::v1::user_service_client::UserServiceClient,
 };
 use std::error::Error;
+use std::sync::Arc;
 use std::time::Duration;
-use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
-use tracing::{info, instrument, warn};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tokio::sync::watch;
+use tracing::{error, info, instrument, warn};
 
 #[instrument(name = "grpc_client_setup", skip(config))]
 pub async fn create_all_grpc_clients(config: &AppConfig) -> Result<GrpcClients, ServiceError> {
@@ -35,27 +37,52 @@ pub async fn create_all_grpc_clients(config: &AppConfig) -> Result<GrpcClients,
         ))
     })?;
 
-    let media_channel = create_secure_grpc_channel(
-        &config.media_service_url,
-        "media-service",
-        config,
-    )
-    .await
-    .map_err(|e| ServiceError::Generic(format!("media-service'e bağlanırken hata oluştu: {}", e)))?;
+    let media_pool = MediaPool::connect(config)
+        .await
+        .map_err(|e| ServiceError::Generic(format!("media-service havuzu oluşturulurken hata oluştu: {}", e)))?;
 
     Ok(GrpcClients {
         user: UserServiceClient::new(user_channel),
         dialplan: DialplanServiceClient::new(dialplan_channel),
-        media: MediaServiceClient::new(media_channel),
+        media: Arc::new(media_pool),
     })
 }
 
-// --- DEĞİŞTİRİLMİŞ VE NİHAİ FONKSİYON ---
+/// Sertifikalar her yenilendiğinde user/dialplan/media kanallarını yeni kimlikle yeniden kurar.
+/// Eski kanallar, onları kullanan istekler bitene kadar yaşamaya devam eder.
+pub fn spawn_reloader(state: Arc<AppState>, mut reload: watch::Receiver<u64>) {
+    tokio::spawn(async move {
+        while reload.changed().await.is_ok() {
+            match create_all_grpc_clients(&state.config).await {
+                Ok(clients) => {
+                    state.replace_grpc_clients(clients);
+                    info!("Giden gRPC kanalları yeni sertifikalarla yeniden kuruldu.");
+                }
+                Err(e) => error!(error = %e, "Giden gRPC kanalları yeniden kurulamadı; mevcut kanallar kullanılmaya devam ediliyor."),
+            }
+        }
+    });
+}
+
 async fn create_secure_grpc_channel(
     url: &str,
     server_name: &str,
     config: &AppConfig,
 ) -> Result<Channel, Box<dyn Error + Send + Sync>> {
+    let endpoint = secure_endpoint(url, server_name, config).await?;
+    info!(url = %endpoint.uri(), server_name = %server_name, "Güvenli gRPC kanalına bağlanılıyor...");
+    let channel = endpoint.connect().await?;
+    info!(url = %endpoint.uri(), "gRPC bağlantısı başarılı.");
+    Ok(channel)
+}
+
+// --- DEĞİŞTİRİLMİŞ VE NİHAİ FONKSİYON ---
+/// mTLS ile yapılandırılmış, henüz bağlanmamış bir uç nokta oluşturur.
+pub async fn secure_endpoint(
+    url: &str,
+    server_name: &str,
+    config: &AppConfig,
+) -> Result<Endpoint, Box<dyn Error + Send + Sync>> {
     // 1. URL Normalizasyonu (Critical Fix for Double-Scheme Issue)
     // Gelen URL zaten "https://" içeriyorsa dokunma, "http://" ise "https://" yap, yoksa ekle.
     let target_url = if url.starts_with("https://") {
@@ -85,13 +112,9 @@ async fn create_secure_grpc_channel(
         // Kendi kimliğimizi (sertifika + anahtar) belirt
         .identity(client_identity);
 
-    // 5. Güvenli kanalı oluştur ve bağlan
-    let endpoint = Channel::from_shared(target_url.clone())?
+    // 5. Güvenli uç noktayı oluştur
+    let endpoint = Channel::from_shared(target_url)?
         .connect_timeout(Duration::from_secs(5))
         .tls_config(tls_config)?;
-
-    info!(url=%target_url, server_name=%server_name, "Güvenli gRPC kanalına bağlanılıyor...");
-    let channel = endpoint.connect().await?;
-    info!(url=%target_url, "gRPC bağlantısı başarılı.");
-    Ok(channel)
+    Ok(endpoint)
 }
\ No newline at end of file
@@ -0,0 +1,204 @@
+// File: sentiric-sip-signaling-service/src/grpc/media_pool.rs
+// Birden fazla media-service örneği: uç nokta keşfi (statik/DNS), sağlık kontrolü ve örnek seçimi.
+use crate::app_state::AppState;
+use crate::config::{AppConfig, MediaSelectionPolicy};
+use crate::grpc::client::secure_endpoint;
+use crate::telemetry::MEDIA_INSTANCE_HEALTHY;
+use sentiric_contracts::sentiric::media::v1::media_service_client::MediaServiceClient;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+use tonic_health::pb::{health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest};
+use tracing::{info, warn};
+
+/// TLS doğrulamasında beklenen sunucu adı; DNS ile çözülen IP'ler için de aynıdır.
+const MEDIA_SERVER_NAME: &str = "media-service";
+const DNS_PREFIX: &str = "dns:";
+
+pub struct MediaInstance {
+    /// Örneğin kalıcı kimliği (uç nokta URL'si). `ActiveCallInfo.media_instance` bu değeri tutar.
+    pub id: String,
+    channel: Channel,
+    healthy: AtomicBool,
+}
+
+impl MediaInstance {
+    pub fn client(&self) -> MediaServiceClient<Channel> {
+        MediaServiceClient::new(self.channel.clone())
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                info!(instance = %self.id, "Medya örneği yeniden sağlıklı.");
+            } else {
+                warn!(instance = %self.id, "Medya örneği sağlıksız olarak işaretlendi.");
+            }
+        }
+        metrics::gauge!(MEDIA_INSTANCE_HEALTHY, "instance" => self.id.clone()).set(if healthy { 1.0 } else { 0.0 });
+    }
+}
+
+#[derive(Default)]
+pub struct MediaPool {
+    instances: RwLock<Vec<Arc<MediaInstance>>>,
+    next: AtomicUsize,
+}
+
+impl MediaPool {
+    /// Yapılandırmadaki uç noktaları çözer ve her biri için (tembel bağlanan) bir kanal oluşturur.
+    pub async fn connect(config: &AppConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pool = Self { instances: RwLock::new(Vec::new()), next: AtomicUsize::new(0) };
+        pool.refresh(config).await?;
+        if pool.instances().is_empty() {
+            return Err("Hiçbir media-service uç noktası çözülemedi.".into());
+        }
+        Ok(pool)
+    }
+
+    pub fn instances(&self) -> Vec<Arc<MediaInstance>> {
+        self.instances.read().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<MediaInstance>> {
+        self.instances.read().unwrap().iter().find(|i| i.id == id).cloned()
+    }
+
+    /// Portun sahibi olan örneğin istemcisi. Örnek havuzdan çıkarıldıysa `Unavailable` döner.
+    pub fn client_for(&self, id: &str) -> Result<MediaServiceClient<Channel>, Status> {
+        self.get(id)
+            .map(|instance| instance.client())
+            .ok_or_else(|| Status::unavailable(format!("Medya örneği havuzda değil: {}", id)))
+    }
+
+    /// Uç nokta listesini yeniden çözer. Mevcut örnekler (ve sağlık durumları) korunur,
+    /// DNS'ten düşen örnekler havuzdan çıkarılır.
+    pub async fn refresh(&self, config: &AppConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let urls = resolve_endpoints(&config.media_endpoints).await?;
+        let current = self.instances();
+        let mut instances = Vec::with_capacity(urls.len());
+        for url in urls {
+            if let Some(existing) = current.iter().find(|i| i.id == url) {
+                instances.push(existing.clone());
+                continue;
+            }
+            let channel = secure_endpoint(&url, MEDIA_SERVER_NAME, config).await?.connect_lazy();
+            info!(instance = %url, "Medya örneği havuza eklendi.");
+            instances.push(Arc::new(MediaInstance { id: url, channel, healthy: AtomicBool::new(true) }));
+        }
+        for removed in current.iter().filter(|c| !instances.iter().any(|i| i.id == c.id)) {
+            info!(instance = %removed.id, "Medya örneği havuzdan çıkarıldı.");
+        }
+        *self.instances.write().unwrap() = instances;
+        Ok(())
+    }
+
+    /// Port ayırma için denenecek sağlıklı örnekleri politikaya göre sıralı döndürür.
+    /// Hiç sağlıklı örnek yoksa tüm örnekler döner; sağlık bilgisi eskimiş olabilir.
+    pub async fn candidates(&self, policy: MediaSelectionPolicy, tenant_id: Option<&str>, state: &AppState) -> Vec<Arc<MediaInstance>> {
+        let all = self.instances();
+        let mut candidates: Vec<_> = all.iter().filter(|i| i.is_healthy()).cloned().collect();
+        if candidates.is_empty() {
+            candidates = all;
+        }
+        if candidates.len() < 2 {
+            return candidates;
+        }
+
+        match (policy, tenant_id) {
+            (MediaSelectionPolicy::RoundRobin, _) => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates.rotate_left(start);
+            }
+            // Rendezvous hashing: örnek eklenip çıkarıldığında yalnızca o örneğin kiracıları yer değiştirir.
+            (MediaSelectionPolicy::TenantAffinity, Some(tenant_id)) => {
+                candidates.sort_by_key(|i| std::cmp::Reverse(affinity_score(tenant_id, &i.id)));
+            }
+            (MediaSelectionPolicy::LeastCalls, _) | (MediaSelectionPolicy::TenantAffinity, None) => {
+                let load = active_calls_per_instance(state).await;
+                candidates.sort_by_key(|i| load.get(&i.id).copied().unwrap_or(0));
+            }
+        }
+        candidates
+    }
+}
+
+fn affinity_score(tenant_id: &str, instance_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (tenant_id, instance_id).hash(&mut hasher);
+    hasher.finish()
+}
+
+async fn active_calls_per_instance(state: &AppState) -> HashMap<String, usize> {
+    let mut load = HashMap::new();
+    for call in state.active_calls.lock().await.values() {
+        if let Some(instance) = &call.media_instance {
+            *load.entry(instance.clone()).or_insert(0) += 1;
+        }
+    }
+    load
+}
+
+/// `dns:host:port` girdilerini A/AAAA kayıtlarına genişletir; diğer girdiler olduğu gibi kullanılır.
+/// Bir DNS sorgusu başarısız olursa hata döner; geçici bir DNS arızası havuzu boşaltmamalıdır.
+async fn resolve_endpoints(endpoints: &[String]) -> Result<Vec<String>, std::io::Error> {
+    let mut urls = Vec::new();
+    for endpoint in endpoints {
+        match endpoint.strip_prefix(DNS_PREFIX) {
+            Some(target) => urls.extend(tokio::net::lookup_host(target).await?.map(|addr| format!("https://{}", addr))),
+            None => urls.push(endpoint.clone()),
+        }
+    }
+    urls.sort();
+    urls.dedup();
+    Ok(urls)
+}
+
+/// Standart gRPC sağlık protokolüyle kontrol eder. Sağlık servisi sunmayan (Unimplemented) ama
+/// yanıt veren örnekler erişilebilir sayılır.
+async fn check(instance: &MediaInstance, timeout: Duration) -> bool {
+    let mut client = HealthClient::new(instance.channel.clone());
+    let mut request = Request::new(HealthCheckRequest { service: String::new() });
+    request.set_timeout(timeout);
+    match tokio::time::timeout(timeout, client.check(request)).await {
+        Ok(Ok(response)) => response.into_inner().status == ServingStatus::Serving as i32,
+        Ok(Err(status)) => status.code() == Code::Unimplemented,
+        Err(_) => false,
+    }
+}
+
+/// Uç nokta listesini yeniler ve örnekleri düzenli aralıklarla sağlık kontrolünden geçirir.
+/// Her turda güncel havuz kullanılır; sertifika yenilemesiyle havuz değişse de çalışmaya devam eder.
+pub async fn run_maintenance(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.config.media_health_interval);
+    loop {
+        interval.tick().await;
+        let pool = state.grpc_clients().media;
+        if let Err(e) = pool.refresh(&state.config).await {
+            warn!(error = %e, "Medya örnek listesi yenilenemedi.");
+        }
+        for instance in pool.instances() {
+            instance.set_healthy(check(&instance, state.config.grpc_timeout).await);
+        }
+    }
+}
+
+#[cfg(test)]
+impl MediaPool {
+    /// Sağlıklı kabul edilen, tembel bağlanan tek bir örnekten oluşan havuz.
+    pub fn with_instance(id: &str) -> Self {
+        let channel = tonic::transport::Endpoint::from_shared(id.to_string()).expect("Geçersiz uç nokta").connect_lazy();
+        let instance = MediaInstance { id: id.to_string(), channel, healthy: AtomicBool::new(true) };
+        Self { instances: RwLock::new(vec![Arc::new(instance)]), next: AtomicUsize::new(0) }
+    }
+}
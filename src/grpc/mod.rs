@@ -1,3 +1,7 @@
 // ========== FILE: src/grpc/mod.rs ==========
+pub mod authz;
 pub mod client;
-pub mod service; // YENİ SATIR
\ No newline at end of file
+pub mod media_pool;
+pub mod resilience;
+pub mod service;
+pub mod tls; // YENİ SATIR
\ No newline at end of file
@@ -0,0 +1,211 @@
+// File: sentiric-sip-signaling-service/src/grpc/resilience.rs
+// Bağımlı gRPC servislerine yapılan çağrılar için süre sınırı, sınırlı tekrar ve devre kesici.
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::error::ServiceError;
+use crate::telemetry::{GRPC_CIRCUIT_REJECTIONS, GRPC_CIRCUIT_STATE, GRPC_RETRIES};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use tonic::{Code, Status};
+use tracing::warn;
+
+const RETRY_BACKOFF_BASE: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dependency {
+    User,
+    Dialplan,
+    Media,
+}
+
+impl Dependency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dependency::User => "user-service",
+            Dependency::Dialplan => "dialplan-service",
+            Dependency::Media => "media-service",
+        }
+    }
+}
+
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// Tek bir deneme isteğine izin verilmiştir; sonucu devrenin kapanıp kapanmayacağını belirler.
+    HalfOpen { since: Instant },
+}
+
+pub struct CircuitBreaker {
+    dependency: Dependency,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(dependency: Dependency) -> Self {
+        metrics::gauge!(GRPC_CIRCUIT_STATE, "dependency" => dependency.as_str()).set(0.0);
+        Self { dependency, state: Mutex::new(BreakerState::Closed { failures: 0 }) }
+    }
+
+    /// İsteğin gönderilip gönderilemeyeceğine karar verir. Açık devre süresi dolmuşsa yarı açığa geçer.
+    fn try_acquire(&self, config: &AppConfig) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now >= until => {
+                self.set(&mut state, BreakerState::HalfOpen { since: now });
+                true
+            }
+            // Deneme isteğinin sahibi iptal edildiyse devre yarı açıkta takılı kalmaz.
+            BreakerState::HalfOpen { since } if now.duration_since(since) >= config.circuit_open_duration => {
+                self.set(&mut state, BreakerState::HalfOpen { since: now });
+                true
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Devre açık ve deneme süresi henüz gelmemiş mi?
+    fn is_open(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), BreakerState::Open { until } if Instant::now() < until)
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, BreakerState::Closed { failures: 0 }) {
+            self.set(&mut state, BreakerState::Closed { failures: 0 });
+        }
+    }
+
+    fn on_failure(&self, config: &AppConfig) {
+        let mut state = self.state.lock().unwrap();
+        let open = BreakerState::Open { until: Instant::now() + config.circuit_open_duration };
+        match *state {
+            BreakerState::Closed { failures } if failures + 1 >= config.circuit_failure_threshold => {
+                warn!(dependency = self.dependency.as_str(), failures = failures + 1, "Devre kesici açıldı.");
+                self.set(&mut state, open);
+            }
+            BreakerState::Closed { failures } => *state = BreakerState::Closed { failures: failures + 1 },
+            BreakerState::HalfOpen { .. } => {
+                warn!(dependency = self.dependency.as_str(), "Deneme isteği başarısız, devre kesici yeniden açıldı.");
+                self.set(&mut state, open);
+            }
+            BreakerState::Open { .. } => {}
+        }
+    }
+
+    fn set(&self, state: &mut BreakerState, new_state: BreakerState) {
+        let value = match new_state {
+            BreakerState::Closed { .. } => 0.0,
+            BreakerState::Open { .. } => 1.0,
+            BreakerState::HalfOpen { .. } => 2.0,
+        };
+        metrics::gauge!(GRPC_CIRCUIT_STATE, "dependency" => self.dependency.as_str()).set(value);
+        *state = new_state;
+    }
+}
+
+/// Bağımlılık başına devre kesiciler.
+pub struct CircuitBreakers {
+    user: CircuitBreaker,
+    dialplan: CircuitBreaker,
+    media: CircuitBreaker,
+}
+
+impl Default for CircuitBreakers {
+    fn default() -> Self {
+        Self {
+            user: CircuitBreaker::new(Dependency::User),
+            dialplan: CircuitBreaker::new(Dependency::Dialplan),
+            media: CircuitBreaker::new(Dependency::Media),
+        }
+    }
+}
+
+impl CircuitBreakers {
+    fn get(&self, dependency: Dependency) -> &CircuitBreaker {
+        match dependency {
+            Dependency::User => &self.user,
+            Dependency::Dialplan => &self.dialplan,
+            Dependency::Media => &self.media,
+        }
+    }
+
+    /// Devresi açık olan, yani şu anda istek gönderilmeyen bağımlılıklar.
+    pub fn open_dependencies(&self) -> Vec<Dependency> {
+        [Dependency::User, Dependency::Dialplan, Dependency::Media]
+            .into_iter()
+            .filter(|dependency| self.get(*dependency).is_open())
+            .collect()
+    }
+}
+
+/// Tek bir RPC için varsayılan son tarih (INVITE bütçesine bağlı olmayan çağrılar için).
+pub fn rpc_deadline(config: &AppConfig) -> Instant {
+    Instant::now() + config.grpc_timeout
+}
+
+/// Bağımlılığın sağlıksız olduğunu gösteren kodlar; yalnızca bunlar tekrar edilir ve devre kesiciye sayılır.
+fn is_transient(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted | Code::Unknown | Code::Internal)
+}
+
+/// `op`'u `deadline`'a kadar çalıştırır. Her deneme `grpc_timeout` ile, kalan süreyi aşmayacak şekilde
+/// sınırlanır; `op` bu süreyi isteğin `grpc-timeout`'u olarak kullanmalıdır. Yalnızca `idempotent`
+/// çağrılar geçici hatalarda `grpc_max_retries` kez tekrar edilir. Devre açıksa hiç denenmeden
+/// `ServiceError::CircuitOpen` döner.
+pub async fn call<T, F, Fut>(
+    state: &AppState,
+    dependency: Dependency,
+    deadline: Instant,
+    idempotent: bool,
+    mut op: F,
+) -> Result<T, ServiceError>
+where
+    F: FnMut(Duration) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let config = &state.config;
+    let breaker = state.circuit_breakers.get(dependency);
+    let max_attempts = if idempotent { config.grpc_max_retries + 1 } else { 1 };
+    let mut attempt = 0;
+
+    loop {
+        if !breaker.try_acquire(config) {
+            metrics::counter!(GRPC_CIRCUIT_REJECTIONS, "dependency" => dependency.as_str()).increment(1);
+            return Err(ServiceError::CircuitOpen(dependency.as_str()));
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Status::deadline_exceeded(format!("{} için süre bütçesi tükendi.", dependency.as_str())).into());
+        }
+        let timeout = remaining.min(config.grpc_timeout);
+        attempt += 1;
+
+        let status = match tokio::time::timeout(timeout, op(timeout)).await {
+            Ok(Ok(value)) => {
+                breaker.on_success();
+                return Ok(value);
+            }
+            Ok(Err(status)) if !is_transient(&status) => {
+                // İş mantığı hataları (NotFound vb.) bağımlılığın sağlıklı olduğunu gösterir.
+                breaker.on_success();
+                return Err(status.into());
+            }
+            Ok(Err(status)) => status,
+            Err(_) => Status::deadline_exceeded(format!("{} {:?} içinde yanıt vermedi.", dependency.as_str(), timeout)),
+        };
+        breaker.on_failure(config);
+
+        let backoff = RETRY_BACKOFF_BASE * 2u32.pow(attempt - 1);
+        if attempt >= max_attempts || Instant::now() + backoff >= deadline {
+            return Err(status.into());
+        }
+        warn!(dependency = dependency.as_str(), attempt, code = ?status.code(), "gRPC çağrısı başarısız, tekrar denenecek.");
+        metrics::counter!(GRPC_RETRIES, "dependency" => dependency.as_str()).increment(1);
+        tokio::time::sleep(backoff).await;
+    }
+}
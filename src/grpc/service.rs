@@ -1,11 +1,27 @@
 // sentiric-sip-signaling-service/src/grpc/service.rs
 use crate::app_state::AppState;
-use crate::sip::utils::create_bye_request;
+use crate::grpc::authz;
+use crate::events::CallEvent;
+use crate::sip::originate::{self, OriginateOutcome, OriginateParams};
+use crate::sip::bye;
+use crate::sip::invite::orchestrator::release_call_media;
+use crate::sip::call_control::{self, ControlOutcome};
+use crate::sip::utils::{extract_sdp_media_info_from_body, get_uri_from_header, is_valid_sip_uri, is_valid_sip_user};
+use crate::state::ActiveCallInfo;
+use crate::trunk::routing;
 use sentiric_contracts::sentiric::sip::v1::{
-    sip_signaling_service_server::SipSignalingService, TerminateCallRequest, TerminateCallResponse,
+    sip_signaling_service_server::SipSignalingService, CallSummary, GetCallRequest, GetCallResponse,
+    GetTrunkRegistrationsRequest, GetTrunkRegistrationsResponse, HoldCallRequest, HoldCallResponse,
+    ListCallsRequest, ListCallsResponse, ListTrunksRequest, ListTrunksResponse, OriginateCallRequest,
+    OriginateCallResponse, TerminateCallRequest, TerminateCallResponse, TransferCallRequest,
+    TransferCallResponse, Trunk, TrunkRegistration, WatchCallsRequest, WatchCallsResponse,
 };
+use futures_util::Stream;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::{Request, Response, Status};
 use tracing::{info, instrument, warn};
 
@@ -16,37 +32,381 @@ pub struct MySipSignalingService {
 
 #[tonic::async_trait]
 impl SipSignalingService for MySipSignalingService {
+    type WatchCallsStream = Pin<Box<dyn Stream<Item = Result<WatchCallsResponse, Status>> + Send>>;
+
     #[instrument(skip(self), fields(call_id = %request.get_ref().call_id))]
     async fn terminate_call(
         &self,
         request: Request<TerminateCallRequest>,
     ) -> Result<Response<TerminateCallResponse>, Status> {
+        authz::authorize(&self.app_state, "TerminateCall", &request, &request.get_ref().call_id)?;
         let req = request.into_inner();
         info!("gRPC üzerinden çağrı sonlandırma isteği alındı.");
 
-        let mut active_calls = self.app_state.active_calls.lock().await;
-        if let Some(call_info) = active_calls.remove(&req.call_id) {
-            let bye_request = create_bye_request(&call_info, &self.app_state.config);
-
-            if let Err(e) = self.sock.send_to(bye_request.as_bytes(), call_info.remote_addr).await {
-                warn!(error = %e, "gRPC TerminateCall: BYE paketi gönderilemedi.");
-            } else {
-                info!("gRPC TerminateCall: BYE paketi başarıyla gönderildi.");
-            }
-            
-            // TODO: call.ended olayını burada yayınla. BYE'a gelen 200 OK yanıtını beklemek daha doğru olur
-            // ancak bu, state yönetimini karmaşıklaştırır. Şimdilik BYE gönderimini başarılı kabul ediyoruz.
-            
-            Ok(Response::new(TerminateCallResponse {
-                success: true,
-                message: "Termination signal sent.".to_string(),
-            }))
-        } else {
+        // Çağrı önce listeden çıkarılır; böylece eşzamanlı bir BYE veya ikinci bir istek aynı çağrıyı tekrar kapatmaz.
+        let removed = self.app_state.active_calls.lock().await.remove(&req.call_id);
+        let Some(call_info) = removed else {
             warn!("Sonlandırılmak istenen çağrı aktif değil veya zaten sonlandırılmış.");
-            Err(Status::not_found(format!(
+            return Err(Status::not_found(format!(
                 "Aktif çağrı bulunamadı: {}",
                 req.call_id
-            )))
+            )));
+        };
+
+        // Köprülenmiş (çatallama ile cevaplanmış) karşı bacak da kapatılır.
+        let bridged = match &call_info.bridged_call_id {
+            Some(bridged_call_id) => self.app_state.active_calls.lock().await.remove(bridged_call_id),
+            None => None,
+        };
+        if let Some(bridged) = bridged {
+            let sock = self.sock.clone();
+            let state = self.app_state.clone();
+            tokio::spawn(async move {
+                release_call_media(&bridged, &state).await;
+                if bye::send_bye(&bridged, sock, &state).await.is_none() {
+                    warn!(bridged_call_id = %bridged.call_id, "Köprülenmiş bacak BYE'a yanıt vermedi.");
+                }
+            });
+        }
+
+        let final_response = bye::send_bye(&call_info, self.sock.clone(), &self.app_state).await;
+        release_call_media(&call_info, &self.app_state).await;
+        bye::publish_call_ended(&self.app_state, &call_info, "terminated_by_request").await;
+
+        let response = match final_response {
+            Some(r) => {
+                info!(status = r.status_code, "gRPC TerminateCall: BYE yanıtı alındı.");
+                TerminateCallResponse {
+                    success: r.is_success(),
+                    message: r.status_line,
+                    sip_status_code: r.status_code as u32,
+                }
+            }
+            None => {
+                warn!("gRPC TerminateCall: BYE isteğine yanıt alınamadı (zaman aşımı).");
+                TerminateCallResponse {
+                    success: false,
+                    message: "BYE zaman aşımına uğradı.".to_string(),
+                    sip_status_code: 408,
+                }
+            }
+        };
+        Ok(Response::new(response))
+    }
+
+    #[instrument(skip(self), fields(destination = %request.get_ref().destination))]
+    async fn originate_call(
+        &self,
+        request: Request<OriginateCallRequest>,
+    ) -> Result<Response<OriginateCallResponse>, Status> {
+        authz::authorize(&self.app_state, "OriginateCall", &request, &request.get_ref().destination)?;
+        let trace_id = request
+            .metadata()
+            .get("x-trace-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("trace-{}", uuid::Uuid::new_v4()));
+        let req = request.into_inner();
+        info!("gRPC üzerinden giden çağrı başlatma isteği alındı.");
+
+        if req.destination.trim().is_empty() {
+            return Err(Status::invalid_argument("Hedef (destination) boş olamaz."));
+        }
+        // Değerler From başlığına ve Request-URI'ye yazılır; CR/LF veya geçersiz karakterler başlık enjekte edebilir.
+        let destination = req.destination.trim();
+        let valid_destination = if destination.starts_with("sip:") || destination.starts_with("sips:") {
+            is_valid_sip_uri(destination)
+        } else {
+            is_valid_sip_user(destination)
+        };
+        if !valid_destination {
+            return Err(Status::invalid_argument("Hedef (destination) geçerli bir SIP URI'si veya kullanıcı adı değil."));
+        }
+        if !req.caller_id.is_empty() && !is_valid_sip_user(&req.caller_id) {
+            return Err(Status::invalid_argument("Arayan kimliği (caller_id) geçerli bir SIP kullanıcı adı değil."));
+        }
+        let trunk_name = Some(req.trunk_name).filter(|n| !n.is_empty());
+        if let Some(name) = &trunk_name {
+            if !self.app_state.config.trunks.iter().any(|t| &t.name == name) {
+                return Err(Status::invalid_argument(format!("Bilinmeyen trunk: {}", name)));
+            }
+        }
+
+        let params = OriginateParams {
+            destination: destination.to_string(),
+            caller_id: Some(req.caller_id).filter(|c| !c.is_empty()),
+            trunk_name,
+            ring_timeout: match req.ring_timeout_seconds {
+                0 => originate::DEFAULT_RING_TIMEOUT,
+                secs => Duration::from_secs(secs as u64),
+            },
+            trace_id,
+            extra_headers: Vec::new(),
+        };
+
+        match originate::originate(params, self.sock.clone(), self.app_state.clone()).await {
+            Ok(OriginateOutcome::Answered(call_info)) => Ok(Response::new(OriginateCallResponse {
+                success: true,
+                call_id: call_info.call_id.clone(),
+                sip_status_code: 200,
+                message: "Call answered.".to_string(),
+                rtp_port: call_info.rtp_port,
+                trunk_name: call_info.trunk.clone().unwrap_or_default(),
+            })),
+            Ok(OriginateOutcome::Failed(status_code, status_line)) => Ok(Response::new(OriginateCallResponse {
+                success: false,
+                call_id: String::new(),
+                sip_status_code: status_code as u32,
+                message: status_line,
+                rtp_port: 0,
+                trunk_name: String::new(),
+            })),
+            Err(e) => {
+                warn!(error = %e, "Giden çağrı başlatılamadı.");
+                Err(Status::unavailable(e.to_string()))
+            }
+        }
+    }
+
+    #[instrument(skip(self), fields(tenant_id = %request.get_ref().tenant_id, caller = %request.get_ref().caller))]
+    async fn list_calls(
+        &self,
+        request: Request<ListCallsRequest>,
+    ) -> Result<Response<ListCallsResponse>, Status> {
+        authz::authorize(&self.app_state, "ListCalls", &request, &request.get_ref().tenant_id)?;
+        let req = request.into_inner();
+        let active_calls = self.app_state.active_calls.lock().await;
+        let mut calls: Vec<CallSummary> = active_calls
+            .values()
+            .filter(|c| req.tenant_id.is_empty() || c.tenant_id.as_deref() == Some(req.tenant_id.as_str()))
+            .filter(|c| req.caller.is_empty() || c.caller_uri().contains(&req.caller))
+            .map(call_summary)
+            .collect();
+        calls.sort_by_key(|c| std::cmp::Reverse(c.duration_seconds));
+        Ok(Response::new(ListCallsResponse { calls }))
+    }
+
+    #[instrument(skip(self), fields(call_id = %request.get_ref().call_id))]
+    async fn get_call(
+        &self,
+        request: Request<GetCallRequest>,
+    ) -> Result<Response<GetCallResponse>, Status> {
+        authz::authorize(&self.app_state, "GetCall", &request, &request.get_ref().call_id)?;
+        let req = request.into_inner();
+        let call_info = self
+            .app_state
+            .active_calls
+            .lock()
+            .await
+            .get(&req.call_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("Aktif çağrı bulunamadı: {}", req.call_id)))?;
+
+        Ok(Response::new(GetCallResponse {
+            summary: Some(call_summary(&call_info)),
+            remote_addr: call_info.remote_addr.to_string(),
+            local_tag: call_info.to_tag.clone(),
+            remote_contact: call_info.contact_header.clone(),
+            record_route: call_info.record_route_header.clone().unwrap_or_default(),
+            remote_rtp_addr: extract_sdp_media_info_from_body(&call_info.raw_body).unwrap_or_default(),
+            remote_sdp: call_info.raw_body.clone(),
+            authenticated_user: call_info.authenticated_user.clone().unwrap_or_default(),
+        }))
+    }
+
+    #[instrument(skip(self), fields(call_id = %request.get_ref().call_id, hold = request.get_ref().hold))]
+    async fn hold_call(
+        &self,
+        request: Request<HoldCallRequest>,
+    ) -> Result<Response<HoldCallResponse>, Status> {
+        authz::authorize(&self.app_state, "HoldCall", &request, &request.get_ref().call_id)?;
+        let req = request.into_inner();
+        info!("gRPC üzerinden bekletme isteği alındı.");
+        let outcome = call_control::set_hold(&req.call_id, req.hold, self.sock.clone(), &self.app_state)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let (success, sip_status_code, message) = control_result(outcome, &req.call_id)?;
+        Ok(Response::new(HoldCallResponse { success, sip_status_code, message }))
+    }
+
+    #[instrument(skip(self), fields(call_id = %request.get_ref().call_id, target = %request.get_ref().target_uri))]
+    async fn transfer_call(
+        &self,
+        request: Request<TransferCallRequest>,
+    ) -> Result<Response<TransferCallResponse>, Status> {
+        authz::authorize(&self.app_state, "TransferCall", &request, &request.get_ref().call_id)?;
+        let req = request.into_inner();
+        info!("gRPC üzerinden aktarım isteği alındı.");
+        let replaces_call_id = Some(req.replaces_call_id.as_str()).filter(|c| !c.is_empty());
+        if req.target_uri.trim().is_empty() && replaces_call_id.is_none() {
+            return Err(Status::invalid_argument("Aktarım hedefi (target_uri) veya replaces_call_id belirtilmelidir."));
+        }
+        // Hedef Refer-To başlığına yazılır; CR/LF veya geçersiz karakterler başlık enjekte edebilir.
+        let target_uri = req.target_uri.trim();
+        if replaces_call_id.is_none() && !is_valid_sip_uri(&get_uri_from_header(target_uri).unwrap_or_else(|| target_uri.to_string())) {
+            return Err(Status::invalid_argument("Aktarım hedefi (target_uri) geçerli bir SIP URI'si değil."));
+        }
+        let outcome = call_control::transfer(&req.call_id, target_uri, replaces_call_id, self.sock.clone(), &self.app_state)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let (success, sip_status_code, message) = control_result(outcome, &req.call_id)?;
+        Ok(Response::new(TransferCallResponse { success, sip_status_code, message }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_trunks(
+        &self,
+        request: Request<ListTrunksRequest>,
+    ) -> Result<Response<ListTrunksResponse>, Status> {
+        authz::authorize(&self.app_state, "ListTrunks", &request, "")?;
+        let config = &self.app_state.config;
+        let mut trunks = Vec::with_capacity(config.trunks.len());
+        for trunk in &config.trunks {
+            trunks.push(Trunk {
+                name: trunk.name.clone(),
+                host: trunk.host.clone(),
+                port: trunk.port as u32,
+                transport: format!("{:?}", trunk.transport).to_lowercase(),
+                max_channels: trunk.max_channels.unwrap_or_default(),
+                active_channels: routing::active_channels(&self.app_state, &trunk.name).await,
+                prefixes: config
+                    .trunk_routes
+                    .iter()
+                    .filter(|r| r.trunk == trunk.name)
+                    .map(|r| r.prefix.clone())
+                    .collect(),
+            });
         }
+        Ok(Response::new(ListTrunksResponse { trunks }))
+    }
+
+    #[instrument(skip_all)]
+    async fn get_trunk_registrations(
+        &self,
+        request: Request<GetTrunkRegistrationsRequest>,
+    ) -> Result<Response<GetTrunkRegistrationsResponse>, Status> {
+        authz::authorize(&self.app_state, "GetTrunkRegistrations", &request, "")?;
+        let registrations = self.app_state.trunk_registrations.lock().await;
+        let mut registrations: Vec<TrunkRegistration> = registrations
+            .values()
+            .map(|r| TrunkRegistration {
+                trunk_name: r.trunk_name.clone(),
+                registrar_uri: r.registrar_uri.clone(),
+                state: r.state.as_str().to_string(),
+                expires_at_unix: r.expires_at.map(|t| t.timestamp()).unwrap_or_default(),
+                consecutive_failures: r.consecutive_failures,
+                last_error: r.last_error.clone().unwrap_or_default(),
+            })
+            .collect();
+        registrations.sort_by(|a, b| a.trunk_name.cmp(&b.trunk_name));
+        Ok(Response::new(GetTrunkRegistrationsResponse { registrations }))
+    }
+
+    #[instrument(skip_all)]
+    async fn watch_calls(
+        &self,
+        request: Request<WatchCallsRequest>,
+    ) -> Result<Response<Self::WatchCallsStream>, Status> {
+        authz::authorize(&self.app_state, "WatchCalls", &request, &request.get_ref().call_id)?;
+        let filter = Arc::new(WatchFilter::try_from(request.into_inner())?);
+        let receiver = self.app_state.call_events.subscribe();
+        info!("Çağrı olayı akışına yeni bir abone bağlandı.");
+
+        let stream = futures_util::stream::unfold(receiver, move |mut receiver| {
+            let filter = filter.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if filter.matches(&event) => return Some((Ok(watch_response(event)), receiver)),
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!(skipped, "WatchCalls abonesi geride kaldı, bazı olaylar atlandı.");
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
     }
-}
\ No newline at end of file
+}
+
+/// `WatchCalls` filtreleri. Boş alanlar filtre uygulanmadığı anlamına gelir.
+struct WatchFilter {
+    event_types: Vec<String>,
+    tenant_id: String,
+    call_id: String,
+    direction: String,
+    trunk_name: String,
+}
+
+impl TryFrom<WatchCallsRequest> for WatchFilter {
+    type Error = Status;
+
+    fn try_from(req: WatchCallsRequest) -> Result<Self, Status> {
+        let direction = req.direction.trim().to_lowercase();
+        if !matches!(direction.as_str(), "" | "inbound" | "outbound") {
+            return Err(Status::invalid_argument("direction 'inbound' veya 'outbound' olmalıdır."));
+        }
+        // "answered" ile "call.answered" aynı kabul edilir.
+        let event_types = req
+            .event_types
+            .iter()
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .map(|t| if t.starts_with("call.") { t } else { format!("call.{}", t) })
+            .collect();
+        Ok(Self { event_types, tenant_id: req.tenant_id, call_id: req.call_id, direction, trunk_name: req.trunk_name })
+    }
+}
+
+impl WatchFilter {
+    fn matches(&self, event: &CallEvent) -> bool {
+        (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && (self.tenant_id.is_empty() || event.tenant_id.as_deref() == Some(self.tenant_id.as_str()))
+            && (self.call_id.is_empty() || event.call_id == self.call_id)
+            && (self.direction.is_empty() || event.direction.as_str() == self.direction)
+            && (self.trunk_name.is_empty() || event.trunk.as_deref() == Some(self.trunk_name.as_str()))
+    }
+}
+
+fn watch_response(event: CallEvent) -> WatchCallsResponse {
+    WatchCallsResponse {
+        event_type: event.event_type,
+        direction: event.direction.as_str().to_string(),
+        call_id: event.call_id,
+        trace_id: event.trace_id,
+        caller_uri: event.caller_uri,
+        tenant_id: event.tenant_id.unwrap_or_default(),
+        trunk_name: event.trunk.unwrap_or_default(),
+        timestamp: event.timestamp,
+        details_json: if event.details.is_null() { String::new() } else { event.details.to_string() },
+    }
+}
+
+fn call_summary(call_info: &ActiveCallInfo) -> CallSummary {
+    CallSummary {
+        call_id: call_info.call_id.clone(),
+        trace_id: call_info.trace_id.clone(),
+        direction: call_info.direction.as_str().to_string(),
+        caller_uri: call_info.caller_uri().to_string(),
+        from_uri: call_info.from_header.clone(),
+        to_uri: call_info.to_header.clone(),
+        tenant_id: call_info.tenant_id.clone().unwrap_or_default(),
+        trunk_name: call_info.trunk.clone().unwrap_or_default(),
+        server_rtp_port: call_info.rtp_port,
+        duration_seconds: call_info.created_at.elapsed().as_secs() as i64,
+        on_hold: call_info.on_hold,
+        bridged_call_id: call_info.bridged_call_id.clone().unwrap_or_default(),
+        media_instance: call_info.media_instance.clone().unwrap_or_default(),
+    }
+}
+
+/// Diyalog içi bir kontrol isteğinin sonucunu (başarı, SIP durum kodu, mesaj) üçlüsüne çevirir.
+fn control_result(outcome: ControlOutcome, call_id: &str) -> Result<(bool, u32, String), Status> {
+    match outcome {
+        ControlOutcome::CallNotFound => Err(Status::not_found(format!("Aktif çağrı bulunamadı: {}", call_id))),
+        ControlOutcome::NoResponse => Ok((false, 408, "İsteğe yanıt alınamadı (zaman aşımı).".to_string())),
+        ControlOutcome::Response(r) => Ok((r.is_success(), r.status_code as u32, r.status_line)),
+    }
+}
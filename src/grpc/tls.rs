@@ -0,0 +1,85 @@
+// File: sentiric-sip-signaling-service/src/grpc/tls.rs
+// gRPC sunucusunun TLS malzemesi ve sertifika rotasyonunun tespiti (SIGHUP veya dosya değişikliği).
+use crate::config::AppConfig;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::watch;
+use tokio::time::Interval;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tracing::{info, warn};
+
+pub async fn load_server_tls_config(config: &AppConfig) -> Result<ServerTlsConfig, Box<dyn Error + Send + Sync>> {
+    let cert = tokio::fs::read(&config.cert_path).await?;
+    let key = tokio::fs::read(&config.key_path).await?;
+    let identity = Identity::from_pem(cert, key);
+    let ca_cert = tokio::fs::read(&config.ca_path).await?;
+    let client_ca_cert = Certificate::from_pem(ca_cert);
+    Ok(ServerTlsConfig::new().identity(identity).client_ca_root(client_ca_cert))
+}
+
+/// Sertifika, anahtar ve CA dosyalarının içerik özeti. Kubernetes secret'ları gibi sembolik bağla
+/// değiştirilen dosyalarda mtime güvenilir olmadığından içerik karşılaştırılır.
+async fn fingerprint(config: &AppConfig) -> Option<md5::Digest> {
+    let mut contents = Vec::new();
+    for path in [&config.cert_path, &config.key_path, &config.ca_path] {
+        contents.extend(tokio::fs::read(path).await.ok()?);
+    }
+    Some(md5::compute(contents))
+}
+
+/// SIGHUP alındığında veya TLS dosyalarının içeriği değiştiğinde artan bir nesil numarası yayınlar.
+/// Sunucu ve giden istemci kanalları bu kanala abone olarak kendilerini yeniden kurar.
+pub fn spawn_reload_watcher(config: Arc<AppConfig>) -> watch::Receiver<u64> {
+    let (tx, rx) = watch::channel(0);
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!(error = %e, "SIGHUP dinleyicisi kurulamadı; TLS yalnızca dosya değişikliğinde yeniden yüklenecek.");
+                None
+            }
+        };
+        let mut interval = (config.tls_reload_interval_secs > 0)
+            .then(|| tokio::time::interval(Duration::from_secs(config.tls_reload_interval_secs)));
+        let mut last = fingerprint(&config).await;
+
+        loop {
+            let forced = tokio::select! {
+                _ = recv_hangup(&mut hangup) => true,
+                _ = tick(&mut interval) => false,
+            };
+            let current = fingerprint(&config).await;
+            if current.is_none() {
+                warn!("TLS dosyaları okunamadı, yeniden yükleme atlandı.");
+                continue;
+            }
+            if !forced && current == last {
+                continue;
+            }
+            last = current;
+            info!(trigger = if forced { "sighup" } else { "file_change" }, "TLS sertifikaları yeniden yükleniyor.");
+            tx.send_modify(|generation| *generation += 1);
+        }
+    });
+    rx
+}
+
+async fn recv_hangup(hangup: &mut Option<Signal>) {
+    match hangup {
+        Some(s) => {
+            s.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(i) => {
+            i.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
@@ -6,11 +6,14 @@ mod app;
 mod app_state;
 mod config;
 mod error;
+mod events;
 mod grpc;
+mod telemetry;
 mod rabbitmq;
 mod redis;
 mod sip;
 mod state;
+mod trunk;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -1,3 +1,4 @@
 // ========== FILE: src/rabbitmq/mod.rs ==========
 pub mod connection;
+pub mod publisher;
 // pub mod terminate; <-- BU SATIRI SİLİN
\ No newline at end of file
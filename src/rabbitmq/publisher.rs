@@ -0,0 +1,27 @@
+// File: sentiric-sip-signaling-service/src/rabbitmq/publisher.rs
+use super::connection::RABBITMQ_EXCHANGE_NAME;
+use crate::error::ServiceError;
+use lapin::{options::BasicPublishOptions, BasicProperties, Channel as LapinChannel};
+use tracing::debug;
+
+/// `sentiric_events` exchange'ine, olay tipini routing key olarak kullanarak kalıcı bir JSON olayı yayınlar.
+pub async fn publish_event(
+    channel: &LapinChannel,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<(), ServiceError> {
+    let payload_str = serde_json::to_string(payload)?;
+    debug!(event_payload = %payload_str, "{} olayı yayınlanıyor (tam içerik).", event_type);
+
+    channel
+        .basic_publish(
+            RABBITMQ_EXCHANGE_NAME,
+            event_type,
+            BasicPublishOptions::default(),
+            payload_str.as_bytes(),
+            BasicProperties::default().with_delivery_mode(2).with_content_type("application/json".into()),
+        )
+        .await?
+        .await?;
+    Ok(())
+}
@@ -5,6 +5,10 @@ pub use redis::Client;
 // DÜZELTME 2: `AsyncCommands` trait'ini de public yapıyoruz ki set_registration içinde kullanabilelim.
 pub use redis::{AsyncCommands, RedisResult};
 
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{info, warn};
@@ -37,4 +41,124 @@ pub async fn set_registration(
 ) -> RedisResult<()> {
     let mut conn = client.get_multiplexed_async_connection().await?;
     conn.set_ex(aor, contact_uri, expires).await
-}
\ No newline at end of file
+}
+
+// --- Çoklu Kayıt (Binding) Yönetimi ---
+// Bir AOR'un birden fazla cihazı (binding) olabilir. Her AOR için bir hash tutulur:
+// alan = contact URI, değer = JSON olarak serileştirilmiş `Binding`.
+
+const BINDINGS_KEY_PREFIX: &str = "sip_bindings:";
+/// Hash, en uzun binding'den bu kadar daha uzun yaşar; böylece süresi dolan son binding
+/// Redis tarafından silinmeden önce süpürücü tarafından görülebilir.
+const BINDINGS_KEY_GRACE_SECS: i64 = 300;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Binding {
+    /// İstemcinin bildirdiği Contact başlığının değeri.
+    pub contact: String,
+    /// REGISTER'ın geldiği adres. Kullanıcıya gönderilecek istekler bu adrese yollanır.
+    pub source_addr: SocketAddr,
+    /// Unix zaman damgası (saniye).
+    pub expires_at: i64,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Bildirilen Contact özel bir adres içeriyor veya `source_addr` ile eşleşmiyor.
+    #[serde(default)]
+    pub behind_nat: bool,
+}
+
+impl Binding {
+    pub fn contact_uri(&self) -> String {
+        crate::sip::utils::get_uri_from_header(&self.contact).unwrap_or_else(|| self.contact.clone())
+    }
+
+    /// Kullanıcıya gönderilecek isteklerin Request-URI'si. NAT arkasındaki binding'lerde
+    /// `rewrite_contact` etkinse Contact'ın host:port kısmı `source_addr` ile değiştirilir.
+    pub fn request_uri(&self, rewrite_contact: bool) -> String {
+        let uri = self.contact_uri();
+        if self.behind_nat && rewrite_contact {
+            crate::sip::utils::rewrite_uri_host_port(&uri, self.source_addr)
+        } else {
+            uri
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= chrono::Utc::now().timestamp()
+    }
+}
+
+fn bindings_key(aor: &str) -> String {
+    format!("{}{}", BINDINGS_KEY_PREFIX, aor)
+}
+
+/// Binding'i ekler veya günceller. Binding yeni ise `true` döner.
+pub async fn upsert_binding(client: &Client, aor: &str, binding: &Binding) -> anyhow::Result<bool> {
+    let value = serde_json::to_string(binding)
+        .with_context(|| format!("'{}' için binding serileştirilemedi", binding.contact))?;
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let key = bindings_key(aor);
+    let is_new: bool = conn.hset(&key, binding.contact_uri(), value).await?;
+
+    // Hash'in ömrü, en uzun süreli binding'den kısa olmamalı.
+    let ttl: i64 = conn.ttl(&key).await?;
+    let remaining = binding.expires_at - chrono::Utc::now().timestamp() + BINDINGS_KEY_GRACE_SECS;
+    if ttl < remaining {
+        conn.expire::<_, ()>(&key, remaining).await?;
+    }
+    Ok(is_new)
+}
+
+/// Binding'i siler. Binding mevcutsa `true` döner.
+pub async fn remove_binding(client: &Client, aor: &str, contact_uri: &str) -> RedisResult<bool> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    conn.hdel(bindings_key(aor), contact_uri).await
+}
+
+/// AOR'a ait tüm binding'leri siler (`Contact: *` ile kayıt silme).
+pub async fn clear_bindings(client: &Client, aor: &str) -> RedisResult<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    conn.del(bindings_key(aor)).await
+}
+
+/// AOR'a ait süresi dolmamış tüm binding'leri döndürür.
+pub async fn get_bindings(client: &Client, aor: &str) -> RedisResult<Vec<Binding>> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let raw: HashMap<String, String> = conn.hgetall(bindings_key(aor)).await?;
+    Ok(raw
+        .values()
+        .filter_map(|v| serde_json::from_str::<Binding>(v).ok())
+        .filter(|b| !b.is_expired())
+        .collect())
+}
+
+/// Süresi dolmuş binding'leri tüm AOR'lardan siler ve silinenleri döndürür.
+/// Birden fazla servis örneği aynı anda süpürse bile her binding yalnızca bir kez döner (HDEL sonucu).
+pub async fn take_expired_bindings(client: &Client) -> RedisResult<Vec<(String, Binding)>> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let keys: Vec<String> = {
+        let mut iter = conn.scan_match::<_, String>(format!("{}*", BINDINGS_KEY_PREFIX)).await?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        keys
+    };
+
+    let mut expired = Vec::new();
+    for key in keys {
+        let raw: HashMap<String, String> = conn.hgetall(&key).await?;
+        for (contact_uri, value) in raw {
+            let Ok(binding) = serde_json::from_str::<Binding>(&value) else { continue };
+            if !binding.is_expired() {
+                continue;
+            }
+            let removed: bool = conn.hdel(&key, &contact_uri).await?;
+            if removed {
+                let aor = key.trim_start_matches(BINDINGS_KEY_PREFIX).to_string();
+                expired.push((aor, binding));
+            }
+        }
+    }
+    Ok(expired)
+}
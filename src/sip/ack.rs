@@ -1,5 +1,6 @@
 // sentiric-sip-signaling-service/src/sip/ack.rs
 use crate::app_state::AppState;
+use crate::events::{self, CallEvent};
 use crate::rabbitmq::connection::RABBITMQ_EXCHANGE_NAME;
 use crate::sip::utils::parse_sip_headers; // DÜZELTME: Doğru fonksiyon adı
 use crate::state::ActiveCallInfo;
@@ -23,6 +24,7 @@ pub async fn handle(
     info!("ACK isteği alındı, çağrı kurulumu tamamlanıyor.");
 
     let mut call_info_to_publish: Option<ActiveCallInfo> = None;
+    let mut answered_leg: Option<ActiveCallInfo> = None;
 
     {
         let mut active_calls = state.active_calls.lock().await;
@@ -38,11 +40,16 @@ pub async fn handle(
         } else {
             warn!("ACK alınan çağrı aktif çağrılar listesinde bulunamadı.");
         }
+        if let Some(leg_call_id) = call_info_to_publish.as_ref().and_then(|c| c.bridged_call_id.as_ref()) {
+            answered_leg = active_calls.get(leg_call_id).cloned();
+        }
     }
 
     if let Some(call_info) = call_info_to_publish {
+        let answered_leg_id = answered_leg.as_ref().map(|leg| leg.call_id.clone());
+        events::emit(&state, CallEvent::for_call("call.answered", &call_info).with_details(serde_json::json!({ "answeredLeg": answered_leg_id })));
         if let Some(rabbit_channel) = &state.rabbit {
-            publish_call_answered_event(&call_info, rabbit_channel).await?;
+            publish_call_answered_event(&call_info, answered_leg.as_ref(), rabbit_channel).await?;
         } else {
             warn!("RabbitMQ bağlantısı aktif değil, 'call.answered' olayı yayınlanamadı.");
         }
@@ -52,17 +59,29 @@ pub async fn handle(
 
 
 #[instrument(skip_all, fields(trace_id = %call_info.trace_id, call_id = %call_info.call_id))]
-async fn publish_call_answered_event(
+pub async fn publish_call_answered_event(
     call_info: &ActiveCallInfo,
+    answered_leg: Option<&ActiveCallInfo>,
     rabbit_channel: &Arc<lapin::Channel>,
 ) -> Result<(), crate::error::ServiceError> {
-    let event_payload = serde_json::json!({
+    let mut event_payload = serde_json::json!({
         "eventType": "call.answered",
         "traceId": &call_info.trace_id,
         "callId": &call_info.call_id,
+        "trunk": &call_info.trunk,
         "timestamp": chrono::Utc::now().to_rfc3339(),
     });
 
+    // Çatallanmış çağrılarda hangi bacağın cevapladığı bildirilir.
+    if let (Some(leg), serde_json::Value::Object(map)) = (answered_leg, &mut event_payload) {
+        map.insert("answeredLeg".to_string(), serde_json::json!({
+            "callId": &leg.call_id,
+            "uri": &leg.from_header,
+            "contact": &leg.contact_header,
+            "serverRtpPort": leg.rtp_port,
+        }));
+    }
+
     rabbit_channel
         .basic_publish(
             RABBITMQ_EXCHANGE_NAME,
@@ -0,0 +1,184 @@
+// sentiric-sip-signaling-service/src/sip/auth.rs
+// REGISTER (401/WWW-Authenticate) ve INVITE (407/Proxy-Authenticate) için ortak
+// SIP Digest (MD5) kimlik doğrulama yardımcıları.
+use crate::app_state::AppState;
+use crate::error::ServiceError;
+use crate::grpc::resilience::{self, Dependency};
+use md5::compute;
+use rand::distributions::{Alphanumeric, DistString};
+use sentiric_contracts::sentiric::user::v1::GetSipCredentialsRequest;
+use std::collections::HashMap;
+use tonic::Request as TonicRequest;
+
+#[derive(Debug, Clone)]
+pub struct DigestCredentials {
+    pub username: String,
+    pub realm: String,
+    pub nonce: String,
+    pub uri: String,
+    pub response: String,
+    pub qop: Option<String>,
+    pub nc: Option<String>,
+    pub cnonce: Option<String>,
+}
+
+/// `Authorization` veya `Proxy-Authorization` başlığının değerini ayrıştırır.
+pub fn parse_credentials(header: &str) -> Result<DigestCredentials, String> {
+    let parts = digest_params(header.trim().strip_prefix("Digest ").unwrap_or(""));
+
+    let required = |key: &str| -> Result<String, String> {
+        parts.get(key).cloned().ok_or_else(|| format!("{} eksik", key))
+    };
+    let optional = |key: &str| parts.get(key).cloned();
+
+    Ok(DigestCredentials {
+        username: required("username")?,
+        realm: required("realm")?,
+        nonce: required("nonce")?,
+        uri: required("uri")?,
+        response: required("response")?,
+        qop: optional("qop"),
+        nc: optional("nc"),
+        cnonce: optional("cnonce"),
+    })
+}
+
+/// `anahtar=değer` çiftlerini ayrıştırır; tırnak içindeki virgüller (örn. `qop="auth,auth-int"`) korunur.
+fn digest_params(params: &str) -> HashMap<String, String> {
+    let mut parts = HashMap::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    let mut push = |segment: &str| {
+        if let Some((k, v)) = segment.trim().split_once('=') {
+            parts.insert(k.trim().to_lowercase(), v.trim().trim_matches('"').to_string());
+        }
+    };
+    for (i, c) in params.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    push(&params[start..]);
+    parts
+}
+
+pub fn generate_nonce() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 32)
+}
+
+pub fn build_challenge(realm: &str, nonce: &str, stale: bool) -> String {
+    let stale_param = if stale { ", stale=true" } else { "" };
+    format!(r#"Digest realm="{}", qop="auth", nonce="{}"{}"#, realm, nonce, stale_param)
+}
+
+/// İstemcinin göndermesi gereken yanıtı hesaplar (RFC 2617 3.2.2.1).
+pub fn expected_response(ha1_hash: &str, method: &str, creds: &DigestCredentials) -> String {
+    let a2_hash = format!("{:x}", compute(format!("{}:{}", method, creds.uri).as_bytes()));
+    let response_str = match (&creds.qop, &creds.nc, &creds.cnonce) {
+        (Some(qop), Some(nc), Some(cnonce)) => {
+            format!("{}:{}:{}:{}:{}:{}", ha1_hash, creds.nonce, nc, cnonce, qop, a2_hash)
+        }
+        _ => format!("{}:{}:{}", ha1_hash, creds.nonce, a2_hash),
+    };
+    format!("{:x}", compute(response_str.as_bytes()))
+}
+
+/// Kullanıcının HA1 özetini user-service'ten alır.
+pub async fn fetch_ha1(state: &AppState, username: &str, realm: &str) -> Result<String, ServiceError> {
+    resilience::call(state, Dependency::User, resilience::rpc_deadline(&state.config), true, |timeout| {
+        let mut user_client = state.grpc_clients().user;
+        let mut request = TonicRequest::new(GetSipCredentialsRequest {
+            sip_username: username.to_string(),
+            realm: realm.to_string(),
+        });
+        request.set_timeout(timeout);
+        async move { user_client.get_sip_credentials(request).await.map(|r| r.into_inner().ha1_hash) }
+    })
+    .await
+}
+
+// --- UAC (istemci) tarafı: upstream sunuculara karşı kimlik doğrulama ---
+
+#[derive(Debug, Clone)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub qop: Option<String>,
+    pub opaque: Option<String>,
+}
+
+/// `WWW-Authenticate` veya `Proxy-Authenticate` başlığını ayrıştırır.
+pub fn parse_challenge(header: &str) -> Option<DigestChallenge> {
+    let parts = digest_params(header.trim().strip_prefix("Digest ")?);
+
+    Some(DigestChallenge {
+        realm: parts.get("realm")?.clone(),
+        nonce: parts.get("nonce")?.clone(),
+        // "auth,auth-int" gibi listelerden yalnızca "auth" desteklenir.
+        qop: parts
+            .get("qop")
+            .filter(|q| q.split(',').any(|v| v.trim() == "auth"))
+            .map(|_| "auth".to_string()),
+        opaque: parts.get("opaque").cloned(),
+    })
+}
+
+/// Bir challenge'a karşılık `Authorization`/`Proxy-Authorization` başlık değerini üretir.
+pub fn build_authorization(
+    challenge: &DigestChallenge,
+    username: &str,
+    password: &str,
+    method: &str,
+    uri: &str,
+) -> String {
+    let ha1_hash = format!("{:x}", compute(format!("{}:{}:{}", username, challenge.realm, password).as_bytes()));
+    let credentials = DigestCredentials {
+        username: username.to_string(),
+        realm: challenge.realm.clone(),
+        nonce: challenge.nonce.clone(),
+        uri: uri.to_string(),
+        response: String::new(),
+        qop: challenge.qop.clone(),
+        nc: challenge.qop.as_ref().map(|_| "00000001".to_string()),
+        cnonce: challenge.qop.as_ref().map(|_| Alphanumeric.sample_string(&mut rand::thread_rng(), 16)),
+    };
+    let response = expected_response(&ha1_hash, method, &credentials);
+
+    let mut header = format!(
+        r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", response="{}", algorithm=MD5"#,
+        username, challenge.realm, challenge.nonce, uri, response
+    );
+    if let (Some(qop), Some(nc), Some(cnonce)) = (&credentials.qop, &credentials.nc, &credentials.cnonce) {
+        header.push_str(&format!(r#", qop={}, nc={}, cnonce="{}""#, qop, nc, cnonce));
+    }
+    if let Some(opaque) = &challenge.opaque {
+        header.push_str(&format!(r#", opaque="{}""#, opaque));
+    }
+    header
+}
+
+/// Bir 401/407 yanıtından, tekrar gönderilecek istek için yetkilendirme başlığı satırını üretir.
+pub fn answer_challenge(
+    response: &crate::sip::transaction::SipResponse,
+    username: &str,
+    password: &str,
+    method: &str,
+    uri: &str,
+) -> Option<String> {
+    let (challenge_header, authorization_name) = match response.status_code {
+        401 => (response.headers.get("www-authenticate")?, "Authorization"),
+        407 => (response.headers.get("proxy-authenticate")?, "Proxy-Authorization"),
+        _ => return None,
+    };
+    let challenge = parse_challenge(challenge_header)?;
+    Some(format!(
+        "{}: {}",
+        authorization_name,
+        build_authorization(&challenge, username, password, method, uri)
+    ))
+}
@@ -1,10 +1,15 @@
 // sentiric-sip-signaling-service/src/sip/bye.rs
 
 use crate::app_state::AppState;
+use crate::events::{self, CallEvent};
 use crate::rabbitmq::connection::RABBITMQ_EXCHANGE_NAME;
+use crate::sip::invite::orchestrator::release_call_media;
+use crate::sip::requests::build_in_dialog_request;
 use crate::sip::responses;
+use crate::sip::transaction::{ClientTransaction, SipResponse};
 use crate::sip::utils::parse_sip_headers;
 use crate::sip::utils::extract_sdp_media_info_from_body; // Gerekirse ekleyin
+use crate::state::ActiveCallInfo;
 use lapin::{options::BasicPublishOptions, BasicProperties};
 use std::error::Error;
 use std::net::SocketAddr;
@@ -28,46 +33,121 @@ pub async fn handle(
     sock.send_to(ok_response.as_bytes(), addr).await?;
     info!("BYE isteğine 200 OK yanıtı gönderildi.");
 
-    if let Some(call_info) = state.active_calls.lock().await.remove(&call_id) {
+    let removed = state.active_calls.lock().await.remove(&call_id);
+    if let Some(call_info) = removed {
         Span::current().record("trace_id", &call_info.trace_id as &str);
         info!(port = call_info.rtp_port, "Çağrı kullanıcı tarafından sonlandırıldı.");
+        release_call_media(&call_info, &state).await;
 
-        if let Some(rabbit_channel) = &state.rabbit {
-            
-            // --- GÜNCELLEME: MediaInfo Ekleme ---
-            let sdp_info = extract_sdp_media_info_from_body(&call_info.raw_body).unwrap_or_default();
-            
-            let event_payload = serde_json::json!({
-                "eventType": "call.ended",
-                "traceId": call_info.trace_id,
-                "callId": call_id,
-                "reason": "normal_clearing_by_user",
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                // YENİ: Agent servisin portu temizleyebilmesi için
-                "mediaInfo": {
-                    "callerRtpAddr": sdp_info,
-                    "serverRtpPort": call_info.rtp_port
-                }
-            });
-            // ------------------------------------
+        // Köprülenmiş (çatallama ile cevaplanmış) karşı bacak da kapatılır.
+        let bridged = match &call_info.bridged_call_id {
+            Some(bridged_call_id) => state.active_calls.lock().await.remove(bridged_call_id),
+            None => None,
+        };
+        if let Some(bridged) = &bridged {
+            release_call_media(bridged, &state).await;
+        }
+
+        publish_call_ended(&state, &call_info, "normal_clearing_by_user").await;
 
-            if let Err(e) = rabbit_channel.basic_publish(
-                RABBITMQ_EXCHANGE_NAME,
-                "call.ended",
-                BasicPublishOptions::default(),
-                event_payload.to_string().as_bytes(),
-                BasicProperties::default().with_delivery_mode(2).with_content_type("application/json".into()),
-            ).await {
-                error!(error = %e, "'call.ended' olayı yayınlanırken hata oluştu.");
-            } else {
-                info!("'call.ended' olayı başarıyla yayınlandı.");
+        if let Some(bridged) = bridged {
+            match send_bye(&bridged, sock, &state).await {
+                Some(response) => info!(bridged_call_id = %bridged.call_id, status = response.status_code, "Köprülenmiş bacak BYE ile kapatıldı."),
+                None => warn!(bridged_call_id = %bridged.call_id, "Köprülenmiş bacak BYE'a yanıt vermedi."),
             }
-        } else {
-            warn!("RabbitMQ bağlantısı aktif değil, 'call.ended' olayı yayınlanamadı.");
         }
-        
     } else {
         warn!("BYE isteği alınan çağrı aktif çağrılar listesinde bulunamadı.");
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Diyaloğu bu servisin kararıyla kapatır (oturum süresi doldu, yoklamaya yanıt yok...): çağrı
+/// listeden çıkarılır, medya portları serbest bırakılır ve her iki bacağa BYE gönderilir.
+/// Çağrı başka bir akış tarafından zaten kapatılmışsa `false` döner.
+#[instrument(skip(sock, state))]
+pub async fn hang_up(call_id: &str, reason: &str, sock: Arc<UdpSocket>, state: &AppState) -> bool {
+    let Some(call_info) = state.active_calls.lock().await.remove(call_id) else {
+        return false;
+    };
+    let bridged = match &call_info.bridged_call_id {
+        Some(bridged_call_id) => state.active_calls.lock().await.remove(bridged_call_id),
+        None => None,
+    };
+    info!(reason, "Çağrı servis tarafından sonlandırılıyor.");
+    release_call_media(&call_info, state).await;
+    if let Some(bridged) = &bridged {
+        release_call_media(bridged, state).await;
+    }
+    publish_call_ended(state, &call_info, reason).await;
+
+    let bridged_bye = async {
+        if let Some(bridged) = &bridged {
+            send_bye(bridged, sock.clone(), state).await;
+        }
+    };
+    tokio::join!(send_bye(&call_info, sock.clone(), state), bridged_bye);
+    true
+}
+
+/// Bir diyaloğu BYE istemci transaction'ı ile kapatır (Timer E/F retransmission).
+/// Karşı tarafın nihai yanıtını, zaman aşımında `None` döndürür.
+pub async fn send_bye(call_info: &ActiveCallInfo, sock: Arc<UdpSocket>, state: &AppState) -> Option<SipResponse> {
+    let mut call_info = call_info.clone();
+    let cseq = call_info.next_local_cseq();
+    let mut transaction = match ClientTransaction::start(
+        build_in_dialog_request(&call_info, "BYE", cseq),
+        call_info.remote_addr,
+        sock,
+        state.config.clone(),
+        state.client_transactions.clone(),
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            warn!(error = %e, "BYE gönderilemedi.");
+            return None;
+        }
+    };
+    transaction.final_response().await
+}
+
+pub async fn publish_call_ended(state: &AppState, call_info: &ActiveCallInfo, reason: &str) {
+    events::emit(state, CallEvent::for_call("call.ended", call_info).with_details(serde_json::json!({ "reason": reason })));
+
+    let Some(rabbit_channel) = &state.rabbit else {
+        warn!("RabbitMQ bağlantısı aktif değil, 'call.ended' olayı yayınlanamadı.");
+        return;
+    };
+
+    // --- GÜNCELLEME: MediaInfo Ekleme ---
+    let sdp_info = extract_sdp_media_info_from_body(&call_info.raw_body).unwrap_or_default();
+
+    let event_payload = serde_json::json!({
+        "eventType": "call.ended",
+        "traceId": call_info.trace_id,
+        "callId": call_info.call_id,
+        "reason": reason,
+        "trunk": call_info.trunk,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        // YENİ: Agent servisin portu temizleyebilmesi için
+        "mediaInfo": {
+            "callerRtpAddr": sdp_info,
+            "serverRtpPort": call_info.rtp_port
+        }
+    });
+    // ------------------------------------
+
+    if let Err(e) = rabbit_channel.basic_publish(
+        RABBITMQ_EXCHANGE_NAME,
+        "call.ended",
+        BasicPublishOptions::default(),
+        event_payload.to_string().as_bytes(),
+        BasicProperties::default().with_delivery_mode(2).with_content_type("application/json".into()),
+    ).await {
+        error!(error = %e, "'call.ended' olayı yayınlanırken hata oluştu.");
+    } else {
+        info!(reason, "'call.ended' olayı başarıyla yayınlandı.");
+    }
+}
@@ -1,9 +1,12 @@
 // sentiric-sip-signaling-service/src/sip/call_context.rs
 
 use crate::error::ServiceError;
+use crate::sip::session_timer::SessionTimer;
 use crate::sip::utils;
+use rand::Rng;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use tokio::time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct CallContext {
@@ -11,6 +14,8 @@ pub struct CallContext {
     pub via_headers: Vec<String>, // Orijinal sıralamayı korumak için Vec
     pub raw_body: String,
     pub remote_addr: SocketAddr,
+    /// İstek satırındaki Request-URI.
+    pub request_uri: String,
     
     // Sık kullanılan header'lar için kısayollar
     pub call_id: String,
@@ -23,6 +28,14 @@ pub struct CallContext {
     pub caller_id: String,
     pub destination_number: String,
     pub trace_id: String,
+    /// INVITE Digest doğrulamasından geçen kullanıcı adı (doğrulama gerekmiyorsa `None`).
+    pub authenticated_user: Option<String>,
+    /// INVITE'ın alındığı an; kurulum süre bütçesi buradan hesaplanır.
+    pub received_at: Instant,
+    /// Bu servisin diyalog için ürettiği To etiketi. Tüm 1xx/2xx yanıtlar aynı etiketi taşır.
+    pub local_tag: String,
+    /// INVITE için anlaşılan oturum zamanlayıcısı (RFC 4028).
+    pub session_timer: Option<SessionTimer>,
 }
 
 impl CallContext {
@@ -31,6 +44,7 @@ impl CallContext {
         let parts: Vec<&str> = request_str.splitn(2, "\r\n\r\n").collect();
         let header_part = parts.get(0).unwrap_or(&"");
         let raw_body = parts.get(1).unwrap_or(&"").to_string();
+        let request_uri = header_part.lines().next().and_then(|l| l.split_whitespace().nth(1)).unwrap_or_default().to_string();
         
         // Headerları Parse Et (utils modülünü kullanıyoruz)
         let (headers, via_headers) = utils::parse_sip_headers(header_part)
@@ -56,6 +70,7 @@ impl CallContext {
             via_headers,
             raw_body,
             remote_addr,
+            request_uri,
             call_id,
             from_header,
             to_header,
@@ -64,6 +79,19 @@ impl CallContext {
             caller_id,
             destination_number,
             trace_id,
+            authenticated_user: None,
+            received_at: Instant::now(),
+            local_tag: rand::thread_rng().gen::<u32>().to_string(),
+            session_timer: None,
         })
     }
+
+    /// Yanıtlarda kullanılacak başlıklar: To başlığına yerel etiket eklenmiştir.
+    pub fn response_headers(&self) -> HashMap<String, String> {
+        let mut headers = self.headers.clone();
+        headers
+            .entry("to".to_string())
+            .and_modify(|v| *v = format!("{};tag={}", v, self.local_tag));
+        headers
+    }
 }
\ No newline at end of file
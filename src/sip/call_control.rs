@@ -0,0 +1,151 @@
+// sentiric-sip-signaling-service/src/sip/call_control.rs
+// Aktif bir çağrı üzerinde diyalog içi kontrol istekleri: beklemeye alma (re-INVITE) ve
+// kör/danışmalı aktarım (REFER, RFC 3515 / Replaces, RFC 3891).
+
+use crate::app_state::AppState;
+use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
+use crate::sip::refer::{refer_to_with_replaces, Replaces};
+use crate::sip::requests::{build_ack_for_2xx, build_in_dialog_request, OutgoingRequest};
+use crate::sip::responses::build_sdp_with_direction;
+use crate::sip::transaction::{ClientTransaction, SipResponse, TRANSACTION_TIMEOUT};
+use crate::sip::utils::get_uri_from_header;
+use crate::state::ActiveCallInfo;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+use tracing::{info, instrument, warn};
+
+pub enum ControlOutcome {
+    CallNotFound,
+    /// Karşı taraf transaction süresi içinde yanıt vermedi.
+    NoResponse,
+    Response(SipResponse),
+}
+
+/// Çağrı için yeni bir CSeq ayırır ve diyaloğun güncel kopyasını döndürür. Çağrı yoksa `None`.
+pub async fn reserve_cseq(call_id: &str, state: &AppState) -> Option<(ActiveCallInfo, u32)> {
+    let mut active_calls = state.active_calls.lock().await;
+    let call_info = active_calls.get_mut(call_id)?;
+    let cseq = call_info.next_local_cseq();
+    Some((call_info.clone(), cseq))
+}
+
+/// Çağrıyı beklemeye alır (`hold = true`, SDP `sendonly`) veya devam ettirir (`sendrecv`).
+#[instrument(skip(sock, state))]
+pub async fn set_hold(
+    call_id: &str,
+    hold: bool,
+    sock: Arc<UdpSocket>,
+    state: &AppState,
+) -> Result<ControlOutcome, ServiceError> {
+    let Some((call_info, cseq)) = reserve_cseq(call_id, state).await else {
+        return Ok(ControlOutcome::CallNotFound);
+    };
+
+    let direction = if hold { "sendonly" } else { "sendrecv" };
+    let mut reinvite = build_in_dialog_request(&call_info, "INVITE", cseq);
+    reinvite.body = Some(build_sdp_with_direction(call_info.rtp_port, &state.config, direction));
+
+    let Some(response) = send(reinvite.clone(), &call_info, sock.clone(), state).await? else {
+        warn!("re-INVITE isteğine yanıt alınamadı.");
+        return Ok(ControlOutcome::NoResponse);
+    };
+
+    if response.is_success() {
+        let ack = build_ack_for_2xx(&reinvite, &response).render(&state.config);
+        sock.send_to(ack.as_bytes(), call_info.remote_addr).await?;
+        if let Some(call) = state.active_calls.lock().await.get_mut(call_id) {
+            call.on_hold = hold;
+            // Karşı tarafın yeni SDP cevabı saklanır; medya adresi değişmiş olabilir.
+            if !response.body.is_empty() {
+                call.raw_body = response.body.clone();
+            }
+        }
+        let event_type = if hold { "call.held" } else { "call.resumed" };
+        events::emit(state, CallEvent::for_call(event_type, &call_info));
+        info!(hold, "Çağrının bekleme durumu güncellendi.");
+    } else {
+        warn!(status = response.status_code, "re-INVITE reddedildi.");
+    }
+    Ok(ControlOutcome::Response(response))
+}
+
+/// Karşı tarafı REFER ile aktarır. `replaces_call_id` verilirse danışmalı (attended) aktarım yapılır:
+/// hedef, o çağrının karşı tarafıdır ve Refer-To'ya `Replaces` gömülür (RFC 3891).
+/// `202 Accepted` yalnızca isteğin kabul edildiğini gösterir; sonuç karşı tarafın NOTIFY'larıyla
+/// bildirilir (bkz. `refer::handle_notify`).
+#[instrument(skip(sock, state))]
+pub async fn transfer(
+    call_id: &str,
+    target_uri: &str,
+    replaces_call_id: Option<&str>,
+    sock: Arc<UdpSocket>,
+    state: &AppState,
+) -> Result<ControlOutcome, ServiceError> {
+    let refer_to = match replaces_call_id {
+        Some(other_call_id) => {
+            let other = state.active_calls.lock().await.get(other_call_id).cloned();
+            let Some(other) = other else {
+                return Ok(ControlOutcome::CallNotFound);
+            };
+            let target = get_uri_from_header(&other.contact_header).unwrap_or_else(|| other.contact_header.clone());
+            refer_to_with_replaces(&target, &Replaces::for_dialog(&other))
+        }
+        None => format!("<{}>", get_uri_from_header(target_uri).unwrap_or_else(|| target_uri.to_string())),
+    };
+
+    let Some((call_info, cseq)) = reserve_cseq(call_id, state).await else {
+        return Ok(ControlOutcome::CallNotFound);
+    };
+    if let Some(call) = state.active_calls.lock().await.get_mut(call_id) {
+        call.pending_refer_to = Some(refer_to.clone());
+    }
+
+    let mut refer = build_in_dialog_request(&call_info, "REFER", cseq);
+    refer.extra_headers = vec![
+        format!("Refer-To: {}", refer_to),
+        format!("Referred-By: {}", call_info.to_header),
+    ];
+
+    match send(refer, &call_info, sock, state).await? {
+        Some(response) => {
+            if response.is_success() {
+                info!(refer_to = %refer_to, "REFER kabul edildi.");
+            } else {
+                warn!(status = response.status_code, "REFER reddedildi.");
+            }
+            Ok(ControlOutcome::Response(response))
+        }
+        None => {
+            warn!("REFER isteğine yanıt alınamadı.");
+            Ok(ControlOutcome::NoResponse)
+        }
+    }
+}
+
+/// Diyalog içi isteği istemci transaction'ı ile gönderir ve nihai yanıtı bekler (zaman aşımında `None`).
+/// INVITE transaction'ı geçici yanıttan sonra kendiliğinden zaman aşımına uğramadığından, 100 Trying
+/// gönderip susan bir karşı taraf isteği süresiz bekletmesin diye nihai yanıt 64*T1 ile sınırlanır.
+pub async fn send(
+    request: OutgoingRequest,
+    call_info: &ActiveCallInfo,
+    sock: Arc<UdpSocket>,
+    state: &AppState,
+) -> Result<Option<SipResponse>, ServiceError> {
+    let mut transaction = ClientTransaction::start(
+        request,
+        call_info.remote_addr,
+        sock,
+        state.config.clone(),
+        state.client_transactions.clone(),
+    )
+    .await?;
+    match timeout(TRANSACTION_TIMEOUT, transaction.final_response()).await {
+        Ok(response) => Ok(response),
+        Err(_) => {
+            warn!(method = %transaction.request().method, "Diyalog içi istek için nihai yanıt süresinde alınamadı.");
+            Ok(None)
+        }
+    }
+}
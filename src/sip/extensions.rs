@@ -0,0 +1,130 @@
+// sentiric-sip-signaling-service/src/sip/extensions.rs
+// Bu servisin desteklediği SIP metodları ve uzantıları (option tag'leri), ve desteklenmeyen bir
+// uzantıyı `Require` ile zorunlu kılan isteklerin `420 Bad Extension` ile reddedilmesi (RFC 3261 8.2.2.3).
+
+use crate::app_state::AppState;
+use crate::sip::responses;
+use crate::sip::utils::parse_sip_headers;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+use tracing::warn;
+
+/// Desteklenen option tag'leri: güvenilir geçici yanıtlar (RFC 3262), oturum zamanlayıcıları
+/// (RFC 4028) ve diyalog değiştirme (RFC 3891).
+pub const SUPPORTED: &[&str] = &["100rel", "timer", "replaces"];
+
+/// `handle_sip_request`'in işlediği metodlar.
+pub const ALLOWED_METHODS: &[&str] = &["INVITE", "ACK", "CANCEL", "BYE", "OPTIONS", "REGISTER", "REFER", "NOTIFY", "PRACK", "UPDATE"];
+
+/// Kabul edilen gövde tipleri: SDP ve REFER ilerleme bildirimleri.
+pub const ACCEPTED_CONTENT_TYPES: &[&str] = &["application/sdp", "message/sipfrag"];
+
+pub fn allow_header() -> String {
+    format!("Allow: {}", ALLOWED_METHODS.join(", "))
+}
+
+pub fn supported_header() -> String {
+    format!("Supported: {}", SUPPORTED.join(", "))
+}
+
+/// Virgülle ayrılmış bir option tag başlığında (`Require`, `Supported`) `tag` var mı?
+pub fn lists(headers: &HashMap<String, String>, header: &str, tag: &str) -> bool {
+    option_tags(headers, header).any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+fn option_tags<'a>(headers: &'a HashMap<String, String>, header: &str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get(header)
+        .into_iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+/// İsteğin `Require` ettiği ama bu servisin desteklemediği uzantılar.
+pub fn unsupported(headers: &HashMap<String, String>) -> Vec<&str> {
+    option_tags(headers, "require")
+        .filter(|tag| !SUPPORTED.iter().any(|s| s.eq_ignore_ascii_case(tag)))
+        .collect()
+}
+
+/// İstek desteklenmeyen bir uzantı gerektiriyorsa `420 Bad Extension` gönderir ve `true` döner.
+/// ACK ve CANCEL için uygulanmaz; bu istekler `Require`'a göre reddedilemez.
+pub async fn reject_unsupported(
+    request_str: &str,
+    sock: &UdpSocket,
+    addr: SocketAddr,
+    state: &AppState,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let header_part = request_str.split_once("\r\n\r\n").map_or(request_str, |(h, _)| h);
+    let Some((headers, via_headers)) = parse_sip_headers(header_part) else { return Ok(false) };
+    let unsupported = unsupported(&headers);
+    if unsupported.is_empty() {
+        return Ok(false);
+    }
+    warn!(?unsupported, "İstek desteklenmeyen bir uzantı gerektiriyor.");
+    let response = responses::create_response_with_headers(
+        "420 Bad Extension",
+        &headers,
+        &via_headers,
+        &[format!("Unsupported: {}", unsupported.join(", "))],
+        None,
+        &state.config,
+        addr,
+    );
+    sock.send_to(response.as_bytes(), addr).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(require: &str) -> String {
+        format!(
+            "INVITE sip:1001@sentiric.test SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5070;branch=z9hG4bK-require\r\n\
+             From: <sip:2002@sentiric.test>;tag=caller\r\n\
+             To: <sip:1001@sentiric.test>\r\n\
+             Call-ID: require-test-call\r\n\
+             CSeq: 1 INVITE\r\n\
+             Require: {}\r\n\
+             Content-Length: 0\r\n\r\n",
+            require
+        )
+    }
+
+    #[test]
+    fn unsupported_lists_only_unknown_option_tags() {
+        let (headers, _) = parse_sip_headers(&request("100rel, Timer, precondition ,sec-agree")).unwrap();
+        assert_eq!(unsupported(&headers), vec!["precondition", "sec-agree"]);
+        assert!(lists(&headers, "require", "timer"));
+    }
+
+    #[tokio::test]
+    async fn unsupported_require_is_rejected_with_420() {
+        let state = AppState::for_tests();
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let caller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let caller_addr = caller.local_addr().unwrap();
+
+        assert!(reject_unsupported(&request("100rel, precondition"), &sock, caller_addr, &state).await.unwrap());
+
+        let mut buf = [0u8; 4096];
+        let len = caller.recv(&mut buf).await.unwrap();
+        let response = String::from_utf8_lossy(&buf[..len]);
+        assert!(response.starts_with("SIP/2.0 420 Bad Extension\r\n"));
+        assert!(response.contains("\r\nUnsupported: precondition\r\n"));
+    }
+
+    #[tokio::test]
+    async fn supported_require_is_accepted() {
+        let state = AppState::for_tests();
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let caller_addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+
+        assert!(!reject_unsupported(&request("100rel, timer, replaces"), &sock, caller_addr, &state).await.unwrap());
+    }
+}
@@ -1,5 +1,5 @@
 // src/sip/handler.rs
-use super::{ack, bye, invite, register};
+use super::{ack, bye, extensions, invite, options, refer, register, transaction, update};
 use crate::app_state::AppState;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -26,18 +26,51 @@ pub async fn handle_sip_request(
         "SIP isteği işleyici tarafından alındı (ham içerik)."
     );
 
+    // ACK ve CANCEL, Require başlığına göre reddedilemez (RFC 3261 8.2.2.3).
+    let is_request = !["SIP/2.0", "ACK", "CANCEL"].iter().any(|p| request_str.starts_with(p));
+    if is_request {
+        match extensions::reject_unsupported(request_str, &sock, addr, &state).await {
+            Ok(false) => {}
+            Ok(true) => return,
+            Err(e) => {
+                error!(error = %e, "420 Bad Extension yanıtı gönderilemedi.");
+                return;
+            }
+        }
+    }
+
     let result = if request_str.starts_with("REGISTER") {
         info!("REGISTER isteği işleniyor...");
         register::handle(request_str, sock, addr, state).await
     } else if request_str.starts_with("INVITE") {
         info!("INVITE isteği işleniyor...");
         invite::handle(request_str, sock, addr, state).await
+    } else if request_str.starts_with("CANCEL") {
+        info!("CANCEL isteği işleniyor...");
+        invite::server_transaction::handle_cancel(request_str, sock, addr, state).await
+    } else if request_str.starts_with("OPTIONS") {
+        options::handle(request_str, sock, addr, state).await
+    } else if request_str.starts_with("PRACK") {
+        info!("PRACK isteği işleniyor...");
+        invite::server_transaction::handle_prack(request_str, sock, addr, state).await
+    } else if request_str.starts_with("UPDATE") {
+        info!("UPDATE isteği işleniyor...");
+        update::handle(request_str, sock, addr, state).await
     } else if request_str.starts_with("BYE") {
         info!("BYE isteği işleniyor...");
         bye::handle(request_str, sock, addr, state).await
     } else if request_str.starts_with("ACK") {
         info!("ACK isteği işleniyor...");
         ack::handle(request_str, sock, addr, state).await
+    } else if request_str.starts_with("REFER") {
+        info!("REFER isteği işleniyor...");
+        refer::handle(request_str, sock, addr, state).await
+    } else if request_str.starts_with("NOTIFY") {
+        info!("NOTIFY isteği işleniyor...");
+        refer::handle_notify(request_str, sock, addr, state).await
+    } else if request_str.starts_with("SIP/2.0") {
+        transaction::dispatch_response(request_str, &state.client_transactions);
+        Ok(())
     } else {
         debug!(
             method = &request_str[..request_str.find(' ').unwrap_or(10)],
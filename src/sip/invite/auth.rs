@@ -0,0 +1,204 @@
+// sentiric-sip-signaling-service/src/sip/invite/auth.rs
+// Yapılandırılan realm'lerden gelen INVITE'ların 407 Proxy-Authenticate ile doğrulanması.
+
+use crate::app_state::AppState;
+use crate::error::ServiceError;
+use crate::redis::AsyncCommands;
+use crate::sip::auth;
+use crate::sip::call_context::CallContext;
+use crate::sip::responses::create_response_from_parts;
+use crate::sip::utils::{extract_domain_from_uri, extract_raw_user_from_uri};
+use tokio::net::UdpSocket;
+use tracing::{info, instrument, warn};
+
+const INVITE_NONCE_TTL_SECS: u64 = 300;
+
+pub enum AuthOutcome {
+    /// INVITE doğrulaması yapılandırılmamış.
+    NotRequired,
+    /// Doğrulama başarılı; kullanıcı adı döner.
+    Authenticated(String),
+    /// Arayana 407 veya 403 gönderildi; INVITE işlenmemeli.
+    Rejected,
+}
+
+#[instrument(skip_all)]
+pub async fn authenticate(
+    context: &CallContext,
+    sock: &UdpSocket,
+    state: &AppState,
+) -> Result<AuthOutcome, ServiceError> {
+    let Some(realm) = challenge_realm(context, &state.config.invite_auth_realms) else {
+        return Ok(AuthOutcome::NotRequired);
+    };
+
+    let Some(auth_header) = context.headers.get("proxy-authorization") else {
+        info!(realm = %realm, "Proxy-Authorization başlığı yok, 407 ile challenge gönderiliyor.");
+        challenge(context, &realm, false, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    };
+
+    let credentials = match auth::parse_credentials(auth_header) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(error = %e, "Geçersiz Proxy-Authorization başlığı.");
+            reject(context, sock, state).await?;
+            return Ok(AuthOutcome::Rejected);
+        }
+    };
+
+    // Başka bir realm için verilmiş kimlik bilgileri bu realm'de geçerli sayılmaz.
+    if credentials.realm != realm {
+        warn!(realm = %realm, credentials_realm = %credentials.realm, "Proxy-Authorization realm'i challenge edilen realm ile eşleşmiyor.");
+        reject(context, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    }
+
+    // Özet, bu isteğin Request-URI'si için hesaplanmış olmalı (RFC 3261 22.4).
+    if credentials.uri != context.request_uri {
+        warn!(digest_uri = %credentials.uri, request_uri = %context.request_uri, "Proxy-Authorization uri'si Request-URI ile eşleşmiyor.");
+        reject(context, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    }
+
+    // Yalnızca bu servisin ürettiği ve süresi dolmamış nonce'lar kabul edilir.
+    let mut conn = state.redis.get_multiplexed_async_connection().await?;
+    let nonce_known: bool = conn.exists(nonce_key(&credentials.nonce)).await?;
+    if !nonce_known {
+        info!("Bilinmeyen veya süresi dolmuş nonce, stale=true ile yeniden challenge gönderiliyor.");
+        challenge(context, &realm, true, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    }
+
+    let ha1_hash = match auth::fetch_ha1(state, &credentials.username, &realm).await {
+        Ok(h) => h,
+        Err(e) => {
+            warn!(error = %e, "SIP kullanıcısı bulunamadı veya user-service hatası.");
+            reject(context, sock, state).await?;
+            return Ok(AuthOutcome::Rejected);
+        }
+    };
+
+    if credentials.response != auth::expected_response(&ha1_hash, "INVITE", &credentials) {
+        warn!(username = %credentials.username, "INVITE kimlik doğrulaması başarısız. Yanlış şifre.");
+        reject(context, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    }
+
+    // Doğrulanan kimlik, From başlığında iddia edilen kimlikle aynı olmalı.
+    if extract_raw_user_from_uri(&context.from_header).as_deref() != Some(credentials.username.as_str()) {
+        warn!(username = %credentials.username, from = %context.from_header, "Doğrulanan kullanıcı From başlığıyla eşleşmiyor.");
+        reject(context, sock, state).await?;
+        return Ok(AuthOutcome::Rejected);
+    }
+
+    // Nonce ilk başarılı doğrulamada bu çağrıya bağlanır: aynı Call-ID ile gelen yeniden iletimler
+    // geçer, yakalanmış bir başlığın başka bir çağrıda yeniden kullanılması reddedilir.
+    let used_key = format!("invite_nonce_used:{}", credentials.nonce);
+    let first_use: bool = conn.set_nx(&used_key, &context.call_id).await?;
+    if first_use {
+        conn.expire::<_, ()>(&used_key, INVITE_NONCE_TTL_SECS as i64).await?;
+    } else {
+        let bound_call_id: Option<String> = conn.get(&used_key).await?;
+        if bound_call_id.as_deref() != Some(context.call_id.as_str()) {
+            warn!(username = %credentials.username, "Nonce başka bir çağrıda kullanılmış, stale=true ile yeniden challenge gönderiliyor.");
+            challenge(context, &realm, true, sock, state).await?;
+            return Ok(AuthOutcome::Rejected);
+        }
+    }
+
+    info!(username = %credentials.username, "INVITE kimlik doğrulaması başarılı.");
+    Ok(AuthOutcome::Authenticated(credentials.username))
+}
+
+/// Challenge edilecek realm'i seçer. Arayanın denetimindeki From alan adı kullanılmaz: Request-URI
+/// veya To alan adı listede değilse listedeki ilk realm (varsayılan) ile challenge edilir.
+fn challenge_realm(context: &CallContext, realms: &[String]) -> Option<String> {
+    [&context.request_uri, &context.to_header]
+        .into_iter()
+        .filter_map(|uri| extract_domain_from_uri(uri))
+        .find(|domain| realms.contains(domain))
+        .or_else(|| realms.first().cloned())
+}
+
+fn nonce_key(nonce: &str) -> String {
+    format!("invite_nonce:{}", nonce)
+}
+
+async fn challenge(
+    context: &CallContext,
+    realm: &str,
+    stale: bool,
+    sock: &UdpSocket,
+    state: &AppState,
+) -> Result<(), ServiceError> {
+    let nonce = auth::generate_nonce();
+    let mut conn = state.redis.get_multiplexed_async_connection().await?;
+    let _: () = conn.set_ex(nonce_key(&nonce), true, INVITE_NONCE_TTL_SECS).await?;
+
+    let mut headers = context.headers.clone();
+    headers.insert("proxy-authenticate".to_string(), auth::build_challenge(realm, &nonce, stale));
+    let response = create_response_from_parts(
+        "407 Proxy Authentication Required",
+        &headers,
+        &context.via_headers,
+        None,
+        &state.config,
+        context.remote_addr,
+    );
+    sock.send_to(response.as_bytes(), context.remote_addr).await?;
+    Ok(())
+}
+
+async fn reject(context: &CallContext, sock: &UdpSocket, state: &AppState) -> Result<(), ServiceError> {
+    let response = create_response_from_parts("403 Forbidden", &context.headers, &context.via_headers, None, &state.config, context.remote_addr);
+    sock.send_to(response.as_bytes(), context.remote_addr).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(request_uri: &str, from: &str, to: &str) -> CallContext {
+        let request = format!(
+            "INVITE {} SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5070;branch=z9hG4bK-auth\r\n\
+             From: <{}>;tag=caller\r\n\
+             To: <{}>\r\n\
+             Call-ID: auth-test\r\n\
+             CSeq: 1 INVITE\r\n\
+             Content-Length: 0\r\n\r\n",
+            request_uri, from, to
+        );
+        CallContext::from_request(&request, "127.0.0.1:5070".parse().unwrap(), "trace-auth".to_string()).unwrap()
+    }
+
+    fn realms() -> Vec<String> {
+        vec!["a.sentiric.test".to_string(), "b.sentiric.test".to_string()]
+    }
+
+    #[test]
+    fn realm_comes_from_request_uri_before_to() {
+        let context = invite("sip:1001@b.sentiric.test", "sip:2002@a.sentiric.test", "sip:1001@a.sentiric.test");
+        assert_eq!(challenge_realm(&context, &realms()).as_deref(), Some("b.sentiric.test"));
+    }
+
+    #[test]
+    fn realm_falls_back_to_to_domain() {
+        let context = invite("sip:1001@10.0.0.1", "sip:2002@evil.test", "sip:1001@b.sentiric.test");
+        assert_eq!(challenge_realm(&context, &realms()).as_deref(), Some("b.sentiric.test"));
+    }
+
+    #[test]
+    fn unlisted_from_domain_does_not_skip_the_challenge() {
+        let context = invite("sip:1001@evil.test", "sip:2002@evil.test", "sip:1001@evil.test");
+        assert_eq!(challenge_realm(&context, &realms()).as_deref(), Some("a.sentiric.test"));
+    }
+
+    #[test]
+    fn no_realms_means_no_challenge() {
+        let context = invite("sip:1001@a.sentiric.test", "sip:2002@a.sentiric.test", "sip:1001@a.sentiric.test");
+        assert_eq!(challenge_realm(&context, &[]), None);
+    }
+}
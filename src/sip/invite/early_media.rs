@@ -0,0 +1,112 @@
+// File: src/sip/invite/early_media.rs
+// Cevaplamadan önce 183 Session Progress ile anons çalma (erken medya, RFC 3960). Arayan bu
+// sürede ücretlendirilmez; anonstan sonra çağrı dialplan'e göre cevaplanır veya reddedilir.
+use super::server_transaction::PendingInvite;
+use crate::app_state::AppState;
+use crate::events::{self, CallEvent};
+use crate::sip::responses;
+use crate::sip::utils::extract_sdp_media_info_from_body;
+use crate::state::ActiveCallInfo;
+use sentiric_contracts::sentiric::dialplan::v1::ResolveDialplanResponse;
+use sentiric_contracts::sentiric::media::v1::PlayAudioRequest;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tonic::Request as TonicRequest;
+use tracing::{info, instrument, warn};
+
+/// Dialplan eyleminin `action_data`'sında anonsun URI'si.
+const ANNOUNCEMENT_KEY: &str = "early_media_uri";
+/// Anonstan sonra ne yapılacağı: `answer` (varsayılan) veya bir SIP durum satırı (örn. `486 Busy Here`).
+const OUTCOME_KEY: &str = "early_media_outcome";
+/// `early_media_outcome` geçerli bir hata durum satırı değilse çağrı bununla reddedilir.
+const FALLBACK_REJECT: &str = "480 Temporarily Unavailable";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EarlyMediaOutcome {
+    Answer,
+    /// Anonstan sonra çağrı bu durum satırıyla reddedilir; çağrı hiç cevaplanmaz.
+    Reject(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct EarlyMedia {
+    pub announcement_uri: String,
+    pub outcome: EarlyMediaOutcome,
+}
+
+/// Dialplan eylemi bir erken medya anonsu içeriyorsa planı döndürür.
+pub fn plan(dialplan: &ResolveDialplanResponse) -> Option<EarlyMedia> {
+    let data = &dialplan.action.as_ref()?.action_data.as_ref()?.data;
+    let announcement_uri = data.get(ANNOUNCEMENT_KEY).filter(|uri| !uri.is_empty())?.clone();
+    let outcome = match data.get(OUTCOME_KEY).map(|o| o.trim()) {
+        None | Some("") | Some("answer") => EarlyMediaOutcome::Answer,
+        Some(status_line) => match responses::parse_status_line(status_line) {
+            Some((code, line)) if code >= 300 => EarlyMediaOutcome::Reject(line),
+            _ => {
+                warn!(outcome = %status_line, "Geçersiz early_media_outcome, çağrı {} ile reddedilecek.", FALLBACK_REJECT);
+                EarlyMediaOutcome::Reject(FALLBACK_REJECT.to_string())
+            }
+        },
+    };
+    Some(EarlyMedia { announcement_uri, outcome })
+}
+
+/// 183'ü SDP ile gönderir ve anonsu çalar. Çağrı cevaplanmalıysa `None`, reddedilmeliyse arayana
+/// gönderilecek durum satırını döndürür. Arayan anons sırasında CANCEL ederse çalma yarıda kesilir.
+#[instrument(skip_all, fields(call_id = %call_info.call_id, announcement = %early_media.announcement_uri))]
+pub async fn play(
+    early_media: &EarlyMedia,
+    call_info: &ActiveCallInfo,
+    answer_sdp: &str,
+    pending: &PendingInvite,
+    sock: &Arc<UdpSocket>,
+    state: &AppState,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let progress = responses::create_response_from_parts("183 Session Progress", &call_info.headers, &call_info.via_headers, Some(answer_sdp), &state.config, call_info.remote_addr);
+    pending.send_provisional(progress, sock).await?;
+    events::emit(state, CallEvent::for_call("call.early_media", call_info).with_details(serde_json::json!({ "announcementUri": &early_media.announcement_uri })));
+    info!("183 Session Progress gönderildi, erken medya anonsu çalınıyor.");
+
+    let mut cancelled = pending.subscribe();
+    tokio::select! {
+        result = play_announcement(&early_media.announcement_uri, call_info, state.config.early_media_max_duration, state) => {
+            if let Err(e) = result {
+                // Anons çalınamadıysa çağrı yine de dialplan'in istediği şekilde sonuçlandırılır.
+                warn!(error = %e, "Erken medya anonsu çalınamadı.");
+            }
+        }
+        _ = cancelled.wait_for(|cancelled| *cancelled) => {
+            info!("Arayan anons sırasında çağrıyı iptal etti.");
+            return Ok(Some("487 Request Terminated".to_string()));
+        }
+    }
+
+    Ok(match &early_media.outcome {
+        EarlyMediaOutcome::Answer => None,
+        EarlyMediaOutcome::Reject(status_line) => Some(status_line.clone()),
+    })
+}
+
+/// `PlayAudio`, anons bitene kadar yanıt vermez; süresi anonsa bağlı olduğundan dayanıklılık
+/// katmanının deneme süresi yerine çağıranın verdiği `max_duration` sınırı uygulanır.
+pub(super) async fn play_announcement(
+    audio_uri: &str,
+    call_info: &ActiveCallInfo,
+    max_duration: Duration,
+    state: &AppState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let instance = call_info.media_instance.as_deref().ok_or("Çağrının medya örneği bilinmiyor.")?;
+    let rtp_target_addr = extract_sdp_media_info_from_body(&call_info.raw_body).ok_or("SDP içinde RTP adresi yok.")?;
+    let mut media_client = state.grpc_clients().media.client_for(instance)?;
+    let mut play_req = TonicRequest::new(PlayAudioRequest {
+        audio_uri: audio_uri.to_string(),
+        server_rtp_port: call_info.rtp_port,
+        rtp_target_addr,
+    });
+    play_req.set_timeout(max_duration);
+    play_req.metadata_mut().insert("x-trace-id", call_info.trace_id.parse()?);
+    tokio::time::timeout(max_duration, media_client.play_audio(play_req)).await??;
+    Ok(())
+}
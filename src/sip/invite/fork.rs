@@ -0,0 +1,422 @@
+// sentiric-sip-signaling-service/src/sip/invite/fork.rs
+// Bir çağrının birden fazla hedefe (ring group veya bir kullanıcının birden fazla cihazı)
+// paralel ya da sıralı olarak çaldırılması. Medya uçtan uca akar: bacaklara arayanın SDP teklifi
+// iletilir, arayana da cevaplayan bacağın SDP cevabı döner.
+
+use super::handler::RINGING;
+use super::server_transaction::InviteTransaction;
+use crate::app_state::AppState;
+use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
+use crate::redis;
+use crate::sip::bye;
+use crate::sip::requests::{build_ack_for_2xx, build_cancel, generate_branch, generate_tag, OutgoingRequest};
+use crate::sip::responses;
+use crate::sip::session_timer::{self, SessionTimer};
+use crate::sip::transaction::{ClientTransaction, SipResponse, TRANSACTION_TIMEOUT};
+use crate::sip::utils::extract_user_from_uri;
+use crate::state::{ActiveCallInfo, CallDirection};
+use sentiric_contracts::sentiric::dialplan::v1::ResolveDialplanResponse;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Instant};
+use tracing::{error, info, instrument, warn};
+
+/// Dialplan'in bir çağrıyı hedef grubuna yönlendirmek için döndürdüğü eylem.
+/// `action_data`: `targets` (virgülle ayrılmış dahili numaralar veya SIP URI'leri),
+/// `strategy` (`parallel` | `sequential`), `ring_timeout` (hedef başına saniye).
+pub const RING_GROUP_ACTION: &str = "RING_GROUP";
+const DEFAULT_RING_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkStrategy {
+    Parallel,
+    Sequential,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForkTarget {
+    pub aor: String,
+    pub contact_uri: String,
+    pub next_hop: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForkPlan {
+    pub strategy: ForkStrategy,
+    pub targets: Vec<ForkTarget>,
+    pub ring_timeout: Duration,
+}
+
+pub enum ForkOutcome {
+    /// Cevaplayan bacak, `ActiveCalls`'a eklenmeye hazır bir diyalog olarak döner.
+    Answered(Box<ActiveCallInfo>),
+    /// Arayana iletilecek en iyi nihai yanıtın durum satırı.
+    Failed(String),
+}
+
+enum LegResult {
+    Answered(Box<ActiveCallInfo>),
+    Final(u16, String),
+}
+
+struct LegContext {
+    caller: ActiveCallInfo,
+    sock: Arc<UdpSocket>,
+    state: Arc<AppState>,
+    ringing_sent: AtomicBool,
+    /// Arayanın INVITE transaction'ı; geçici yanıtlar bunun üzerinden gönderilir.
+    transaction: Arc<InviteTransaction>,
+}
+
+/// Dialplan yanıtı bir ring group istiyorsa hedefleri Redis'teki kayıtlardan çözer.
+pub async fn plan(
+    dialplan: &ResolveDialplanResponse,
+    destination_number: &str,
+    state: &AppState,
+) -> Result<Option<ForkPlan>, ServiceError> {
+    let Some(action) = dialplan.action.as_ref() else { return Ok(None) };
+    if action.action != RING_GROUP_ACTION {
+        return Ok(None);
+    }
+    let data = action.action_data.as_ref().map(|d| d.data.clone()).unwrap_or_default();
+
+    let strategy = match data.get("strategy").map(String::as_str) {
+        Some("sequential") => ForkStrategy::Sequential,
+        _ => ForkStrategy::Parallel,
+    };
+    let ring_timeout = data
+        .get("ring_timeout")
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_RING_TIMEOUT);
+    let members: Vec<String> = match data.get("targets") {
+        Some(t) => t.split(',').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect(),
+        None => vec![destination_number.to_string()],
+    };
+
+    let mut targets = Vec::new();
+    for member in members {
+        let aor = if member.starts_with("sip:") {
+            member
+        } else {
+            format!("sip:{}@{}", member, state.config.sip_realm)
+        };
+        let bindings = redis::get_bindings(&state.redis, &aor).await?;
+        if bindings.is_empty() {
+            info!(aor = %aor, "Hedefin aktif kaydı yok, atlanıyor.");
+        }
+        targets.extend(bindings.into_iter().map(|b| ForkTarget {
+            aor: aor.clone(),
+            contact_uri: b.request_uri(state.config.nat_contact_rewrite),
+            next_hop: b.source_addr,
+        }));
+    }
+
+    Ok(Some(ForkPlan { strategy, targets, ring_timeout }))
+}
+
+#[instrument(skip_all, fields(trace_id = %caller.trace_id, strategy = ?plan.strategy, targets = plan.targets.len()))]
+pub async fn execute(
+    plan: ForkPlan,
+    caller: &ActiveCallInfo,
+    transaction: Arc<InviteTransaction>,
+    sock: Arc<UdpSocket>,
+    state: Arc<AppState>,
+) -> ForkOutcome {
+    if plan.targets.is_empty() {
+        warn!("Çaldırılacak kayıtlı hedef bulunamadı.");
+        return ForkOutcome::Failed("480 Temporarily Unavailable".to_string());
+    }
+
+    // Bacaklara arayanın teklifi iletilir; teklifsiz (late offer) bir INVITE'ın cevabı bacağa taşınamaz.
+    if caller.raw_body.trim().is_empty() {
+        warn!("Arayanın INVITE'ı SDP teklifi taşımıyor, çatallama yapılamıyor.");
+        return ForkOutcome::Failed("488 Not Acceptable Here".to_string());
+    }
+
+    let cancel = transaction.subscribe();
+    let ctx = Arc::new(LegContext {
+        caller: caller.clone(),
+        sock,
+        state: state.clone(),
+        ringing_sent: AtomicBool::new(state.config.immediate_provisional == Some(RINGING)),
+        transaction,
+    });
+
+    match plan.strategy {
+        ForkStrategy::Parallel => ring_parallel(plan, ctx, cancel).await,
+        ForkStrategy::Sequential => ring_sequential(plan, ctx, cancel).await,
+    }
+}
+
+/// Cevaplanan bacağı arayan bacağına bağlar ve `ActiveCalls`'a ekler.
+pub async fn bridge(caller_call_id: &str, leg: Box<ActiveCallInfo>, state: &AppState) {
+    let mut active_calls = state.active_calls.lock().await;
+    if let Some(caller) = active_calls.get_mut(caller_call_id) {
+        caller.bridged_call_id = Some(leg.call_id.clone());
+    }
+    active_calls.insert(leg.call_id.clone(), *leg);
+}
+
+/// `caller_cancel`, arayan INVITE'ını CANCEL ettiğinde `true` olur; tüm bacaklar iptal edilir.
+async fn ring_parallel(plan: ForkPlan, ctx: Arc<LegContext>, mut caller_cancel: watch::Receiver<bool>) -> ForkOutcome {
+    let (cancel_tx, cancel_rx) = watch::channel(false);
+    let mut legs = JoinSet::new();
+    for target in plan.targets {
+        legs.spawn(ring_leg(target, ctx.clone(), plan.ring_timeout, cancel_rx.clone()));
+    }
+
+    let mut failures = Vec::new();
+    let mut caller_cancelled = false;
+    loop {
+        tokio::select! {
+            joined = legs.join_next() => {
+                let Some(joined) = joined else { break };
+                match joined {
+                    Ok(LegResult::Answered(leg)) => {
+                        info!(leg_call_id = %leg.call_id, "Bacak cevapladı, diğer bacaklar iptal ediliyor.");
+                        let _ = cancel_tx.send(true);
+                        tokio::spawn(drain_losing_legs(legs, ctx.clone(), cancel_tx));
+                        return ForkOutcome::Answered(leg);
+                    }
+                    Ok(LegResult::Final(code, status_line)) => failures.push((code, status_line)),
+                    Err(e) => error!(error = %e, "Çatallama bacağı görevi beklenmedik şekilde sonlandı."),
+                }
+            }
+            Ok(()) = caller_cancel.changed(), if !caller_cancelled => {
+                if *caller_cancel.borrow() {
+                    info!("Arayan çağrıyı iptal etti, tüm bacaklar iptal ediliyor.");
+                    caller_cancelled = true;
+                    let _ = cancel_tx.send(true);
+                }
+            }
+        }
+    }
+    ForkOutcome::Failed(best_final_response(&failures))
+}
+
+async fn ring_sequential(plan: ForkPlan, ctx: Arc<LegContext>, caller_cancel: watch::Receiver<bool>) -> ForkOutcome {
+    let mut failures = Vec::new();
+    for target in plan.targets {
+        match ring_leg(target, ctx.clone(), plan.ring_timeout, caller_cancel.clone()).await {
+            LegResult::Answered(leg) => return ForkOutcome::Answered(leg),
+            LegResult::Final(code, status_line) => {
+                failures.push((code, status_line));
+                // 6xx, aramanın başka hedeflerde sürdürülmemesi gerektiğini belirtir (RFC 3261 16.7).
+                if code >= 600 || *caller_cancel.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+    ForkOutcome::Failed(best_final_response(&failures))
+}
+
+/// İptal sonrası bitmeyi bekleyen bacakları toplar. Yarış durumunda cevaplayan bacaklar kapatılır.
+async fn drain_losing_legs(mut legs: JoinSet<LegResult>, ctx: Arc<LegContext>, _cancel_tx: watch::Sender<bool>) {
+    while let Some(joined) = legs.join_next().await {
+        if let Ok(LegResult::Answered(leg)) = joined {
+            warn!(leg_call_id = %leg.call_id, "İptal edilen bacak eşzamanlı cevapladı, BYE gönderiliyor.");
+            if bye::send_bye(&leg, ctx.sock.clone(), &ctx.state).await.is_none() {
+                warn!(leg_call_id = %leg.call_id, "Fazla bacak BYE'a yanıt vermedi.");
+            }
+        }
+    }
+}
+
+#[instrument(skip_all, fields(aor = %target.aor, contact = %target.contact_uri))]
+async fn ring_leg(
+    target: ForkTarget,
+    ctx: Arc<LegContext>,
+    ring_timeout: Duration,
+    mut cancel: watch::Receiver<bool>,
+) -> LegResult {
+    let config = ctx.state.config.clone();
+    let caller_user = extract_user_from_uri(&ctx.caller.from_header).unwrap_or_else(|| "unknown".to_string());
+    let local_uri = format!("<sip:{}@{}>", caller_user, config.sip_realm);
+    let local_tag = generate_tag();
+
+    let invite = OutgoingRequest {
+        method: "INVITE".to_string(),
+        request_uri: target.contact_uri.clone(),
+        branch: generate_branch(),
+        from: format!("{};tag={}", local_uri, local_tag),
+        to: format!("<{}>", target.aor),
+        call_id: uuid::Uuid::new_v4().to_string(),
+        cseq: 1,
+        route: Vec::new(),
+        extra_headers: session_timer::offer_headers(&config),
+        body: Some(ctx.caller.raw_body.clone()),
+    };
+
+    let mut transaction = match ClientTransaction::start(
+        invite,
+        target.next_hop,
+        ctx.sock.clone(),
+        config.clone(),
+        ctx.state.client_transactions.clone(),
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            warn!(error = %e, "Bacak INVITE'ı gönderilemedi.");
+            return LegResult::Final(503, "503 Service Unavailable".to_string());
+        }
+    };
+
+    let ring_deadline = Instant::now() + ring_timeout;
+    let mut provisional_received = false;
+    let mut cancel_requested = false;
+    // CANCEL gönderildikten sonra INVITE'ın nihai yanıtı (487) için beklenecek son an. Geçici yanıt
+    // alınmış INVITE'ın kendi zaman aşımı yoktur; yanıt vermeyen bir hedef çatallamayı süresiz
+    // tutmasın diye bekleme 64*T1 ile sınırlanır (RFC 3261 9.1).
+    let mut cancel_deadline: Option<Instant> = None;
+    let mut timed_out = false;
+
+    loop {
+        tokio::select! {
+            response = transaction.recv() => {
+                let Some(response) = response else {
+                    return LegResult::Final(408, "408 Request Timeout".to_string());
+                };
+                if response.is_provisional() {
+                    provisional_received = true;
+                    if matches!(response.status_code, 180 | 183) {
+                        notify_caller_ringing(&ctx).await;
+                    }
+                    // CANCEL yalnızca geçici yanıt alındıktan sonra gönderilebilir (RFC 3261 9.1).
+                    if cancel_requested && cancel_deadline.is_none() {
+                        cancel_deadline = Some(send_cancel(transaction.request(), target.next_hop, &ctx));
+                    }
+                } else if response.is_success() {
+                    let ack = build_ack_for_2xx(transaction.request(), &response).render(&config);
+                    if let Err(e) = ctx.sock.send_to(ack.as_bytes(), target.next_hop).await {
+                        warn!(error = %e, "2xx yanıtı için ACK gönderilemedi.");
+                    }
+                    return LegResult::Answered(Box::new(leg_dialog(&response, transaction.request(), &local_uri, &local_tag, &target, &ctx)));
+                } else if timed_out {
+                    return LegResult::Final(408, "408 Request Timeout".to_string());
+                } else {
+                    info!(status = response.status_code, "Bacak reddedildi.");
+                    return LegResult::Final(response.status_code, response.status_line);
+                }
+            }
+            _ = sleep_until(ring_deadline), if !cancel_requested => {
+                info!("Bacak çalma süresi doldu, iptal ediliyor.");
+                timed_out = true;
+                cancel_requested = true;
+                if provisional_received {
+                    cancel_deadline = Some(send_cancel(transaction.request(), target.next_hop, &ctx));
+                }
+            }
+            Ok(()) = cancel.changed(), if !cancel_requested => {
+                if *cancel.borrow() {
+                    cancel_requested = true;
+                    if provisional_received {
+                        cancel_deadline = Some(send_cancel(transaction.request(), target.next_hop, &ctx));
+                    }
+                }
+            }
+            _ = sleep_until(cancel_deadline.unwrap_or(ring_deadline)), if cancel_deadline.is_some() => {
+                warn!("İptal edilen bacaktan nihai yanıt alınamadı, bacak sonlandırılmış sayılıyor.");
+                return if timed_out {
+                    LegResult::Final(408, "408 Request Timeout".to_string())
+                } else {
+                    LegResult::Final(487, "487 Request Terminated".to_string())
+                };
+            }
+        }
+    }
+}
+
+/// UAC bacağını, `bye::send_bye` ile kapatılabilecek bir `ActiveCallInfo`'ya çevirir.
+/// `to_*` alanları yerel tarafı, `from_header` ise uzak tarafı (etiketiyle) temsil eder. Bacağın kendi
+/// medya portu yoktur; port arayanın diyaloğuna aittir ve onunla serbest bırakılır.
+fn leg_dialog(
+    response: &SipResponse,
+    invite: &OutgoingRequest,
+    local_uri: &str,
+    local_tag: &str,
+    target: &ForkTarget,
+    ctx: &LegContext,
+) -> ActiveCallInfo {
+    ActiveCallInfo {
+        remote_addr: target.next_hop,
+        rtp_port: ctx.caller.rtp_port,
+        trace_id: ctx.caller.trace_id.clone(),
+        to_tag: local_tag.to_string(),
+        created_at: std::time::Instant::now(),
+        headers: response.headers.clone(),
+        via_headers: Vec::new(),
+        call_id: invite.call_id.clone(),
+        from_header: response.headers.get("to").cloned().unwrap_or_else(|| invite.to.clone()),
+        to_header: local_uri.to_string(),
+        contact_header: response.headers.get("contact").cloned().unwrap_or_else(|| format!("<{}>", target.contact_uri)),
+        record_route_header: response.headers.get("record-route").cloned(),
+        raw_body: response.body.clone(),
+        answered_event_published: Arc::new(Mutex::new(true)),
+        bridged_call_id: Some(ctx.caller.call_id.clone()),
+        authenticated_user: None,
+        trunk: None,
+        tenant_id: ctx.caller.tenant_id.clone(),
+        direction: CallDirection::Outbound,
+        on_hold: false,
+        pending_refer_to: None,
+        media_instance: None,
+        local_cseq: invite.cseq,
+        session_timer: SessionTimer::from_uac_response(response),
+        last_activity: std::time::Instant::now(),
+    }
+}
+
+async fn notify_caller_ringing(ctx: &LegContext) {
+    if ctx.ringing_sent.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let caller = &ctx.caller;
+    let ringing = responses::build_180_ringing(&caller.headers, &caller.via_headers, &ctx.state.config, caller.remote_addr);
+    if let Err(e) = ctx.transaction.send_provisional(ringing, &ctx.sock).await {
+        warn!(error = %e, "Arayana 180 Ringing gönderilemedi.");
+    }
+    events::emit(&ctx.state, CallEvent::for_call("call.ringing", caller));
+}
+
+/// CANCEL'ı kendi transaction'ıyla gönderir ve INVITE'ın nihai yanıtının bekleneceği son anı döndürür.
+fn send_cancel(invite: &OutgoingRequest, next_hop: SocketAddr, ctx: &LegContext) -> Instant {
+    let cancel = build_cancel(invite);
+    let sock = ctx.sock.clone();
+    let config = ctx.state.config.clone();
+    let registry = ctx.state.client_transactions.clone();
+    tokio::spawn(async move {
+        match ClientTransaction::start(cancel, next_hop, sock, config, registry).await {
+            Ok(mut transaction) => {
+                if transaction.final_response().await.is_none() {
+                    warn!("CANCEL isteğine yanıt alınamadı.");
+                }
+            }
+            Err(e) => warn!(error = %e, "CANCEL gönderilemedi."),
+        }
+    });
+    Instant::now() + TRANSACTION_TIMEOUT
+}
+
+/// Hiçbir bacak cevaplamadığında arayana iletilecek yanıtı seçer (RFC 3261 16.7.6'ya yakın):
+/// 6xx varsa o, yoksa en düşük sınıftaki gerçek bir ret; yalnızca zaman aşımı varsa 480.
+fn best_final_response(failures: &[(u16, String)]) -> String {
+    if let Some((_, status_line)) = failures.iter().find(|(code, _)| *code >= 600) {
+        return status_line.clone();
+    }
+    failures
+        .iter()
+        .filter(|(code, _)| *code != 408)
+        .min_by_key(|(code, _)| code / 100)
+        .map(|(_, status_line)| status_line.clone())
+        .unwrap_or_else(|| "480 Temporarily Unavailable".to_string())
+}
@@ -1,18 +1,36 @@
 // src/sip/invite/handler.rs
+use super::auth::{self, AuthOutcome};
+use super::fork::{self, ForkOutcome};
+use super::early_media;
+use super::max_duration;
+use super::nat;
 use super::orchestrator;
+use super::reinvite;
+use super::saga::SetupSaga;
+use super::server_transaction::{self, PendingInvite};
 use crate::app_state::AppState;
 use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
 use crate::redis::AsyncCommands;
 use crate::sip::call_context::CallContext;
+use crate::sip::bye;
+use crate::sip::extensions;
+use crate::sip::refer;
 use crate::sip::responses;
+use crate::sip::session_timer;
+use crate::sip::transaction::{T1, T2, TRANSACTION_TIMEOUT};
+use crate::state::{ActiveCallInfo, CallDirection};
 use rand::distributions::{Alphanumeric, DistString};
 use std::error::Error;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::UdpSocket;
+use tokio::time::Instant;
 use tracing::{error, instrument, warn, Span};
 
-#[instrument(skip_all, fields(remote_addr = %addr, call_id, trace_id, caller, destination))]
+pub(super) const RINGING: &str = "180 Ringing";
+
+#[instrument(skip_all, fields(remote_addr = %addr, call_id, trace_id, caller, destination, authenticated_user))]
 pub async fn handle(
     request_str: &str,
     sock: Arc<UdpSocket>,
@@ -20,39 +38,247 @@ pub async fn handle(
     state: Arc<AppState>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let trace_id = format!("trace-{}", Alphanumeric.sample_string(&mut rand::thread_rng(), 12));
-    let context = CallContext::from_request(request_str, addr, trace_id)?;
+    let mut context = CallContext::from_request(request_str, addr, trace_id)?;
 
     Span::current().record("call_id", &context.call_id as &str);
     Span::current().record("trace_id", &context.trace_id as &str);
     Span::current().record("caller", &context.caller_id as &str);
     Span::current().record("destination", &context.destination_number as &str);
 
+    if reinvite::is_in_dialog(&context) {
+        return reinvite::handle(&context, &sock, &state).await;
+    }
+
+    // Kapanış sırasında yalnızca mevcut diyaloglar sürdürülür.
+    if state.is_draining() {
+        warn!("Servis kapanıyor, yeni çağrı reddediliyor.");
+        sock.send_to(responses::create_response("503 Service Unavailable", &context, None, &state.config).as_bytes(), addr).await?;
+        return Ok(());
+    }
+
+    // Doğrulama, yinelenen INVITE kilidinden önce yapılır: 407 sonrası gelen INVITE aynı Call-ID'yi taşır.
+    match auth::authenticate(&context, &sock, &state).await? {
+        AuthOutcome::Rejected => return Ok(()),
+        AuthOutcome::Authenticated(username) => {
+            Span::current().record("authenticated_user", &username as &str);
+            context.authenticated_user = Some(username);
+        }
+        AuthOutcome::NotRequired => {}
+    }
+
+    // 422 sonrası arayan aynı Call-ID ile daha uzun bir süre önerir; bu yüzden kilitten önce yapılır.
+    context.session_timer = match session_timer::negotiate_uas(&context.headers, &state.config) {
+        Ok(timer) => timer,
+        Err(min_se) => {
+            warn!(min_se, "İstenen oturum süresi çok kısa.");
+            let response = responses::create_response_with_headers("422 Session Interval Too Small", &context.headers, &context.via_headers, &[format!("Min-SE: {}", min_se)], None, &state.config, addr);
+            sock.send_to(response.as_bytes(), addr).await?;
+            return Ok(());
+        }
+    };
+
     if check_and_handle_duplicate(&context.call_id, &state.redis).await? {
+        server_transaction::absorb_retransmission(&context.call_id, &sock, &state).await;
         return Ok(());
     }
 
+    // Danışmalı aktarım (RFC 3891): Replaces başlığı mevcut bir diyaloğa işaret etmelidir.
+    let replaced = match context.headers.get("replaces") {
+        Some(value) => match refer::find_replaced_dialog(value, &state).await {
+            Some(call_info) => Some(call_info),
+            None => {
+                warn!(replaces = %value, "Replaces başlığındaki diyalog bulunamadı.");
+                sock.send_to(responses::create_response("481 Call/Transaction Does Not Exist", &context, None, &state.config).as_bytes(), addr).await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
     sock.send_to(responses::create_response("100 Trying", &context, None, &state.config).as_bytes(), addr).await?;
+    let reliable = extensions::lists(&context.headers, "require", "100rel");
+    let pending = PendingInvite::register(&context.call_id, addr, reliable, &state.pending_invites);
+    send_immediate_provisional(&context, &pending, &sock, &state).await?;
+    if let Some(interval) = state.config.provisional_refresh_interval {
+        pending.spawn_refresh(interval, sock.clone());
+    }
 
-    match orchestrator::setup_and_finalize_call(&context, state.clone()).await {
-        Ok(call_info) => {
-            let ringing_response = responses::build_180_ringing(&call_info.headers, &call_info.via_headers, &state.config, call_info.remote_addr);
-            sock.send_to(ringing_response.as_bytes(), call_info.remote_addr).await?;
-            
-            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-            
-            let ok_response = responses::build_200_ok_with_sdp(&call_info.headers, &call_info.via_headers, call_info.rtp_port, &state.config, call_info.remote_addr);
-            sock.send_to(ok_response.as_bytes(), call_info.remote_addr).await?;
-        }
+    // Kurulum başarısız olduysa tamamlanan adımlar orkestratör tarafından geri alınmıştır.
+    let setup = match orchestrator::setup_and_finalize_call(&context, state.clone()).await {
+        Ok(setup) => setup,
         Err(e) => {
             error!(error = %e, "Çağrı kurulumu orkestrasyonu başarısız oldu.");
-            let error_response = responses::create_response("503 Service Unavailable", &context, None, &state.config);
+            let status_line = pending.termination_status().unwrap_or_else(|| orchestrator::setup_failure_response(&e, &state.config));
+            let error_response = responses::create_response_from_parts(status_line, &context.response_headers(), &context.via_headers, None, &state.config, addr);
             sock.send_to(error_response.as_bytes(), addr).await?;
+            return Ok(());
+        }
+    };
+    let call_info = setup.call_info;
+    let mut answer_sdp = nat::answer_sdp(call_info.rtp_port, setup.nat_traversal, &state.config);
+
+    let max_duration = max_duration::plan(&setup.dialplan, &state.config);
+    let early_media = early_media::plan(&setup.dialplan);
+    if let Some(early_media) = &early_media {
+        if let Some(status_line) = early_media::play(early_media, &call_info, &answer_sdp, &pending, &sock, &state).await? {
+            return reject(&call_info, &status_line, &pending, &sock, &state).await;
+        }
+    }
+
+    let answered_leg = match fork::plan(&setup.dialplan, &context.destination_number, &state).await {
+        Ok(Some(plan)) => match fork::execute(plan, &call_info, pending.transaction(), sock.clone(), state.clone()).await {
+            ForkOutcome::Answered(leg) => Some(leg),
+            ForkOutcome::Failed(status_line) => {
+                warn!(status = %status_line, "Hiçbir hedef çağrıyı cevaplamadı.");
+                return reject(&call_info, &status_line, &pending, &sock, &state).await;
+            }
+        },
+        Ok(None) => None,
+        Err(e) => {
+            error!(error = %e, "Çatallama hedefleri çözülemedi.");
+            return reject(&call_info, "503 Service Unavailable", &pending, &sock, &state).await;
         }
+    };
+
+    match answered_leg {
+        Some(leg) => {
+            // Bacak arayanın teklifini cevapladı; medya uçtan uca akar. Erken medya çalındıysa arayanın
+            // cevabı 183 ile belirlenmiştir ve 200 OK'teki farklı bir SDP yok sayılır (RFC 3261 13.2.1).
+            if early_media.is_none() {
+                answer_sdp = leg.raw_body.clone();
+            }
+            fork::bridge(&call_info.call_id, leg, &state).await;
+        }
+        // Erken medya çalındıysa arayan zaten ilerleme bildirimi almıştır.
+        None if early_media.is_some() => {}
+        None => {
+            if state.config.immediate_provisional != Some(RINGING) {
+                let ringing_response = responses::build_180_ringing(&call_info.headers, &call_info.via_headers, &state.config, call_info.remote_addr);
+                pending.send_provisional(ringing_response, &sock).await?;
+                events::emit(&state, CallEvent::for_call("call.ringing", &call_info));
+            }
+
+            // Çaldırma süresi boyunca gelen CANCEL beklenmeden işlenir.
+            let mut cancelled = pending.subscribe();
+            let _ = tokio::time::timeout(state.config.ring_duration, cancelled.wait_for(|cancelled| *cancelled)).await;
+            if let Some(status_line) = pending.termination_status() {
+                return reject(&call_info, status_line, &pending, &sock, &state).await;
+            }
+        }
+    }
+
+    // SDP cevabını taşıyan güvenilir 183 PRACK'lenmeden 2xx gönderilemez (RFC 3262 3).
+    if !pending.await_answer_prack().await {
+        warn!("Erken medya yanıtı PRACK ile onaylanmadı, çağrı reddediliyor.");
+        let bridged_call_id = bridged_call_id(&call_info.call_id, &state).await;
+        let result = reject(&call_info, "504 Server Time-out", &pending, &sock, &state).await;
+        close_bridged_leg(bridged_call_id, sock, &state).await;
+        return result;
     }
-    
+
+    // Nihai yanıttan sonra gelen CANCEL'ın etkisi yoktur.
+    drop(pending);
+    let session_headers = call_info.session_timer.map(|t| t.response_headers()).unwrap_or_default();
+    let ok_response = responses::build_200_ok_with_sdp(&call_info.headers, &call_info.via_headers, &answer_sdp, &session_headers, &state.config, call_info.remote_addr);
+    if let Err(e) = sock.send_to(ok_response.as_bytes(), call_info.remote_addr).await {
+        abort_answered_call(&call_info, "send_error", sock, state).await;
+        return Err(e.into());
+    }
+    if let Some(old) = replaced {
+        refer::complete_replacement(old, &call_info, sock.clone(), state.clone());
+    }
+    // Süre, arayan için cevaplandığı andan itibaren işler.
+    if let Some(max_duration) = max_duration {
+        max_duration::enforce(max_duration, call_info.call_id.clone(), sock.clone(), state.clone());
+    }
+    tokio::spawn(retransmit_until_ack(ok_response, call_info, sock, state));
+
+    Ok(())
+}
+
+/// Kurulumu beklemeden arayana geçici bir yanıt gönderir; yavaş bir dialplan sırasında arayan sessizlik
+/// duymaz ve ara sunucular INVITE'ı zaman aşımına uğratmaz.
+async fn send_immediate_provisional(
+    context: &CallContext,
+    pending: &PendingInvite,
+    sock: &Arc<UdpSocket>,
+    state: &AppState,
+) -> std::io::Result<()> {
+    let Some(status_line) = state.config.immediate_provisional else { return Ok(()) };
+    let response = responses::create_response_from_parts(status_line, &context.response_headers(), &context.via_headers, None, &state.config, context.remote_addr);
+    pending.send_provisional(response, sock).await?;
+    if status_line == RINGING {
+        let ringing = CallEvent::new("call.ringing", &context.call_id, &context.trace_id, CallDirection::Inbound, &context.from_header);
+        events::emit(state, ringing);
+    }
+    Ok(())
+}
+
+/// Kurulmuş ama henüz cevaplanmamış çağrıyı nihai bir hata yanıtıyla reddeder ve kurulumu geri alır.
+/// Transaction sonlandırıldıysa (CANCEL, PRACK gelmemesi) onun yanıtı gönderilir.
+async fn reject(
+    call_info: &ActiveCallInfo,
+    status_line: &str,
+    pending: &PendingInvite,
+    sock: &UdpSocket,
+    state: &AppState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let status_line = pending.termination_status().unwrap_or(status_line);
+    let error_response = responses::create_response_from_parts(status_line, &call_info.headers, &call_info.via_headers, None, &state.config, call_info.remote_addr);
+    let sent = sock.send_to(error_response.as_bytes(), call_info.remote_addr).await;
+    SetupSaga::for_call(call_info).compensate(state, status_line).await;
+    sent?;
     Ok(())
 }
 
+/// 200 OK, ACK gelene kadar T1'den başlayıp T2'ye kadar ikiye katlanan aralıklarla yeniden gönderilir
+/// (RFC 3261 13.3.1.4). 64*T1 içinde ACK gelmezse diyalog kapatılır ve kurulum geri alınır.
+async fn retransmit_until_ack(ok_response: String, call_info: ActiveCallInfo, sock: Arc<UdpSocket>, state: Arc<AppState>) {
+    let deadline = Instant::now() + TRANSACTION_TIMEOUT;
+    let mut interval = T1;
+    loop {
+        tokio::time::sleep(interval).await;
+        if *call_info.answered_event_published.lock().await || !state.active_calls.lock().await.contains_key(&call_info.call_id) {
+            return;
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        if let Err(e) = sock.send_to(ok_response.as_bytes(), call_info.remote_addr).await {
+            warn!(error = %e, "200 OK yeniden gönderilemedi.");
+        }
+        interval = (interval * 2).min(T2);
+    }
+    warn!(call_id = %call_info.call_id, "200 OK için ACK alınmadı, çağrı kapatılıyor.");
+    abort_answered_call(&call_info, "ack_timeout", sock, state).await;
+}
+
+/// Arayana 200 OK gönderilmiş (veya gönderilememiş) ama diyaloğu ACK ile kurulmamış çağrıyı kapatır:
+/// kurulum geri alınır, arayana ve köprülenmiş bacağa BYE gönderilir.
+async fn abort_answered_call(call_info: &ActiveCallInfo, reason: &str, sock: Arc<UdpSocket>, state: Arc<AppState>) {
+    let bridged_call_id = bridged_call_id(&call_info.call_id, &state).await;
+    SetupSaga::for_call(call_info).compensate(&state, reason).await;
+    close_bridged_leg(bridged_call_id, sock.clone(), &state).await;
+    bye::send_bye(call_info, sock, &state).await;
+}
+
+/// Arayana bağlanmış (çatallama ile cevaplanmış) bacağın Call-ID'si. Kurulum geri alınmadan önce okunmalıdır.
+async fn bridged_call_id(call_id: &str, state: &AppState) -> Option<String> {
+    state.active_calls.lock().await.get(call_id).and_then(|c| c.bridged_call_id.clone())
+}
+
+/// Arayanın kurulumu geri alındığında ona bağlanmış bacağı kapatır.
+async fn close_bridged_leg(bridged_call_id: Option<String>, sock: Arc<UdpSocket>, state: &AppState) {
+    let bridged = match bridged_call_id {
+        Some(id) => state.active_calls.lock().await.remove(&id),
+        None => None,
+    };
+    if let Some(leg) = bridged {
+        orchestrator::release_call_media(&leg, state).await;
+        bye::send_bye(&leg, sock, state).await;
+    }
+}
+
 #[instrument(skip(redis_client))]
 async fn check_and_handle_duplicate(call_id: &str, redis_client: &Arc<crate::redis::Client>) -> Result<bool, ServiceError> {
     let mut conn = redis_client.get_multiplexed_async_connection().await?;
@@ -0,0 +1,65 @@
+// File: src/sip/invite/max_duration.rs
+// Cevaplanan gelen çağrıların en uzun süresi. Sınır dialplan eyleminden veya kiracı yapılandırmasından
+// gelir; süre dolunca çağrı BYE ile kapatılır ve `call.ended` `max_duration_exceeded` nedeniyle
+// yayınlanır. İsteğe bağlı olarak süre dolmadan önce media-service üzerinden bir uyarı anonsu çalınır.
+use super::early_media::play_announcement;
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::sip::bye;
+use sentiric_contracts::sentiric::dialplan::v1::ResolveDialplanResponse;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep_until, Instant};
+use tracing::{info, warn};
+
+/// Dialplan eyleminin `action_data`'sında en uzun süre (saniye); `0` kiracı sınırını kaldırır.
+const MAX_DURATION_KEY: &str = "max_duration_seconds";
+/// Dialplan eyleminin `action_data`'sında uyarı anonsunun URI'si; genel ayarı ezer.
+const WARNING_URI_KEY: &str = "max_duration_warning_uri";
+
+#[derive(Debug, Clone)]
+pub struct MaxDuration {
+    pub limit: Duration,
+    pub warning_uri: Option<String>,
+}
+
+/// Çağrı için süre sınırını belirler: önce dialplan eylemi, sonra kiracı ayarı, sonra genel ayar.
+pub fn plan(dialplan: &ResolveDialplanResponse, config: &AppConfig) -> Option<MaxDuration> {
+    let data = dialplan.action.as_ref().and_then(|a| a.action_data.as_ref()).map(|d| &d.data);
+    let limit = match data.and_then(|d| d.get(MAX_DURATION_KEY)).and_then(|v| v.trim().parse::<u64>().ok()) {
+        Some(secs) => Some(secs).filter(|secs| *secs > 0).map(Duration::from_secs),
+        None => match config.max_call_duration_tenants.get(&dialplan.tenant_id) {
+            Some(limit) => *limit,
+            None => config.max_call_duration,
+        },
+    }?;
+    let warning_uri = data
+        .and_then(|d| d.get(WARNING_URI_KEY))
+        .filter(|uri| !uri.is_empty())
+        .cloned()
+        .or_else(|| config.max_duration_warning_uri.clone());
+    Some(MaxDuration { limit, warning_uri })
+}
+
+/// Cevaplanan çağrı için süre sınırını zamanlar. Çağrı süre dolmadan başka bir akış tarafından
+/// kapatılırsa görev hiçbir şey yapmadan biter.
+pub fn enforce(max_duration: MaxDuration, call_id: String, sock: Arc<UdpSocket>, state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let deadline = Instant::now() + max_duration.limit;
+        let lead = state.config.max_duration_warning_lead.min(max_duration.limit);
+        if let Some(warning_uri) = max_duration.warning_uri.as_deref().filter(|_| !lead.is_zero()) {
+            sleep_until(deadline - lead).await;
+            let Some(call_info) = state.active_calls.lock().await.get(&call_id).cloned() else { return };
+            info!(call_id = %call_id, warning_uri, "En uzun çağrı süresi dolmak üzere, uyarı anonsu çalınıyor.");
+            // Anons en geç süre dolduğunda kesilir.
+            if let Err(e) = play_announcement(warning_uri, &call_info, lead, &state).await {
+                warn!(call_id = %call_id, error = %e, "Süre uyarısı anonsu çalınamadı.");
+            }
+        }
+        sleep_until(deadline).await;
+        if bye::hang_up(&call_id, "max_duration_exceeded", sock, &state).await {
+            info!(call_id = %call_id, limit_secs = max_duration.limit.as_secs(), "En uzun çağrı süresi doldu, çağrı kapatıldı.");
+        }
+    });
+}
@@ -1,8 +1,16 @@
 // File: src/sip/invite/mod.rs
 // Bu modül, bir INVITE isteğinin işlenmesiyle ilgili tüm mantığı içerir.
 
+pub mod auth;
+pub mod early_media;
+pub mod fork;
+pub mod nat;
 pub mod handler;
+pub mod max_duration;
 pub mod orchestrator;
+pub mod reinvite;
+pub mod saga;
+pub mod server_transaction;
 // response_builder modülü artık gereksiz olduğu için kaldırıldı.
 
 // Ana `sip` modülünün kolayca erişebilmesi için `handler` fonksiyonunu public yapıyoruz.
@@ -0,0 +1,113 @@
+// File: src/sip/invite/nat.rs
+// Gelen çağrıda arayanın medyasına ulaşmak için NAT geçiş stratejisinin seçimi ve uygulanması.
+use crate::app_state::AppState;
+use crate::config::{AppConfig, NatTraversal};
+use crate::grpc::resilience::{self, Dependency};
+use crate::sip::call_context::CallContext;
+use crate::sip::responses;
+use crate::sip::utils::{extract_sdp_media_info_from_body, is_private_ip};
+use base64::{engine::general_purpose, Engine as _};
+use sentiric_contracts::sentiric::media::v1::{EnableRtpLatchingRequest, PlayAudioRequest};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::Request as TonicRequest;
+use tracing::{info, instrument, warn};
+
+/// 1 saniyelik 8 kHz, 16 bit sessizlik.
+const SILENCE_BURST_BYTES: usize = 16000;
+
+/// Kiracıya tanımlı strateji, yoksa genel strateji kullanılır. `Auto`, teklif SDP'sine göre çözülür.
+pub fn select(context: &CallContext, tenant_id: Option<&str>, config: &AppConfig) -> NatTraversal {
+    let configured = tenant_id
+        .and_then(|tenant| config.nat_traversal_tenants.get(tenant))
+        .copied()
+        .unwrap_or(config.nat_traversal);
+    match configured {
+        NatTraversal::Auto => detect(&context.raw_body, context.remote_addr),
+        strategy => strategy,
+    }
+}
+
+/// Arayan comedia istiyorsa (RFC 4145 `a=direction:active`) comedia; SDP adresi özel bir IP ise veya
+/// paketin geldiği IP ile eşleşmiyorsa symmetric RTP; aksi halde NAT geçişi gerekmez.
+fn detect(sdp: &str, source: SocketAddr) -> NatTraversal {
+    if sdp.lines().any(|line| line.trim() == "a=direction:active") {
+        return NatTraversal::Comedia;
+    }
+    let sdp_ip = extract_sdp_media_info_from_body(sdp)
+        .and_then(|addr| addr.parse::<SocketAddr>().ok())
+        .map(|addr| addr.ip());
+    match sdp_ip {
+        Some(ip) if is_private_ip(&ip) || ip != source.ip() => NatTraversal::SymmetricRtp,
+        _ => NatTraversal::None,
+    }
+}
+
+/// Arayana gönderilecek SDP cevabı. Comedia'da sunucu pasif taraftır.
+pub fn answer_sdp(rtp_port: u32, strategy: NatTraversal, config: &AppConfig) -> String {
+    let mut sdp = responses::build_sdp(rtp_port, config);
+    if strategy == NatTraversal::Comedia {
+        sdp.push_str("a=direction:passive\r\n");
+    }
+    sdp
+}
+
+/// Stratejiyi media-service üzerinde uygular. Çağrı kurulumunu bekletmemek için arka planda çalışır;
+/// başarısızlık yalnızca loglanır (en kötü durumda medya tek yönlü kalır).
+#[instrument(skip(context, state), fields(trace_id = %context.trace_id, strategy = strategy.as_str()))]
+pub fn apply(strategy: NatTraversal, context: &CallContext, rtp_port: u32, media_instance: &str, state: Arc<AppState>) {
+    let sdp_target = extract_sdp_media_info_from_body(&context.raw_body);
+    let media_instance = media_instance.to_string();
+    match strategy {
+        NatTraversal::None | NatTraversal::Auto => {}
+        NatTraversal::SilenceBurst => {
+            let Some(target_addr) = sdp_target else {
+                warn!("SDP içinde geçerli RTP adresi bulunamadı. NAT delme yapılamıyor.");
+                return;
+            };
+            info!(target = %target_addr, "NAT delme için sessizlik gönderiliyor.");
+            let silence_uri = format!("data:audio/pcm;base64,{}", general_purpose::STANDARD.encode(vec![0u8; SILENCE_BURST_BYTES]));
+            tokio::spawn(async move {
+                let deadline = resilience::rpc_deadline(&state.config);
+                let result = resilience::call(&state, Dependency::Media, deadline, false, |timeout| {
+                    let media_client = state.grpc_clients().media.client_for(&media_instance);
+                    let mut play_req = TonicRequest::new(PlayAudioRequest {
+                        audio_uri: silence_uri.clone(),
+                        server_rtp_port: rtp_port,
+                        rtp_target_addr: target_addr.clone(),
+                    });
+                    play_req.set_timeout(timeout);
+                    async move { media_client?.play_audio(play_req).await }
+                })
+                .await;
+                if let Err(e) = result {
+                    warn!("NAT delme (PlayAudio) başarısız oldu: {}", e);
+                }
+            });
+        }
+        NatTraversal::SymmetricRtp | NatTraversal::Comedia => {
+            // Comedia'da SDP adresi anlamsız olabilir; media-service ilk paketin kaynağını kabul eder.
+            let expected_remote_addr = match strategy {
+                NatTraversal::SymmetricRtp => sdp_target.unwrap_or_default(),
+                _ => String::new(),
+            };
+            tokio::spawn(async move {
+                // Aynı porta aynı bayrağı tekrar göndermek zararsızdır.
+                let deadline = resilience::rpc_deadline(&state.config);
+                let result = resilience::call(&state, Dependency::Media, deadline, true, |timeout| {
+                    let media_client = state.grpc_clients().media.client_for(&media_instance);
+                    let mut latch_req = TonicRequest::new(EnableRtpLatchingRequest {
+                        server_rtp_port: rtp_port,
+                        expected_remote_addr: expected_remote_addr.clone(),
+                    });
+                    latch_req.set_timeout(timeout);
+                    async move { media_client?.enable_rtp_latching(latch_req).await }
+                })
+                .await;
+                if let Err(e) = result {
+                    warn!(error = %e, "RTP adres öğrenme (latching) etkinleştirilemedi.");
+                }
+            });
+        }
+    }
+}
@@ -1,79 +1,99 @@
 // sentiric-sip-signaling-service/src/sip/invite/orchestrator.rs
 
+use super::nat;
+use super::saga::{Compensation, SetupSaga, SetupServices};
 use crate::app_state::AppState;
+use crate::config::{AppConfig, NatTraversal};
 use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
+use crate::grpc::resilience::{self, Dependency};
 use crate::rabbitmq::connection::RABBITMQ_EXCHANGE_NAME;
 use crate::sip::call_context::CallContext;
 use crate::sip::utils::extract_sdp_media_info_from_body;
-use crate::state::ActiveCallInfo;
-use base64::{engine::general_purpose, Engine as _};
+use crate::state::{ActiveCallInfo, CallDirection};
 use lapin::{options::*, BasicProperties, Channel as LapinChannel};
-use rand::Rng;
 use sentiric_contracts::sentiric::{
     dialplan::v1::{ResolveDialplanRequest, ResolveDialplanResponse},
-    media::v1::{AllocatePortRequest, PlayAudioRequest},
+    media::v1::{AllocatePortRequest, ReleasePortRequest, UpdateRtpTargetRequest},
 };
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tonic::Request as TonicRequest;
-use tracing::{debug, info, instrument, warn};
+use tokio::time::Instant;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request as TonicRequest};
+use tracing::{debug, error, info, instrument, warn};
 
-#[instrument(skip_all, fields(trace_id = %context.trace_id))]
+/// Başarılı bir çağrı kurulumunun sonucu.
+pub struct CallSetup {
+    pub call_info: ActiveCallInfo,
+    pub dialplan: ResolveDialplanResponse,
+    /// Arayanın medyası için seçilen NAT geçiş stratejisi; SDP cevabını da belirler.
+    pub nat_traversal: NatTraversal,
+}
+
+/// Çağrıyı kurar; herhangi bir adım başarısız olursa tamamlanan adımlar geri alınır ve
+/// `call.failed` yayınlanmış olarak hata döner.
 pub async fn setup_and_finalize_call(
     context: &CallContext,
     state: Arc<AppState>,
-) -> Result<ActiveCallInfo, ServiceError> {
-    let dialplan_response = resolve_dialplan(context, state.clone()).await?;
-    info!(dialplan_id = %dialplan_response.dialplan_id, "Dialplan başarıyla çözüldü.");
+) -> Result<CallSetup, ServiceError> {
+    setup_with(context, state.clone(), state.as_ref()).await
+}
 
-    let rtp_port = allocate_media_port(context, state.clone()).await?;
-    info!(rtp_port, "Medya portu başarıyla ayrıldı.");
-
-    // --- NAT DELME (HOLE PUNCHING) BAŞLANGIÇ ---
-    if let Some(target_addr) = extract_sdp_media_info_from_body(&context.raw_body) {
-        info!(target = %target_addr, "SDP'den hedef RTP adresi bulundu. NAT delme işlemi başlatılıyor...");
-        
-        // GÜNCELLEME: SÜREYİ UZAT (1 Saniye)
-        // 1 saniye = 1000ms
-        // 8000Hz * 1s = 8000 samples
-        // 8000 samples * 2 bytes = 16000 bytes
-        let silence_bytes = vec![0u8; 16000];
-        
-        let silence_b64 = general_purpose::STANDARD.encode(&silence_bytes);
-        let silence_uri = format!("data:audio/pcm;base64,{}", silence_b64);
-        
-        let mut media_client = state.grpc.media.clone();
-        let play_req = TonicRequest::new(PlayAudioRequest {
-            audio_uri: silence_uri,
-            server_rtp_port: rtp_port,
-            rtp_target_addr: target_addr.clone(),
-        });
-        
-        tokio::spawn(async move {
-            if let Err(e) = media_client.play_audio(play_req).await {
-                warn!("NAT delme (PlayAudio) başarısız oldu: {}", e);
-            } else {
-                info!("NAT delme paketi (1 Saniye Sessizlik) gönderildi.");
-            }
-        });
-    } else {
-        warn!("SDP içinde geçerli RTP adresi bulunamadı. NAT delme yapılamıyor.");
+#[instrument(skip_all, fields(trace_id = %context.trace_id))]
+pub(super) async fn setup_with(
+    context: &CallContext,
+    state: Arc<AppState>,
+    services: &impl SetupServices,
+) -> Result<CallSetup, ServiceError> {
+    let mut saga = SetupSaga::new(context);
+    match run_setup(context, state.clone(), services, &mut saga).await {
+        Ok(setup) => Ok(setup),
+        Err(e) => {
+            saga.compensate_with(&state, services, setup_failure_response(&e, &state.config)).await;
+            Err(e)
+        }
+    }
+}
+
+/// Kurulum hatasında arayana gönderilecek durum satırı. Açık devre, bağımlılığın bilinen bir
+/// arızasıdır; yanıtı yapılandırılabilir (örn. 480 ile başka bir hedefe yönlendirme).
+pub fn setup_failure_response<'a>(error: &ServiceError, config: &'a AppConfig) -> &'a str {
+    match error {
+        ServiceError::CircuitOpen(_) => config.circuit_open_sip_response.as_str(),
+        _ => "503 Service Unavailable",
     }
-    // --- NAT DELME BİTİŞ ---
+}
+
+async fn run_setup(
+    context: &CallContext,
+    state: Arc<AppState>,
+    services: &impl SetupServices,
+    saga: &mut SetupSaga,
+) -> Result<CallSetup, ServiceError> {
+    // Dialplan ve medya çağrıları, arayanın INVITE'ı için ayrılan toplam süreyi paylaşır.
+    let deadline = context.received_at + state.config.invite_setup_budget;
+    let dialplan_response = services.resolve_dialplan(context, deadline).await?;
+    info!(dialplan_id = %dialplan_response.dialplan_id, "Dialplan başarıyla çözüldü.");
+
+    let tenant_id = Some(dialplan_response.tenant_id.as_str()).filter(|t| !t.is_empty());
+    saga.set_tenant(tenant_id.map(str::to_string));
+    let media = services.allocate_media_port(context, tenant_id, deadline).await?;
+    saga.port_allocated(&media);
+    let rtp_port = media.rtp_port;
+    info!(rtp_port, media_instance = %media.instance, "Medya portu başarıyla ayrıldı.");
 
-    let mut response_headers = context.headers.clone();
-    let to_tag: u32 = rand::thread_rng().gen();
-    response_headers
-        .entry("to".to_string())
-        .and_modify(|v| *v = format!("{};tag={}", v, to_tag));
+    let nat_traversal = nat::select(context, tenant_id, &state.config);
+    info!(strategy = nat_traversal.as_str(), "NAT geçiş stratejisi seçildi.");
+    nat::apply(nat_traversal, context, rtp_port, &media.instance, state.clone());
 
     let call_info = ActiveCallInfo {
         remote_addr: context.remote_addr,
         rtp_port,
         trace_id: context.trace_id.clone(),
-        to_tag: to_tag.to_string(),
+        to_tag: context.local_tag.clone(),
         created_at: std::time::Instant::now(),
-        headers: response_headers.clone(),
+        headers: context.response_headers(),
         via_headers: context.via_headers.clone(),
         call_id: context.call_id.clone(),
         from_header: context.from_header.clone(),
@@ -82,6 +102,17 @@ pub async fn setup_and_finalize_call(
         record_route_header: context.record_route_header.clone(),
         raw_body: context.raw_body.clone(),
         answered_event_published: Arc::new(Mutex::new(false)),
+        bridged_call_id: None,
+        authenticated_user: context.authenticated_user.clone(),
+        trunk: None,
+        tenant_id: Some(dialplan_response.tenant_id.clone()).filter(|t| !t.is_empty()),
+        direction: CallDirection::Inbound,
+        on_hold: false,
+        pending_refer_to: None,
+        media_instance: Some(media.instance),
+        local_cseq: 0,
+        session_timer: context.session_timer,
+        last_activity: std::time::Instant::now(),
     };
 
     state
@@ -89,46 +120,196 @@ pub async fn setup_and_finalize_call(
         .lock()
         .await
         .insert(call_info.call_id.clone(), call_info.clone());
+    saga.record(Compensation::RemoveActiveCall { call_id: call_info.call_id.clone() });
     info!("Aktif çağrı durumu başarıyla kaydedildi.");
+    events::emit(&state, CallEvent::for_call("call.started", &call_info));
+
+    services.publish_call_started(&call_info, &dialplan_response).await?;
+
+    Ok(CallSetup { call_info, dialplan: dialplan_response, nat_traversal })
+}
+
+impl SetupServices for AppState {
+    async fn resolve_dialplan(&self, context: &CallContext, deadline: Instant) -> Result<ResolveDialplanResponse, ServiceError> {
+        resolve_dialplan(context, deadline, self).await
+    }
+
+    async fn allocate_media_port(&self, context: &CallContext, tenant_id: Option<&str>, deadline: Instant) -> Result<MediaAllocation, ServiceError> {
+        allocate_media_port(&context.call_id, &context.trace_id, tenant_id, deadline, self).await
+    }
+
+    async fn release_media_port(&self, instance: &str, rtp_port: u32, trace_id: &str) -> Result<(), ServiceError> {
+        release_media_port(instance, rtp_port, trace_id, self).await
+    }
 
-    if let Some(rabbit_channel) = &state.rabbit {
-        publish_call_event("call.started", &call_info, Some(&dialplan_response), rabbit_channel)
-            .await?;
-    } else {
-        warn!("RabbitMQ bağlantısı aktif değil, 'call.started' olayı yayınlanamadı.");
+    async fn publish_call_started(&self, call_info: &ActiveCallInfo, dialplan: &ResolveDialplanResponse) -> Result<(), ServiceError> {
+        match &self.rabbit {
+            Some(rabbit_channel) => publish_call_event("call.started", call_info, Some(dialplan), rabbit_channel).await,
+            None => {
+                warn!("RabbitMQ bağlantısı aktif değil, 'call.started' olayı yayınlanamadı.");
+                Ok(())
+            }
+        }
     }
 
-    Ok(call_info)
+    async fn publish_call_failed(&self, event: &CallEvent, reason: &str) {
+        let Some(rabbit_channel) = &self.rabbit else {
+            warn!("RabbitMQ bağlantısı aktif değil, 'call.failed' olayı yayınlanamadı.");
+            return;
+        };
+        let event_payload = serde_json::json!({
+            "eventType": "call.failed",
+            "traceId": &event.trace_id,
+            "callId": &event.call_id,
+            "fromUri": &event.caller_uri,
+            "tenantId": &event.tenant_id,
+            "reason": reason,
+            "timestamp": &event.timestamp,
+        });
+        if let Err(e) = rabbit_channel.basic_publish(
+            RABBITMQ_EXCHANGE_NAME,
+            "call.failed",
+            BasicPublishOptions::default(),
+            event_payload.to_string().as_bytes(),
+            BasicProperties::default().with_delivery_mode(2).with_content_type("application/json".into()),
+        ).await {
+            error!(error = %e, "'call.failed' olayı yayınlanırken hata oluştu.");
+        }
+    }
 }
 
 #[instrument(skip(context, state))]
 async fn resolve_dialplan(
     context: &CallContext,
-    state: Arc<AppState>,
+    deadline: Instant,
+    state: &AppState,
 ) -> Result<ResolveDialplanResponse, ServiceError> {
-    let mut dialplan_client = state.grpc.dialplan.clone();
-    let mut dialplan_req = TonicRequest::new(ResolveDialplanRequest {
-        caller_contact_value: context.caller_id.clone(),
-        destination_number: context.destination_number.clone(),
-    });
-    dialplan_req.metadata_mut().insert("x-trace-id", context.trace_id.parse()?);
-    let dialplan_res = dialplan_client.resolve_dialplan(dialplan_req).await?.into_inner();
-    Ok(dialplan_res)
+    let trace_id: MetadataValue<_> = context.trace_id.parse()?;
+    let authenticated_user: Option<MetadataValue<_>> = context.authenticated_user.as_deref().map(str::parse).transpose()?;
+    // Dialplan çözümlemesi salt okunurdur; geçici hatalarda tekrar edilebilir.
+    resilience::call(state, Dependency::Dialplan, deadline, true, |timeout| {
+        let mut dialplan_client = state.grpc_clients().dialplan;
+        let mut dialplan_req = TonicRequest::new(ResolveDialplanRequest {
+            caller_contact_value: context.caller_id.clone(),
+            destination_number: context.destination_number.clone(),
+        });
+        dialplan_req.set_timeout(timeout);
+        dialplan_req.metadata_mut().insert("x-trace-id", trace_id.clone());
+        if let Some(user) = &authenticated_user {
+            dialplan_req.metadata_mut().insert("x-authenticated-user", user.clone());
+        }
+        async move { dialplan_client.resolve_dialplan(dialplan_req).await.map(|r| r.into_inner()) }
+    })
+    .await
 }
 
-#[instrument(skip(context, state))]
-async fn allocate_media_port(context: &CallContext, state: Arc<AppState>) -> Result<u32, ServiceError> {
-    let mut media_client = state.grpc.media.clone();
-    let mut media_req = TonicRequest::new(AllocatePortRequest {
-        call_id: context.call_id.clone(),
-    });
-    media_req.metadata_mut().insert("x-trace-id", context.trace_id.parse()?);
-    let rtp_port = media_client.allocate_port(media_req).await?.into_inner().rtp_port;
-    Ok(rtp_port)
+/// Ayrılan RTP portu ve portun sahibi olan media-service örneği.
+#[derive(Debug, Clone)]
+pub struct MediaAllocation {
+    pub rtp_port: u32,
+    pub instance: String,
+}
+
+/// Seçim politikasına göre bir medya örneğinden port ayırır. Port ayırma idempotent olmadığından
+/// (tekrar, ikinci bir port sızdırabilir) aynı örnekte tekrar edilmez; yalnızca örneğe hiç
+/// ulaşılamadıysa sıradaki örnek denenir.
+#[instrument(skip(state))]
+pub async fn allocate_media_port(
+    call_id: &str,
+    trace_id: &str,
+    tenant_id: Option<&str>,
+    deadline: Instant,
+    state: &AppState,
+) -> Result<MediaAllocation, ServiceError> {
+    let trace_id: MetadataValue<_> = trace_id.parse()?;
+    let pool = state.grpc_clients().media;
+    let mut last_error = None;
+    for instance in pool.candidates(state.config.media_selection_policy, tenant_id, state).await {
+        let result = resilience::call(state, Dependency::Media, deadline, false, |timeout| {
+            let mut media_client = instance.client();
+            let mut media_req = TonicRequest::new(AllocatePortRequest {
+                call_id: call_id.to_string(),
+            });
+            media_req.set_timeout(timeout);
+            media_req.metadata_mut().insert("x-trace-id", trace_id.clone());
+            async move { media_client.allocate_port(media_req).await.map(|r| r.into_inner().rtp_port) }
+        })
+        .await;
+        match result {
+            Ok(rtp_port) => return Ok(MediaAllocation { rtp_port, instance: instance.id.clone() }),
+            Err(ServiceError::GrpcStatus(status)) if status.code() == Code::Unavailable => {
+                warn!(instance = %instance.id, "Medya örneğine ulaşılamadı, sıradaki örnek deneniyor.");
+                instance.set_healthy(false);
+                last_error = Some(ServiceError::GrpcStatus(status));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| ServiceError::Generic("Kullanılabilir media-service örneği yok.".to_string())))
+}
+
+/// Portu ayıran örnekte serbest bırakır. Serbest bırakma idempotenttir; geçici hatalarda tekrar edilir
+/// ve port zaten serbest bırakılmışsa (örn. agent-service tarafından) başarılı sayılır.
+#[instrument(skip(state))]
+pub async fn release_media_port(
+    instance: &str,
+    rtp_port: u32,
+    trace_id: &str,
+    state: &AppState,
+) -> Result<(), ServiceError> {
+    let trace_id: MetadataValue<_> = trace_id.parse()?;
+    let deadline = resilience::rpc_deadline(&state.config);
+    let result = resilience::call(state, Dependency::Media, deadline, true, |timeout| {
+        let media_client = state.grpc_clients().media.client_for(instance);
+        let mut media_req = TonicRequest::new(ReleasePortRequest { rtp_port });
+        media_req.set_timeout(timeout);
+        media_req.metadata_mut().insert("x-trace-id", trace_id.clone());
+        async move { media_client?.release_port(media_req).await.map(|r| r.into_inner().success) }
+    })
+    .await;
+    match result {
+        Ok(true) => info!("Medya portu serbest bırakıldı."),
+        Ok(false) => debug!("Medya portu zaten serbest bırakılmış."),
+        Err(ServiceError::GrpcStatus(status)) if status.code() == Code::NotFound => debug!("Medya portu zaten serbest bırakılmış."),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Karşı tarafın RTP adresi diyalog içinde (re-INVITE, UPDATE) değiştiğinde media-service'e bildirir;
+/// portun medyası bundan sonra yeni adrese gönderilir. Hata yalnızca loglanır.
+pub async fn update_rtp_target(call_info: &ActiveCallInfo, rtp_target_addr: &str, state: &AppState) {
+    let Some(instance) = &call_info.media_instance else { return };
+    let Ok(trace_id) = call_info.trace_id.parse::<MetadataValue<_>>() else { return };
+    let deadline = resilience::rpc_deadline(&state.config);
+    let result = resilience::call(state, Dependency::Media, deadline, true, |timeout| {
+        let media_client = state.grpc_clients().media.client_for(instance);
+        let mut media_req = TonicRequest::new(UpdateRtpTargetRequest {
+            server_rtp_port: call_info.rtp_port,
+            rtp_target_addr: rtp_target_addr.to_string(),
+        });
+        media_req.set_timeout(timeout);
+        media_req.metadata_mut().insert("x-trace-id", trace_id.clone());
+        async move { media_client?.update_rtp_target(media_req).await }
+    })
+    .await;
+    match result {
+        Ok(_) => info!(call_id = %call_info.call_id, rtp_target_addr, "Medya hedefi güncellendi."),
+        Err(e) => error!(error = %e, call_id = %call_info.call_id, rtp_target_addr, "Medya hedefi güncellenemedi."),
+    }
+}
+
+/// Sona eren bir çağrının portunu serbest bırakır. Portun tek sahibi, çağrıyı `ActiveCalls`'tan
+/// çıkaran akıştır; bu fonksiyon yalnızca o akış tarafından çağrılmalıdır. Hata yalnızca loglanır.
+pub async fn release_call_media(call_info: &ActiveCallInfo, state: &AppState) {
+    let Some(instance) = &call_info.media_instance else { return };
+    if let Err(e) = release_media_port(instance, call_info.rtp_port, &call_info.trace_id, state).await {
+        error!(error = %e, call_id = %call_info.call_id, rtp_port = call_info.rtp_port, "Medya portu serbest bırakılamadı.");
+    }
 }
 
 #[instrument(skip(call_info, dialplan_res, rabbit_channel))]
-async fn publish_call_event(
+pub async fn publish_call_event(
     event_type: &str,
     call_info: &ActiveCallInfo,
     dialplan_res: Option<&ResolveDialplanResponse>,
@@ -143,6 +324,8 @@ async fn publish_call_event(
         "callId": &call_info.call_id,
         "fromUri": &call_info.from_header,
         "toUri": &call_info.to_header,
+        "authenticatedUser": &call_info.authenticated_user,
+        "trunk": &call_info.trunk,
         "timestamp": chrono::Utc::now().to_rfc3339(),
     });
 
@@ -0,0 +1,21 @@
+// File: src/sip/invite/reinvite.rs
+// Kurulu bir diyalog içindeki INVITE'lar (re-INVITE): oturum yenilemesi (RFC 4028), karşı tarafın
+// beklemeye alması veya medya değişikliği. Diyalog zaten kurulu olduğundan kimlik doğrulama,
+// yinelenen INVITE kilidi ve dialplan adımları uygulanmaz; oturum UPDATE ile aynı şekilde değiştirilir.
+use crate::app_state::AppState;
+use crate::sip::call_context::CallContext;
+use crate::sip::update;
+use crate::sip::utils::extract_tag;
+use std::error::Error;
+use tokio::net::UdpSocket;
+use tracing::instrument;
+
+/// INVITE mevcut bir diyaloğa mı ait? Diyalog içi isteklerin To başlığı etiket taşır (RFC 3261 12.2.2).
+pub fn is_in_dialog(context: &CallContext) -> bool {
+    extract_tag(&context.to_header).is_some()
+}
+
+#[instrument(skip_all, fields(call_id = %context.call_id))]
+pub async fn handle(context: &CallContext, sock: &UdpSocket, state: &AppState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    update::modify_session("INVITE", &context.headers, &context.via_headers, &context.raw_body, context.remote_addr, sock, state).await
+}
@@ -0,0 +1,350 @@
+// File: src/sip/invite/saga.rs
+// Gelen çağrı kurulumunun telafi adımları. Kurulumun her başarılı adımı, geri alınmasını sağlayan
+// bir `Compensation` kaydeder; kurulum herhangi bir noktada başarısız olursa (orkestrasyon hatası,
+// hiçbir hedefin cevap vermemesi, CANCEL, 200 OK'e ACK gelmemesi) kayıtlı adımlar ters sırada
+// uygulanır ve `call.failed` yayınlanır.
+use super::orchestrator::MediaAllocation;
+use crate::app_state::AppState;
+use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
+use crate::sip::call_context::CallContext;
+use crate::state::{ActiveCallInfo, CallDirection};
+use sentiric_contracts::sentiric::dialplan::v1::ResolveDialplanResponse;
+use std::future::Future;
+use tokio::time::Instant;
+use tracing::{error, info, instrument, warn};
+
+/// Kurulumun dış servislerle konuşan adımları. Üretimde `AppState` bunları gRPC ve RabbitMQ
+/// üzerinden uygular; testlerde istenen adımda hata üreten sahte servisler kullanılır.
+pub trait SetupServices: Sync {
+    fn resolve_dialplan(&self, context: &CallContext, deadline: Instant) -> impl Future<Output = Result<ResolveDialplanResponse, ServiceError>> + Send;
+
+    fn allocate_media_port(&self, context: &CallContext, tenant_id: Option<&str>, deadline: Instant) -> impl Future<Output = Result<MediaAllocation, ServiceError>> + Send;
+
+    fn release_media_port(&self, instance: &str, rtp_port: u32, trace_id: &str) -> impl Future<Output = Result<(), ServiceError>> + Send;
+
+    fn publish_call_started(&self, call_info: &ActiveCallInfo, dialplan: &ResolveDialplanResponse) -> impl Future<Output = Result<(), ServiceError>> + Send;
+
+    /// `call.failed` olayını RabbitMQ'ya yayınlar; hata yalnızca loglanır.
+    fn publish_call_failed(&self, event: &CallEvent, reason: &str) -> impl Future<Output = ()> + Send;
+}
+
+/// Tamamlanmış bir kurulum adımını geri alan işlem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compensation {
+    /// `allocate_media_port` ile ayrılan portu sahibi olan örnekte serbest bırakır.
+    ReleasePort { instance: String, rtp_port: u32 },
+    /// Çağrıyı `ActiveCalls`'tan çıkarır.
+    RemoveActiveCall { call_id: String },
+}
+
+pub struct SetupSaga {
+    failed_event: CallEvent,
+    completed: Vec<Compensation>,
+}
+
+impl SetupSaga {
+    /// Henüz hiçbir adımı tamamlanmamış bir kurulum.
+    pub fn new(context: &CallContext) -> Self {
+        let failed_event = CallEvent::new("call.failed", &context.call_id, &context.trace_id, CallDirection::Inbound, &context.from_header);
+        Self { failed_event, completed: Vec::new() }
+    }
+
+    /// Orkestrasyonu tamamlanmış ama arayana henüz kurulmamış bir çağrı (çatallama, 200 OK, ACK bekleniyor).
+    pub fn for_call(call_info: &ActiveCallInfo) -> Self {
+        let mut saga = Self { failed_event: CallEvent::for_call("call.failed", call_info), completed: Vec::new() };
+        if let Some(instance) = &call_info.media_instance {
+            saga.record(Compensation::ReleasePort { instance: instance.clone(), rtp_port: call_info.rtp_port });
+        }
+        saga.record(Compensation::RemoveActiveCall { call_id: call_info.call_id.clone() });
+        saga
+    }
+
+    pub fn record(&mut self, step: Compensation) {
+        self.completed.push(step);
+    }
+
+    pub fn port_allocated(&mut self, media: &MediaAllocation) {
+        self.record(Compensation::ReleasePort { instance: media.instance.clone(), rtp_port: media.rtp_port });
+    }
+
+    /// Dialplan çözüldükten sonra `call.failed` olayı kiracı bilgisini de taşır.
+    pub fn set_tenant(&mut self, tenant_id: Option<String>) {
+        self.failed_event.tenant_id = tenant_id;
+    }
+
+    /// Sırası ile uygulanacak telafi adımları (son tamamlanan adım ilk geri alınır).
+    pub fn steps(&self) -> impl Iterator<Item = &Compensation> {
+        self.completed.iter().rev()
+    }
+
+    /// Tamamlanan adımları ters sırada geri alır ve `call.failed` yayınlar. Çağrı `ActiveCalls`'ta
+    /// yoksa başka bir akış (BYE, CANCEL, sonlandırma) onu zaten kapatmıştır; kaynakların sahibi
+    /// o akış olduğundan telafi burada durur.
+    pub async fn compensate(self, state: &AppState, reason: &str) {
+        self.compensate_with(state, state, reason).await
+    }
+
+    #[instrument(skip_all, fields(call_id = %self.failed_event.call_id, trace_id = %self.failed_event.trace_id, reason))]
+    pub(super) async fn compensate_with(self, state: &AppState, services: &impl SetupServices, reason: &str) {
+        for step in self.steps() {
+            match step {
+                Compensation::RemoveActiveCall { call_id } => {
+                    if state.active_calls.lock().await.remove(call_id).is_none() {
+                        info!("Çağrı başka bir akış tarafından kapatılmış, telafi atlanıyor.");
+                        return;
+                    }
+                }
+                Compensation::ReleasePort { instance, rtp_port } => {
+                    if let Err(e) = services.release_media_port(instance, *rtp_port, &self.failed_event.trace_id).await {
+                        // Port media-service'in kendi zaman aşımına kalır; kurulumun geri kalanı yine de geri alınır.
+                        error!(error = %e, rtp_port, media_instance = %instance, "Medya portu serbest bırakılamadı.");
+                    }
+                }
+            }
+        }
+        warn!(reason, "Çağrı kurulumu geri alındı.");
+        let event = self.failed_event.with_details(serde_json::json!({ "reason": reason }));
+        events::emit(state, event.clone());
+        services.publish_call_failed(&event, reason).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip::invite::orchestrator::{setup_with, CallSetup};
+    use crate::sip::invite::server_transaction::{handle_cancel, PendingInvite};
+    use crate::state::ActiveCalls;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UdpSocket;
+    use tokio::sync::broadcast;
+    use tonic::Status;
+
+    const CALL_ID: &str = "saga-test-call";
+    const RTP_PORT: u32 = 40000;
+    const MEDIA_INSTANCE: &str = "http://media-1:13031";
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum FailAt {
+        Dialplan,
+        Media,
+        Publish,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Effect {
+        Allocated(u32),
+        /// `call_active`: port serbest bırakılırken çağrı hâlâ `ActiveCalls`'ta mıydı?
+        Released { rtp_port: u32, call_active: bool },
+        CallFailed(String),
+    }
+
+    /// İstenen adımda hata üreten ve yaptığı her yan etkiyi sırasıyla kaydeden servisler.
+    struct FakeServices {
+        fail_at: Option<FailAt>,
+        active_calls: ActiveCalls,
+        effects: Mutex<Vec<Effect>>,
+    }
+
+    impl FakeServices {
+        fn new(fail_at: Option<FailAt>, state: &AppState) -> Self {
+            Self { fail_at, active_calls: state.active_calls.clone(), effects: Mutex::new(Vec::new()) }
+        }
+
+        fn record(&self, effect: Effect) {
+            self.effects.lock().unwrap().push(effect);
+        }
+
+        fn take_effects(&self) -> Vec<Effect> {
+            std::mem::take(&mut self.effects.lock().unwrap())
+        }
+    }
+
+    impl SetupServices for FakeServices {
+        async fn resolve_dialplan(&self, _context: &CallContext, _deadline: Instant) -> Result<ResolveDialplanResponse, ServiceError> {
+            if self.fail_at == Some(FailAt::Dialplan) {
+                return Err(Status::unavailable("dialplan-service kapalı").into());
+            }
+            Ok(ResolveDialplanResponse { dialplan_id: "dp-test".to_string(), tenant_id: "tenant-test".to_string(), ..Default::default() })
+        }
+
+        async fn allocate_media_port(&self, _context: &CallContext, _tenant_id: Option<&str>, _deadline: Instant) -> Result<MediaAllocation, ServiceError> {
+            if self.fail_at == Some(FailAt::Media) {
+                return Err(Status::resource_exhausted("boş port yok").into());
+            }
+            self.record(Effect::Allocated(RTP_PORT));
+            Ok(MediaAllocation { rtp_port: RTP_PORT, instance: MEDIA_INSTANCE.to_string() })
+        }
+
+        async fn release_media_port(&self, instance: &str, rtp_port: u32, _trace_id: &str) -> Result<(), ServiceError> {
+            assert_eq!(instance, MEDIA_INSTANCE);
+            let call_active = self.active_calls.lock().await.contains_key(CALL_ID);
+            self.record(Effect::Released { rtp_port, call_active });
+            Ok(())
+        }
+
+        async fn publish_call_started(&self, _call_info: &ActiveCallInfo, _dialplan: &ResolveDialplanResponse) -> Result<(), ServiceError> {
+            if self.fail_at == Some(FailAt::Publish) {
+                return Err(ServiceError::Generic("RabbitMQ kanalı kapalı".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn publish_call_failed(&self, event: &CallEvent, reason: &str) {
+            assert_eq!(event.call_id, CALL_ID);
+            self.record(Effect::CallFailed(reason.to_string()));
+        }
+    }
+
+    fn invite_context() -> CallContext {
+        let request = format!(
+            "INVITE sip:1001@sentiric.test SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5070;branch=z9hG4bK-saga\r\n\
+             From: <sip:905551112233@127.0.0.1>;tag=caller\r\n\
+             To: <sip:1001@sentiric.test>\r\n\
+             Call-ID: {}\r\n\
+             CSeq: 1 INVITE\r\n\
+             Contact: <sip:905551112233@127.0.0.1:5070>\r\n\
+             Content-Length: 0\r\n\r\n",
+            CALL_ID
+        );
+        CallContext::from_request(&request, "127.0.0.1:5070".parse().unwrap(), "trace-saga".to_string()).unwrap()
+    }
+
+    async fn setup(fail_at: Option<FailAt>) -> (Arc<AppState>, FakeServices, Result<CallSetup, ServiceError>) {
+        let state = Arc::new(AppState::for_tests());
+        let services = FakeServices::new(fail_at, &state);
+        let result = setup_with(&invite_context(), state.clone(), &services).await;
+        (state, services, result)
+    }
+
+    /// Olay kanalına yayılan `call.failed` olaylarının nedenleri.
+    fn call_failed_reasons(events: &mut broadcast::Receiver<CallEvent>) -> Vec<String> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| event.event_type == "call.failed")
+            .map(|event| event.details["reason"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn dialplan_failure_emits_call_failed_without_touching_media() {
+        let state = Arc::new(AppState::for_tests());
+        let mut events = state.call_events.subscribe();
+        let services = FakeServices::new(Some(FailAt::Dialplan), &state);
+
+        let result = setup_with(&invite_context(), state.clone(), &services).await;
+
+        assert!(matches!(result, Err(ServiceError::GrpcStatus(_))));
+        assert_eq!(services.take_effects(), vec![Effect::CallFailed("503 Service Unavailable".to_string())]);
+        assert!(state.active_calls.lock().await.is_empty());
+        assert_eq!(call_failed_reasons(&mut events), vec!["503 Service Unavailable"]);
+    }
+
+    #[tokio::test]
+    async fn media_allocation_failure_emits_call_failed_without_release() {
+        let state = Arc::new(AppState::for_tests());
+        let mut events = state.call_events.subscribe();
+        let services = FakeServices::new(Some(FailAt::Media), &state);
+
+        let result = setup_with(&invite_context(), state.clone(), &services).await;
+
+        assert!(matches!(result, Err(ServiceError::GrpcStatus(_))));
+        assert_eq!(services.take_effects(), vec![Effect::CallFailed("503 Service Unavailable".to_string())]);
+        assert!(state.active_calls.lock().await.is_empty());
+        assert_eq!(call_failed_reasons(&mut events), vec!["503 Service Unavailable"]);
+    }
+
+    #[tokio::test]
+    async fn publish_failure_removes_call_then_releases_port_once() {
+        let state = Arc::new(AppState::for_tests());
+        let mut events = state.call_events.subscribe();
+        let services = FakeServices::new(Some(FailAt::Publish), &state);
+
+        let result = setup_with(&invite_context(), state.clone(), &services).await;
+
+        assert!(matches!(result, Err(ServiceError::Generic(_))));
+        // Çağrı son eklenen adım olduğundan port serbest bırakılmadan önce `ActiveCalls`'tan çıkarılır.
+        assert_eq!(
+            services.take_effects(),
+            vec![
+                Effect::Allocated(RTP_PORT),
+                Effect::Released { rtp_port: RTP_PORT, call_active: false },
+                Effect::CallFailed("503 Service Unavailable".to_string()),
+            ]
+        );
+        assert!(state.active_calls.lock().await.is_empty());
+        assert_eq!(call_failed_reasons(&mut events), vec!["503 Service Unavailable"]);
+    }
+
+    #[tokio::test]
+    async fn missing_ack_compensates_answered_call_once() {
+        let (state, services, result) = setup(None).await;
+        let setup = result.expect("kurulum başarılı olmalı");
+        assert_eq!(services.take_effects(), vec![Effect::Allocated(RTP_PORT)]);
+        assert!(state.active_calls.lock().await.contains_key(CALL_ID));
+        let mut events = state.call_events.subscribe();
+
+        // 200 OK'e ACK gelmediğinde INVITE işleyicisinin yaptığı gibi.
+        let saga = SetupSaga::for_call(&setup.call_info);
+        assert_eq!(
+            saga.steps().cloned().collect::<Vec<_>>(),
+            vec![
+                Compensation::RemoveActiveCall { call_id: CALL_ID.to_string() },
+                Compensation::ReleasePort { instance: MEDIA_INSTANCE.to_string(), rtp_port: RTP_PORT },
+            ]
+        );
+        saga.compensate_with(&state, &services, "ack_timeout").await;
+
+        assert_eq!(
+            services.take_effects(),
+            vec![Effect::Released { rtp_port: RTP_PORT, call_active: false }, Effect::CallFailed("ack_timeout".to_string())]
+        );
+        assert!(state.active_calls.lock().await.is_empty());
+        assert_eq!(call_failed_reasons(&mut events), vec!["ack_timeout"]);
+
+        // Çağrıyı kapatan ikinci bir akış portu tekrar serbest bırakmaz ve ikinci bir `call.failed` yaymaz.
+        SetupSaga::for_call(&setup.call_info).compensate_with(&state, &services, "ack_timeout").await;
+        assert!(services.take_effects().is_empty());
+        assert!(call_failed_reasons(&mut events).is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancel_compensates_with_487() {
+        let (state, services, result) = setup(None).await;
+        let setup = result.expect("kurulum başarılı olmalı");
+        services.take_effects();
+        let mut events = state.call_events.subscribe();
+
+        let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let caller: SocketAddr = sock.local_addr().unwrap();
+        let pending = PendingInvite::register(CALL_ID, caller, false, &state.pending_invites);
+        let cancel = format!(
+            "CANCEL sip:1001@sentiric.test SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5070;branch=z9hG4bK-saga\r\n\
+             From: <sip:905551112233@127.0.0.1>;tag=caller\r\n\
+             To: <sip:1001@sentiric.test>\r\n\
+             Call-ID: {}\r\n\
+             CSeq: 1 CANCEL\r\n\
+             Content-Length: 0\r\n\r\n",
+            CALL_ID
+        );
+        handle_cancel(&cancel, sock.clone(), caller, state.clone()).await.unwrap();
+
+        let mut buf = [0u8; 2048];
+        let len = sock.recv(&mut buf).await.unwrap();
+        assert!(String::from_utf8_lossy(&buf[..len]).starts_with("SIP/2.0 200 OK"));
+        let status_line = pending.termination_status().expect("INVITE iptal edilmiş olmalı");
+        assert_eq!(status_line, "487 Request Terminated");
+
+        SetupSaga::for_call(&setup.call_info).compensate_with(&state, &services, status_line).await;
+
+        assert_eq!(
+            services.take_effects(),
+            vec![Effect::Released { rtp_port: RTP_PORT, call_active: false }, Effect::CallFailed(status_line.to_string())]
+        );
+        assert!(state.active_calls.lock().await.is_empty());
+        assert_eq!(call_failed_reasons(&mut events), vec![status_line]);
+    }
+}
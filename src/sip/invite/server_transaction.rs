@@ -0,0 +1,482 @@
+// File: src/sip/invite/server_transaction.rs
+// Nihai yanıtı henüz gönderilmemiş INVITE sunucu transaction'ları (RFC 3261 17.2.1): son geçici
+// yanıtın yinelenen INVITE'lara ve periyodik olarak yeniden gönderilmesi, güvenilir geçici
+// yanıtlar ve PRACK (RFC 3262), ve gelen CANCEL istekleri (RFC 3261 9.2). CANCEL yalnızca bekleyen
+// bir INVITE'ı etkiler; INVITE işleyicisi iptali fark edince 487 gönderir ve kurulumu geri alır.
+use crate::app_state::AppState;
+use crate::sip::responses;
+use crate::sip::transaction::{T1, TRANSACTION_TIMEOUT};
+use crate::sip::utils::parse_sip_headers;
+use rand::Rng;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, info, instrument, warn, Span};
+
+/// Call-ID → bekleyen transaction. `Drop` içinde silinebilmesi için std Mutex kullanılır.
+pub type PendingInvites = Arc<Mutex<HashMap<String, Arc<InviteTransaction>>>>;
+
+pub struct InviteTransaction {
+    remote_addr: SocketAddr,
+    cancel_tx: watch::Sender<bool>,
+    cancelled: watch::Receiver<bool>,
+    /// Gönderilen son geçici (1xx, 100 hariç) yanıt.
+    last_provisional: Mutex<Option<String>>,
+    /// Nihai yanıt gönderildi; çatallama bacakları transaction'ı hâlâ tutuyor olabilir.
+    finished: AtomicBool,
+    /// Arayan `Require: 100rel` gönderdiyse geçici yanıtlar güvenilir gönderilir.
+    reliable: Option<Reliable>,
+}
+
+/// Güvenilir geçici yanıtların durumu (RFC 3262). Aynı anda yalnızca bir yanıt PRACK bekleyebilir.
+struct Reliable {
+    /// Bir sonraki güvenilir yanıtın RSeq değeri.
+    next_rseq: AtomicU32,
+    /// PRACK bekleyen son güvenilir yanıtın RSeq değeri.
+    unacked: Mutex<Option<u32>>,
+    /// PRACK ile onaylanan son RSeq.
+    acked: watch::Sender<u32>,
+    /// SDP taşıyan (teklife cevap veren) son güvenilir yanıtın RSeq değeri; 2xx bunun PRACK'ini bekler.
+    answer_rseq: Mutex<Option<u32>>,
+    /// 64*T1 içinde PRACK gelmedi; INVITE 5xx ile reddedilir.
+    timed_out: AtomicBool,
+}
+
+impl InviteTransaction {
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Transaction nihai yanıt beklenmeden sonlandırıldıysa arayana gönderilecek durum satırı:
+    /// CANCEL için 487, güvenilir geçici yanıt PRACK'lenmediyse 504 (RFC 3262 3).
+    pub fn termination_status(&self) -> Option<&'static str> {
+        if !self.is_cancelled() {
+            return None;
+        }
+        let prack_timed_out = self.reliable.as_ref().is_some_and(|r| r.timed_out.load(Ordering::Relaxed));
+        Some(if prack_timed_out { "504 Server Time-out" } else { "487 Request Terminated" })
+    }
+
+    /// Arayan CANCEL ettiğinde `true` olan kanal.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.cancelled.clone()
+    }
+
+    /// Geçici yanıtı gönderir ve yinelenen INVITE'larda ve periyodik yenilemede tekrar gönderilmek üzere saklar.
+    /// Güvenilir modda yanıt `RSeq` ile gönderilir ve PRACK gelene kadar yeniden gönderilir; önceki
+    /// güvenilir yanıt henüz PRACK'lenmediyse önce onu bekler.
+    pub async fn send_provisional(self: &Arc<Self>, response: String, sock: &Arc<UdpSocket>) -> std::io::Result<()> {
+        let response = match &self.reliable {
+            Some(reliable) => {
+                let previous = *reliable.unacked.lock().unwrap();
+                if let Some(previous) = previous {
+                    let (mut acked, mut cancelled) = (reliable.acked.subscribe(), self.subscribe());
+                    tokio::select! {
+                        _ = acked.wait_for(|acked| *acked >= previous) => {}
+                        _ = cancelled.wait_for(|cancelled| *cancelled) => return Ok(()),
+                    }
+                }
+                let rseq = reliable.next_rseq.fetch_add(1, Ordering::SeqCst);
+                if has_body(&response) {
+                    *reliable.answer_rseq.lock().unwrap() = Some(rseq);
+                }
+                let response = insert_headers(&response, &["Require: 100rel".to_string(), format!("RSeq: {}", rseq)]);
+                *reliable.unacked.lock().unwrap() = Some(rseq);
+                self.spawn_reliable_retransmit(rseq, response.clone(), sock.clone());
+                response
+            }
+            None => response,
+        };
+        sock.send_to(response.as_bytes(), self.remote_addr).await?;
+        *self.last_provisional.lock().unwrap() = Some(response);
+        Ok(())
+    }
+
+    /// 2xx gönderilmeden önce, SDP taşıyan güvenilir geçici yanıtın PRACK'ini bekler (RFC 3262 3); aksi
+    /// halde arayanın geç gelen PRACK'i sonlanmış transaction ile eşleşmez. Bekleme 64*T1 ile sınırlıdır.
+    /// PRACK gelmezse veya arayan CANCEL ederse `false` döner ve INVITE reddedilmelidir.
+    pub async fn await_answer_prack(&self) -> bool {
+        let Some(reliable) = &self.reliable else { return true };
+        let Some(rseq) = *reliable.answer_rseq.lock().unwrap() else { return true };
+        let (mut acked, mut cancelled) = (reliable.acked.subscribe(), self.subscribe());
+        debug!(rseq, "2xx'ten önce SDP taşıyan güvenilir geçici yanıtın PRACK'i bekleniyor.");
+        tokio::select! {
+            _ = acked.wait_for(|acked| *acked >= rseq) => !self.is_cancelled(),
+            _ = cancelled.wait_for(|cancelled| *cancelled) => false,
+            _ = tokio::time::sleep(TRANSACTION_TIMEOUT) => {
+                warn!(rseq, "SDP taşıyan güvenilir geçici yanıt için PRACK alınmadı.");
+                false
+            }
+        }
+    }
+
+    /// Güvenilir yanıt PRACK gelene kadar T1'den başlayıp ikiye katlanan aralıklarla yeniden gönderilir.
+    /// 64*T1 içinde PRACK gelmezse transaction sonlandırılır (RFC 3262 3).
+    fn spawn_reliable_retransmit(self: &Arc<Self>, rseq: u32, response: String, sock: Arc<UdpSocket>) {
+        let transaction = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let deadline = Instant::now() + TRANSACTION_TIMEOUT;
+            let mut interval = T1;
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(transaction) = transaction.upgrade() else { return };
+                let Some(reliable) = &transaction.reliable else { return };
+                if transaction.finished.load(Ordering::Relaxed) || *reliable.acked.borrow() >= rseq {
+                    return;
+                }
+                if Instant::now() >= deadline {
+                    warn!(rseq, "Güvenilir geçici yanıt için PRACK alınmadı, INVITE reddediliyor.");
+                    reliable.timed_out.store(true, Ordering::Relaxed);
+                    transaction.cancel_tx.send_replace(true);
+                    return;
+                }
+                if let Err(e) = sock.send_to(response.as_bytes(), transaction.remote_addr).await {
+                    warn!(error = %e, "Güvenilir geçici yanıt yeniden gönderilemedi.");
+                }
+                interval *= 2;
+            }
+        });
+    }
+
+    /// PRACK'in `RAck`'indeki RSeq PRACK bekleyen yanıtla eşleşiyorsa onu onaylar. Yinelenen PRACK'ler de eşleşir.
+    fn acknowledge(&self, rseq: u32) -> bool {
+        let Some(reliable) = &self.reliable else { return false };
+        let mut unacked = reliable.unacked.lock().unwrap();
+        if *unacked == Some(rseq) {
+            *unacked = None;
+            reliable.acked.send_replace(rseq);
+            return true;
+        }
+        rseq <= *reliable.acked.borrow()
+    }
+
+    async fn retransmit_provisional(&self, sock: &UdpSocket) {
+        if self.finished.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(response) = self.last_provisional.lock().unwrap().clone() else { return };
+        if let Err(e) = sock.send_to(response.as_bytes(), self.remote_addr).await {
+            warn!(error = %e, "Geçici yanıt yeniden gönderilemedi.");
+        }
+    }
+}
+
+/// Bekleyen transaction'ın sahibi. Düşürüldüğünde (nihai yanıt gönderilirken) kaydı silinir;
+/// sonrasında gelen CANCEL'ın etkisi olmaz ve periyodik yenileme durur.
+pub struct PendingInvite {
+    call_id: String,
+    invites: PendingInvites,
+    transaction: Arc<InviteTransaction>,
+}
+
+impl PendingInvite {
+    pub fn register(call_id: &str, remote_addr: SocketAddr, reliable: bool, invites: &PendingInvites) -> Self {
+        let (cancel_tx, cancelled) = watch::channel(false);
+        let reliable = reliable.then(|| Reliable {
+            // RFC 3262 3: ilk RSeq 1 ile 2^31-1 arasında rastgele seçilir.
+            next_rseq: AtomicU32::new(rand::thread_rng().gen_range(1..1 << 31)),
+            unacked: Mutex::new(None),
+            acked: watch::channel(0).0,
+            answer_rseq: Mutex::new(None),
+            timed_out: AtomicBool::new(false),
+        });
+        let transaction = Arc::new(InviteTransaction {
+            remote_addr,
+            cancel_tx,
+            cancelled,
+            last_provisional: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            reliable,
+        });
+        invites.lock().unwrap().insert(call_id.to_string(), transaction.clone());
+        Self { call_id: call_id.to_string(), invites: invites.clone(), transaction }
+    }
+
+    pub fn transaction(&self) -> Arc<InviteTransaction> {
+        self.transaction.clone()
+    }
+
+    pub async fn send_provisional(&self, response: String, sock: &Arc<UdpSocket>) -> std::io::Result<()> {
+        self.transaction.send_provisional(response, sock).await
+    }
+
+    /// Uzun süren kurulumlarda ara sunucuların INVITE'ı zaman aşımına uğratmaması için son geçici
+    /// yanıt `interval` aralıklarla yeniden gönderilir (RFC 3261 13.3.1.1).
+    pub fn spawn_refresh(&self, interval: Duration, sock: Arc<UdpSocket>) {
+        let transaction: Weak<InviteTransaction> = Arc::downgrade(&self.transaction);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(transaction) = transaction.upgrade().filter(|t| !t.finished.load(Ordering::Relaxed)) else { return };
+                debug!("Geçici yanıt yenileniyor.");
+                transaction.retransmit_provisional(&sock).await;
+            }
+        });
+    }
+}
+
+impl Deref for PendingInvite {
+    type Target = InviteTransaction;
+
+    fn deref(&self) -> &InviteTransaction {
+        &self.transaction
+    }
+}
+
+impl Drop for PendingInvite {
+    fn drop(&mut self) {
+        self.transaction.finished.store(true, Ordering::Relaxed);
+        self.invites.lock().unwrap().remove(&self.call_id);
+    }
+}
+
+/// Yinelenen bir INVITE için bekleyen transaction'ın son geçici yanıtını yeniden gönderir (RFC 3261 17.2.1).
+pub async fn absorb_retransmission(call_id: &str, sock: &UdpSocket, state: &AppState) {
+    let transaction = state.pending_invites.lock().unwrap().get(call_id).cloned();
+    if let Some(transaction) = transaction {
+        transaction.retransmit_provisional(sock).await;
+    }
+}
+
+fn has_body(response: &str) -> bool {
+    response.split_once("\r\n\r\n").is_some_and(|(_, body)| !body.is_empty())
+}
+
+/// Yanıtın `Content-Length` satırından önce başlık ekler.
+fn insert_headers(response: &str, headers: &[String]) -> String {
+    let at = response.find("Content-Length:").unwrap_or(response.len());
+    let inserted: String = headers.iter().map(|h| format!("{}\r\n", h)).collect();
+    format!("{}{}{}", &response[..at], inserted, &response[at..])
+}
+
+/// Güvenilir bir geçici yanıtın PRACK'i (RFC 3262 4). PRACK yeni bir transaction'dır ve kendi 200 OK'ini alır.
+#[instrument(skip_all, fields(remote_addr = %addr, call_id))]
+pub async fn handle_prack(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (headers, via_headers) = parse_sip_headers(request_str).ok_or("Geçersiz başlıklar")?;
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    Span::current().record("call_id", &call_id as &str);
+
+    // RAck: <RSeq> <CSeq numarası> <metod>
+    let rseq = headers.get("rack").and_then(|v| v.split_whitespace().next()).and_then(|r| r.parse::<u32>().ok());
+    let transaction = state.pending_invites.lock().unwrap().get(&call_id).cloned();
+    let acknowledged = transaction.zip(rseq).is_some_and(|(transaction, rseq)| transaction.acknowledge(rseq));
+    let status_line = if acknowledged {
+        debug!(rseq, "Güvenilir geçici yanıt PRACK ile onaylandı.");
+        "200 OK"
+    } else {
+        warn!(rseq, "PRACK hiçbir güvenilir geçici yanıtla eşleşmedi.");
+        "481 Call/Transaction Does Not Exist"
+    };
+    let response = responses::create_response_from_parts(status_line, &headers, &via_headers, None, &state.config, addr);
+    sock.send_to(response.as_bytes(), addr).await?;
+    Ok(())
+}
+
+#[instrument(skip_all, fields(remote_addr = %addr, call_id))]
+pub async fn handle_cancel(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (headers, via_headers) = parse_sip_headers(request_str).ok_or("Geçersiz başlıklar")?;
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    Span::current().record("call_id", &call_id as &str);
+
+    let pending = state.pending_invites.lock().unwrap().get(&call_id).map(|t| t.cancel_tx.send_replace(true));
+    let status_line = if pending.is_some() {
+        info!("CANCEL alındı, bekleyen INVITE iptal ediliyor.");
+        "200 OK"
+    } else if state.active_calls.lock().await.contains_key(&call_id) {
+        // INVITE zaten nihai yanıtını aldı; CANCEL'ın etkisi yoktur ama transaction eşleşir.
+        info!("CANCEL, nihai yanıtı gönderilmiş bir INVITE için alındı; etkisi yok.");
+        "200 OK"
+    } else {
+        warn!("CANCEL alınan INVITE bulunamadı.");
+        "481 Call/Transaction Does Not Exist"
+    };
+    let response = responses::create_response_from_parts(status_line, &headers, &via_headers, None, &state.config, addr);
+    sock.send_to(response.as_bytes(), addr).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip::transaction::SipResponse;
+
+    const CALL_ID: &str = "prack-test-call";
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\nm=audio 40000 RTP/AVP 0\r\n";
+
+    struct Dialog {
+        state: Arc<AppState>,
+        /// Servisin soketi.
+        sock: Arc<UdpSocket>,
+        /// Arayanın soketi; servisin yanıtları buraya gelir.
+        caller: UdpSocket,
+        caller_addr: SocketAddr,
+    }
+
+    impl Dialog {
+        async fn new() -> Self {
+            let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+            let caller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let caller_addr = caller.local_addr().unwrap();
+            Self { state: Arc::new(AppState::for_tests()), sock, caller, caller_addr }
+        }
+
+        fn register(&self, reliable: bool) -> PendingInvite {
+            PendingInvite::register(CALL_ID, self.caller_addr, reliable, &self.state.pending_invites)
+        }
+
+        fn provisional(&self, status_line: &str, body: Option<&str>) -> String {
+            let request = format!(
+                "INVITE sip:1001@sentiric.test SIP/2.0\r\n\
+                 Via: SIP/2.0/UDP {};branch=z9hG4bK-invite\r\n\
+                 From: <sip:2002@sentiric.test>;tag=caller\r\n\
+                 To: <sip:1001@sentiric.test>\r\n\
+                 Call-ID: {}\r\n\
+                 CSeq: 1 INVITE\r\n\
+                 Require: 100rel\r\n\
+                 Content-Length: 0",
+                self.caller_addr, CALL_ID
+            );
+            let (headers, via_headers) = parse_sip_headers(&request).unwrap();
+            responses::create_response_from_parts(status_line, &headers, &via_headers, body, &self.state.config, self.caller_addr)
+        }
+
+        async fn prack(&self, rack: &str) {
+            let request = format!(
+                "PRACK sip:1001@sentiric.test SIP/2.0\r\n\
+                 Via: SIP/2.0/UDP {};branch=z9hG4bK-prack-{}\r\n\
+                 From: <sip:2002@sentiric.test>;tag=caller\r\n\
+                 To: <sip:1001@sentiric.test>;tag=local\r\n\
+                 Call-ID: {}\r\n\
+                 CSeq: 2 PRACK\r\n\
+                 RAck: {}\r\n\
+                 Content-Length: 0\r\n\r\n",
+                self.caller_addr, rack.replace(' ', "-"), CALL_ID, rack
+            );
+            handle_prack(&request, self.sock.clone(), self.caller_addr, self.state.clone()).await.unwrap();
+        }
+
+        async fn recv(&self) -> SipResponse {
+            let mut buf = [0u8; 4096];
+            let len = tokio::time::timeout(Duration::from_secs(1), self.caller.recv(&mut buf))
+                .await
+                .expect("yanıt bekleniyordu")
+                .unwrap();
+            SipResponse::parse(&String::from_utf8_lossy(&buf[..len])).unwrap()
+        }
+    }
+
+    fn rseq(response: &SipResponse) -> u32 {
+        response.headers.get("rseq").and_then(|v| v.trim().parse().ok()).expect("RSeq başlığı yok")
+    }
+
+    #[tokio::test]
+    async fn reliable_provisional_is_acknowledged_only_by_matching_rack() {
+        let dialog = Dialog::new().await;
+        let pending = dialog.register(true);
+
+        pending.send_provisional(dialog.provisional("180 Ringing", None), &dialog.sock).await.unwrap();
+        let ringing = dialog.recv().await;
+        assert_eq!(ringing.headers.get("require").map(String::as_str), Some("100rel"));
+        let rseq = rseq(&ringing);
+
+        dialog.prack(&format!("{} 1 INVITE", rseq + 1)).await;
+        assert_eq!(dialog.recv().await.status_code, 481);
+
+        dialog.prack(&format!("{} 1 INVITE", rseq)).await;
+        assert_eq!(dialog.recv().await.status_code, 200);
+
+        // Yinelenen PRACK de aynı yanıtla eşleşir.
+        dialog.prack(&format!("{} 1 INVITE", rseq)).await;
+        assert_eq!(dialog.recv().await.status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn next_reliable_provisional_waits_for_prack_and_increments_rseq() {
+        let dialog = Dialog::new().await;
+        let pending = dialog.register(true);
+
+        pending.send_provisional(dialog.provisional("180 Ringing", None), &dialog.sock).await.unwrap();
+        let first = rseq(&dialog.recv().await);
+
+        let transaction = pending.transaction();
+        let sock = dialog.sock.clone();
+        let progress = dialog.provisional("183 Session Progress", Some(SDP));
+        let sending = tokio::spawn(async move { transaction.send_provisional(progress, &sock).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!sending.is_finished(), "önceki yanıt PRACK'lenmeden yeni güvenilir yanıt gönderilmemeli");
+
+        dialog.prack(&format!("{} 1 INVITE", first)).await;
+        sending.await.unwrap().unwrap();
+        let mut responses = [dialog.recv().await, dialog.recv().await];
+        responses.sort_by_key(|r| r.status_code);
+        assert_eq!(responses[0].status_code, 183);
+        assert_eq!(rseq(&responses[0]), first + 1);
+        assert_eq!(responses[1].status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn answer_waits_for_prack_of_reliable_provisional_with_sdp() {
+        let dialog = Dialog::new().await;
+        let pending = dialog.register(true);
+
+        pending.send_provisional(dialog.provisional("183 Session Progress", Some(SDP)), &dialog.sock).await.unwrap();
+        let rseq = rseq(&dialog.recv().await);
+
+        let transaction = pending.transaction();
+        let waiting = tokio::spawn(async move { transaction.await_answer_prack().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished(), "2xx, SDP taşıyan 183'ün PRACK'ini beklemeli");
+
+        dialog.prack(&format!("{} 1 INVITE", rseq)).await;
+        assert!(waiting.await.unwrap());
+        assert_eq!(dialog.recv().await.status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn answer_does_not_wait_without_an_sdp_bearing_reliable_provisional() {
+        let dialog = Dialog::new().await;
+
+        let reliable = dialog.register(true);
+        reliable.send_provisional(dialog.provisional("180 Ringing", None), &dialog.sock).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(50), reliable.await_answer_prack()).await.unwrap());
+        drop(reliable);
+
+        let unreliable = dialog.register(false);
+        unreliable.send_provisional(dialog.provisional("183 Session Progress", Some(SDP)), &dialog.sock).await.unwrap();
+        dialog.recv().await;
+        let progress = dialog.recv().await;
+        assert!(!progress.headers.contains_key("rseq"));
+        assert!(tokio::time::timeout(Duration::from_millis(50), unreliable.await_answer_prack()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn cancel_while_waiting_for_prack_rejects_with_487() {
+        let dialog = Dialog::new().await;
+        let pending = dialog.register(true);
+        pending.send_provisional(dialog.provisional("183 Session Progress", Some(SDP)), &dialog.sock).await.unwrap();
+
+        let transaction = pending.transaction();
+        let waiting = tokio::spawn(async move { transaction.await_answer_prack().await });
+        pending.cancel_tx.send_replace(true);
+
+        assert!(!waiting.await.unwrap());
+        assert_eq!(pending.termination_status(), Some("487 Request Terminated"));
+    }
+}
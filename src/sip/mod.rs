@@ -1,10 +1,20 @@
 // sentiric-sip-signaling-service/src/sip/mod.rs
 
 pub mod ack;
+pub mod auth;
 pub mod bye;
 pub mod call_context;
+pub mod call_control;
+pub mod extensions;
 pub mod handler;
 pub mod invite;
+pub mod options;
+pub mod originate;
+pub mod refer;
 pub mod register;
+pub mod requests;
 pub mod responses;
+pub mod session_timer;
+pub mod transaction;
+pub mod update;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,143 @@
+// sentiric-sip-signaling-service/src/sip/options.rs
+// OPTIONS (RFC 3261 11): gateway ve operatörlerin canlılık yoklamalarına yetenek bildirimiyle yanıt.
+// Servis yeni çağrı kabul edemiyorsa (kapanıyor veya bir bağımlılığın devresi açık) 503 döner;
+// böylece yoklama tabanlı sağlık kontrolleri trafiği başka bir düğüme yönlendirebilir.
+
+use crate::app_state::AppState;
+use crate::sip::extensions;
+use crate::sip::responses;
+use crate::sip::session_timer;
+use crate::sip::utils::{extract_tag, parse_sip_headers};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tracing::{debug, instrument, warn, Span};
+
+/// Servis şu anda yeni çağrı kabul edemiyorsa nedeni.
+pub fn unavailability(state: &AppState) -> Option<String> {
+    if state.is_draining() {
+        return Some("draining".to_string());
+    }
+    let open = state.circuit_breakers.open_dependencies();
+    if !open.is_empty() {
+        let names: Vec<&str> = open.iter().map(|d| d.as_str()).collect();
+        return Some(format!("circuit_open: {}", names.join(", ")));
+    }
+    if !state.grpc_clients().media.instances().iter().any(|instance| instance.is_healthy()) {
+        return Some("no_healthy_media_instance".to_string());
+    }
+    None
+}
+
+#[instrument(skip_all, fields(remote_addr = %addr, call_id))]
+pub async fn handle(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (headers, via_headers) = parse_sip_headers(request_str).ok_or("Geçersiz başlıklar")?;
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    Span::current().record("call_id", &call_id as &str);
+
+    // Diyalog içi OPTIONS, karşı tarafın diyaloğu hâlâ tanıdığını gösterir.
+    let in_dialog = headers.get("to").and_then(|to| extract_tag(to)).is_some();
+    if in_dialog {
+        session_timer::touch(&call_id, &state).await;
+    }
+
+    // Yeni çağrı kabul durumu yalnızca diyalog dışı yoklamalara bildirilir: diyalog içi bir yoklamaya
+    // verilen 503, karşı tarafın kapanışta beklenen çağrıyı sonlandırmasına yol açar.
+    let unavailable = if in_dialog { None } else { unavailability(&state) };
+    let response = match unavailable {
+        Some(reason) => {
+            warn!(reason = %reason, "OPTIONS yoklamasına 503 ile yanıt veriliyor.");
+            responses::create_response_with_headers(
+                "503 Service Unavailable",
+                &headers,
+                &via_headers,
+                &[format!("Warning: 399 sentiric \"{}\"", reason)],
+                None,
+                &state.config,
+                addr,
+            )
+        }
+        None => {
+            debug!("OPTIONS yoklamasına 200 OK ile yanıt veriliyor.");
+            responses::create_response_with_headers(
+                "200 OK",
+                &headers,
+                &via_headers,
+                &[format!("Accept: {}", extensions::ACCEPTED_CONTENT_TYPES.join(", "))],
+                None,
+                &state.config,
+                addr,
+            )
+        }
+    };
+    sock.send_to(response.as_bytes(), addr).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::media_pool::MediaPool;
+    use std::sync::atomic::Ordering;
+
+    fn request(to_tag: Option<&str>) -> String {
+        let to_tag = to_tag.map(|t| format!(";tag={}", t)).unwrap_or_default();
+        format!(
+            "OPTIONS sip:sentiric.test SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5070;branch=z9hG4bK-options\r\n\
+             From: <sip:gateway@sentiric.test>;tag=probe\r\n\
+             To: <sip:sentiric.test>{}\r\n\
+             Call-ID: options-test\r\n\
+             CSeq: 1 OPTIONS\r\n\
+             Content-Length: 0\r\n\r\n",
+            to_tag
+        )
+    }
+
+    /// Sağlıklı bir media örneği olan durum; `draining` ile kapanış başlatılabilir.
+    fn state(draining: bool) -> Arc<AppState> {
+        let state = AppState::for_tests();
+        let mut clients = state.grpc_clients();
+        clients.media = Arc::new(MediaPool::with_instance("http://127.0.0.1:1"));
+        state.replace_grpc_clients(clients);
+        state.draining.store(draining, Ordering::Relaxed);
+        Arc::new(state)
+    }
+
+    async fn probe(request: &str, state: Arc<AppState>) -> String {
+        let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let prober = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        handle(request, sock, prober.local_addr().unwrap(), state).await.unwrap();
+
+        let mut buf = [0u8; 4096];
+        let len = prober.recv(&mut buf).await.unwrap();
+        String::from_utf8_lossy(&buf[..len]).to_string()
+    }
+
+    #[tokio::test]
+    async fn available_service_answers_with_accept() {
+        let response = probe(&request(None), state(false)).await;
+        assert!(response.starts_with("SIP/2.0 200 OK\r\n"));
+        assert!(response.contains(&format!("\r\nAccept: {}\r\n", extensions::ACCEPTED_CONTENT_TYPES.join(", "))));
+    }
+
+    #[tokio::test]
+    async fn draining_service_answers_out_of_dialog_probe_with_503_and_warning() {
+        let response = probe(&request(None), state(true)).await;
+        assert!(response.starts_with("SIP/2.0 503 Service Unavailable\r\n"));
+        assert!(response.contains("\r\nWarning: 399 sentiric \"draining\"\r\n"));
+    }
+
+    #[tokio::test]
+    async fn in_dialog_probe_is_answered_while_draining() {
+        let response = probe(&request(Some("local")), state(true)).await;
+        assert!(response.starts_with("SIP/2.0 200 OK\r\n"));
+        assert!(!response.contains("Warning:"));
+    }
+}
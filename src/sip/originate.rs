@@ -0,0 +1,468 @@
+// sentiric-sip-signaling-service/src/sip/originate.rs
+// Platformun dışarıya (bir trunk'a veya kayıtlı bir kullanıcıya) çağrı başlatması (click-to-call).
+// Kurulan çağrı, gelen çağrılarla aynı şekilde `ActiveCalls`'a eklenir ve aynı olaylar yayınlanır.
+
+use crate::app_state::AppState;
+use crate::config::TrunkConfig;
+use crate::error::ServiceError;
+use crate::events::{self, CallEvent};
+use crate::grpc::resilience;
+use crate::redis;
+use crate::sip::ack::publish_call_answered_event;
+use crate::sip::auth;
+use crate::sip::invite::orchestrator::{allocate_media_port, publish_call_event, release_media_port, MediaAllocation};
+use crate::sip::requests::{build_ack_for_2xx, build_cancel, generate_branch, generate_tag, OutgoingRequest};
+use crate::sip::responses;
+use crate::sip::session_timer::{self, SessionTimer};
+use crate::sip::transaction::{ClientTransaction, SipResponse, TRANSACTION_TIMEOUT};
+use crate::sip::utils::{extract_host_port_from_uri, extract_raw_user_from_uri};
+use crate::state::{ActiveCallInfo, CallDirection};
+use crate::trunk::{self, routing};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, timeout, Instant};
+use tracing::{error, info, instrument, warn};
+
+pub const DEFAULT_RING_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct OriginateParams {
+    /// Dahili numara, harici numara veya tam SIP URI'si.
+    pub destination: String,
+    /// Boşsa trunk kullanıcı adı veya "sentiric" kullanılır.
+    pub caller_id: Option<String>,
+    /// Belirtilirse çağrı doğrudan bu trunk üzerinden gönderilir.
+    pub trunk_name: Option<String>,
+    pub ring_timeout: Duration,
+    pub trace_id: String,
+    /// INVITE'a eklenecek ek başlıklar (örn. aktarımda `Referred-By`, `Replaces`).
+    pub extra_headers: Vec<String>,
+}
+
+pub enum OriginateOutcome {
+    /// Cevaplanan çağrı; trunk üzerinden gönderildiyse `trunk` alanı doludur.
+    Answered(Box<ActiveCallInfo>),
+    /// Karşı tarafın (veya zaman aşımının) nihai yanıtı.
+    Failed(u16, String),
+}
+
+/// INVITE'ın gönderileceği yer ve gerekirse kimlik bilgileri.
+struct Route {
+    request_uri: String,
+    to_uri: String,
+    from_domain: String,
+    next_hop: SocketAddr,
+    credentials: Option<(String, String)>,
+    trunk: Option<TrunkConfig>,
+}
+
+/// Tek bir rotaya yapılan denemenin sonucu.
+enum AttemptOutcome {
+    Answered(Box<ActiveCallInfo>),
+    /// `failover`: Bir sonraki trunk denenmeli mi (5xx veya yanıt alınamadı).
+    Failed { status_code: u16, status_line: String, failover: bool },
+}
+
+#[instrument(skip_all, fields(trace_id = %params.trace_id, destination = %params.destination, call_id))]
+pub async fn originate(
+    params: OriginateParams,
+    sock: Arc<UdpSocket>,
+    state: Arc<AppState>,
+) -> Result<OriginateOutcome, ServiceError> {
+    let routes = resolve_routes(&params, &state).await?;
+    if routes.is_empty() {
+        warn!("Hedef için kayıtlı bir cihaz veya kullanılabilir bir trunk bulunamadı.");
+        return Ok(OriginateOutcome::Failed(404, "404 Not Found".to_string()));
+    }
+
+    let mut call_id = uuid::Uuid::new_v4().to_string();
+    tracing::Span::current().record("call_id", &call_id as &str);
+
+    let media = allocate_media_port(&call_id, &params.trace_id, None, resilience::rpc_deadline(&state.config), &state).await?;
+    info!(rtp_port = media.rtp_port, media_instance = %media.instance, "Giden çağrı için medya portu ayrıldı.");
+
+    let mut last_failure: Result<(u16, String), ServiceError> = Ok((503, "503 Service Unavailable".to_string()));
+    let mut attempted = false;
+    for route in &routes {
+        // Kanal, yalnızca çaldırma süresince ayrılır; cevaplanan çağrı `ActiveCalls` üzerinden sayılır.
+        let _reservation = match &route.trunk {
+            Some(trunk) => match routing::reserve_channel(&state, trunk).await {
+                Some(reservation) => Some(reservation),
+                None => {
+                    warn!(trunk = %trunk.name, "Trunk'ın kanal sınırı dolu, sonraki trunk deneniyor.");
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        // Her trunk denemesi ayrı bir diyalogdur ve kendi Call-ID'sini taşır.
+        if attempted {
+            call_id = uuid::Uuid::new_v4().to_string();
+            tracing::Span::current().record("call_id", &call_id as &str);
+        }
+        attempted = true;
+
+        // Kanal, deneme bitene kadar tutulur. Denemenin her aşaması kendi zaman aşımıyla sınırlıdır; bu üst
+        // sınır, beklenmeyen bir bekleme durumunda bile kanalın ve medya portunun kalıcı olarak tutulmamasını sağlar.
+        let outcome = match timeout(attempt_limit(params.ring_timeout), attempt(route, &call_id, &media, &params, &sock, &state)).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                error!("Rota denemesi üst süre sınırını aştı, deneme sonlandırılıyor.");
+                Ok(AttemptOutcome::Failed { status_code: 408, status_line: "408 Request Timeout".to_string(), failover: false })
+            }
+        };
+        match outcome {
+            Ok(AttemptOutcome::Answered(call_info)) => {
+                register_answered_call(&call_info, &state).await;
+                return Ok(OriginateOutcome::Answered(call_info));
+            }
+            Ok(AttemptOutcome::Failed { status_code, status_line, failover }) => {
+                last_failure = Ok((status_code, status_line));
+                if !failover {
+                    break;
+                }
+                if let Some(trunk) = &route.trunk {
+                    warn!(trunk = %trunk.name, status = status_code, "Trunk çağrıyı iletemedi, sonraki trunk deneniyor.");
+                }
+            }
+            // İsteğin gönderilememesi (ağ hatası, ACK gönderilemedi...) da bir sonraki rotayı dener.
+            Err(e) => {
+                warn!(error = %e, trunk = route.trunk.as_ref().map(|t| t.name.as_str()), "Rotaya INVITE gönderilemedi, sonraki rota deneniyor.");
+                last_failure = Err(e);
+            }
+        }
+    }
+
+    release_allocation(&media, &params.trace_id, &state).await;
+    let (status_code, status_line) = last_failure?;
+    let caller_uri = params.caller_id.clone().unwrap_or_default();
+    let failed = CallEvent::new("call.failed", &call_id, &params.trace_id, CallDirection::Outbound, &caller_uri);
+    events::emit(&state, failed.with_details(serde_json::json!({ "statusCode": status_code, "reason": &status_line })));
+    Ok(OriginateOutcome::Failed(status_code, status_line))
+}
+
+/// Tek bir rota denemesinin sürebileceği en uzun süre: kimlik doğrulama sonrası yeniden gönderim dahil iki
+/// INVITE transaction'ı (Timer B), çalma süresi ve CANCEL sonrası nihai yanıt beklemesi.
+fn attempt_limit(ring_timeout: Duration) -> Duration {
+    ring_timeout + TRANSACTION_TIMEOUT * 3
+}
+
+/// Hiçbir rota cevaplamadığında, diyalog kurulmadığı için port doğrudan serbest bırakılır.
+async fn release_allocation(media: &MediaAllocation, trace_id: &str, state: &AppState) {
+    if let Err(e) = release_media_port(&media.instance, media.rtp_port, trace_id, state).await {
+        error!(error = %e, rtp_port = media.rtp_port, "Giden çağrının medya portu serbest bırakılamadı.");
+    }
+}
+
+/// Bir rotaya INVITE gönderir; gerekirse 401/407 challenge'ını bir kez yanıtlar.
+async fn attempt(
+    route: &Route,
+    call_id: &str,
+    media: &MediaAllocation,
+    params: &OriginateParams,
+    sock: &Arc<UdpSocket>,
+    state: &Arc<AppState>,
+) -> Result<AttemptOutcome, ServiceError> {
+    let caller_user = params
+        .caller_id
+        .clone()
+        .filter(|c| !c.is_empty())
+        .or_else(|| route.credentials.as_ref().map(|(user, _)| user.clone()))
+        .unwrap_or_else(|| "sentiric".to_string());
+    let local_uri = format!("<sip:{}@{}>", caller_user, route.from_domain);
+    let local_tag = generate_tag();
+
+    let mut invite = OutgoingRequest {
+        method: "INVITE".to_string(),
+        request_uri: route.request_uri.clone(),
+        branch: generate_branch(),
+        from: format!("{};tag={}", local_uri, local_tag),
+        to: format!("<{}>", route.to_uri),
+        call_id: call_id.to_string(),
+        cseq: 1,
+        route: Vec::new(),
+        extra_headers: [params.extra_headers.clone(), session_timer::offer_headers(&state.config)].concat(),
+        body: Some(responses::build_sdp(media.rtp_port, &state.config)),
+    };
+
+    let ring_deadline = Instant::now() + params.ring_timeout;
+    let mut challenged = false;
+
+    loop {
+        let transaction = ClientTransaction::start(
+            invite.clone(),
+            route.next_hop,
+            sock.clone(),
+            state.config.clone(),
+            state.client_transactions.clone(),
+        )
+        .await?;
+
+        let mut ringing = CallEvent::new("call.ringing", call_id, &params.trace_id, CallDirection::Outbound, &local_uri);
+        ringing.trunk = route.trunk.as_ref().map(|t| t.name.clone());
+        let response = match await_final_response(transaction, ring_deadline, ringing, sock, state).await {
+            FinalResponse::Received(r) => r,
+            FinalResponse::RingTimeout => {
+                return Ok(AttemptOutcome::Failed { status_code: 408, status_line: "408 Request Timeout".to_string(), failover: false });
+            }
+            FinalResponse::NoResponse => {
+                return Ok(AttemptOutcome::Failed { status_code: 408, status_line: "408 Request Timeout".to_string(), failover: true });
+            }
+        };
+
+        match response.status_code {
+            200..=299 => {
+                let ack = build_ack_for_2xx(&invite, &response).render(&state.config);
+                sock.send_to(ack.as_bytes(), route.next_hop).await?;
+                let call_info = outbound_dialog(&response, &invite, &local_uri, &local_tag, route, media, params);
+                return Ok(AttemptOutcome::Answered(Box::new(call_info)));
+            }
+            401 | 407 if !challenged => {
+                let authorization = route.credentials.as_ref().and_then(|(user, password)| {
+                    auth::answer_challenge(&response, user, password, "INVITE", &invite.request_uri)
+                });
+                let Some(authorization) = authorization else {
+                    return Ok(AttemptOutcome::Failed { status_code: response.status_code, status_line: response.status_line, failover: false });
+                };
+                info!(status = response.status_code, "INVITE challenge edildi, kimlik bilgileriyle tekrar gönderiliyor.");
+                challenged = true;
+                invite.branch = generate_branch();
+                invite.cseq += 1;
+                invite.extra_headers = [params.extra_headers.clone(), session_timer::offer_headers(&state.config)].concat();
+                invite.extra_headers.push(authorization);
+            }
+            _ => {
+                info!(status = response.status_code, "Giden çağrı reddedildi.");
+                return Ok(AttemptOutcome::Failed {
+                    failover: (500..600).contains(&response.status_code),
+                    status_code: response.status_code,
+                    status_line: response.status_line,
+                });
+            }
+        }
+    }
+}
+
+enum FinalResponse {
+    Received(SipResponse),
+    /// Karşı taraf çaldı ancak süre içinde cevaplamadı; CANCEL gönderildi (veya CANCEL sonrası
+    /// nihai yanıt 64*T1 içinde gelmedi).
+    RingTimeout,
+    /// Hiç yanıt alınamadı (Timer B).
+    NoResponse,
+}
+
+/// Geçici yanıtları loglayarak nihai yanıtı bekler; ilk 180/183'te `ringing` olayını yayar.
+/// Çalma süresi dolarsa CANCEL gönderir ve nihai yanıtı en fazla 64*T1 daha bekler.
+async fn await_final_response(
+    mut transaction: ClientTransaction,
+    ring_deadline: Instant,
+    ringing: CallEvent,
+    sock: &Arc<UdpSocket>,
+    state: &Arc<AppState>,
+) -> FinalResponse {
+    let mut ringing = Some(ringing);
+    let mut provisional_received = false;
+    let mut timed_out = false;
+    // Geçici yanıt alınmış INVITE'ın kendi zaman aşımı yoktur; CANCEL'a yanıt vermeyen bir karşı taraf
+    // isteği (ve ayrılan medya portunu, trunk kanalını) süresiz tutmasın diye 487 beklemesi sınırlanır.
+    let mut cancel_deadline: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            response = transaction.recv() => {
+                let Some(response) = response else {
+                    return if provisional_received { FinalResponse::RingTimeout } else { FinalResponse::NoResponse };
+                };
+                if !response.is_provisional() {
+                    // CANCEL ile yarışan 2xx yine de kabul edilir; diğer yanıtlar (487) zaman aşımıdır.
+                    return if timed_out && !response.is_success() { FinalResponse::RingTimeout } else { FinalResponse::Received(response) };
+                }
+                info!(status = response.status_code, "Giden çağrı için geçici yanıt alındı.");
+                provisional_received = true;
+                if matches!(response.status_code, 180 | 183) {
+                    if let Some(event) = ringing.take() {
+                        events::emit(state, event);
+                    }
+                }
+                if timed_out && cancel_deadline.is_none() {
+                    cancel_deadline = Some(send_cancel(&transaction, sock, state));
+                }
+            }
+            _ = sleep_until(ring_deadline), if !timed_out => {
+                info!("Çalma süresi doldu, giden çağrı iptal ediliyor.");
+                timed_out = true;
+                // CANCEL yalnızca geçici yanıt alındıktan sonra gönderilebilir (RFC 3261 9.1).
+                if provisional_received {
+                    cancel_deadline = Some(send_cancel(&transaction, sock, state));
+                }
+            }
+            _ = sleep_until(cancel_deadline.unwrap_or(ring_deadline)), if cancel_deadline.is_some() => {
+                warn!("CANCEL sonrası INVITE için nihai yanıt alınamadı, deneme sonlandırılıyor.");
+                return FinalResponse::RingTimeout;
+            }
+        }
+    }
+}
+
+/// CANCEL, INVITE ile aynı adrese gönderilir (RFC 3261 9.1). INVITE'ın nihai yanıtı için beklenecek
+/// son anı döndürür.
+fn send_cancel(transaction: &ClientTransaction, sock: &Arc<UdpSocket>, state: &Arc<AppState>) -> Instant {
+    let cancel = build_cancel(transaction.request());
+    let destination = transaction.destination();
+    let sock = sock.clone();
+    let config = state.config.clone();
+    let registry = state.client_transactions.clone();
+    tokio::spawn(async move {
+        match ClientTransaction::start(cancel, destination, sock, config, registry).await {
+            Ok(mut transaction) => {
+                if transaction.final_response().await.is_none() {
+                    warn!("CANCEL isteğine yanıt alınamadı.");
+                }
+            }
+            Err(e) => warn!(error = %e, "CANCEL gönderilemedi."),
+        }
+    });
+    Instant::now() + TRANSACTION_TIMEOUT
+}
+
+/// Denenecek rotaları sırasıyla döndürür: belirtilen trunk, kayıtlı kullanıcı veya yönlendirme tablosu.
+async fn resolve_routes(params: &OriginateParams, state: &AppState) -> Result<Vec<Route>, ServiceError> {
+    if let Some(name) = params.trunk_name.as_deref().filter(|n| !n.is_empty()) {
+        let trunk = state
+            .config
+            .trunks
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| ServiceError::Generic(format!("Bilinmeyen trunk: {}", name)))?;
+        return Ok(trunk_route(trunk, &params.destination).await.into_iter().collect());
+    }
+
+    // Önce kayıtlı bir kullanıcı aranır; NAT arkasındaki cihazlara gözlenen adres üzerinden ulaşılır.
+    let aor = if params.destination.starts_with("sip:") {
+        params.destination.clone()
+    } else {
+        format!("sip:{}@{}", params.destination, state.config.sip_realm)
+    };
+    let bindings = redis::get_bindings(&state.redis, &aor).await?;
+    if let Some(binding) = bindings.iter().max_by_key(|b| b.expires_at) {
+        return Ok(vec![Route {
+            request_uri: binding.request_uri(state.config.nat_contact_rewrite),
+            to_uri: aor,
+            from_domain: state.config.sip_realm.clone(),
+            next_hop: binding.source_addr,
+            credentials: None,
+            trunk: None,
+        }]);
+    }
+
+    // Başka bir alan adına ait SIP URI'lerine doğrudan gidilir.
+    if params.destination.starts_with("sip:") {
+        if let Some((host, port)) = extract_host_port_from_uri(&params.destination) {
+            if host != state.config.sip_realm {
+                let next_hop = tokio::net::lookup_host((host.as_str(), port.unwrap_or(5060))).await.ok().and_then(|mut a| a.next());
+                if let Some(next_hop) = next_hop {
+                    return Ok(vec![Route {
+                        request_uri: params.destination.clone(),
+                        to_uri: params.destination.clone(),
+                        from_domain: state.config.sip_realm.clone(),
+                        next_hop,
+                        credentials: None,
+                        trunk: None,
+                    }]);
+                }
+            }
+        }
+    }
+
+    let number = extract_raw_user_from_uri(&params.destination).unwrap_or_else(|| params.destination.clone());
+    let mut routes = Vec::new();
+    for trunk in routing::candidates(&state.config, &number) {
+        routes.extend(trunk_route(trunk, &number).await);
+    }
+    Ok(routes)
+}
+
+async fn trunk_route(trunk: &TrunkConfig, destination: &str) -> Option<Route> {
+    let next_hop = match trunk::resolve_addr(trunk).await {
+        Ok(addr) => addr,
+        Err(e) => {
+            warn!(trunk = %trunk.name, error = %e, "Trunk atlanıyor.");
+            return None;
+        }
+    };
+    let uri = format!("sip:{}@{}", trunk.rewrite_number(destination), trunk.domain());
+    Some(Route {
+        request_uri: uri.clone(),
+        to_uri: uri,
+        from_domain: trunk.domain().to_string(),
+        next_hop,
+        credentials: trunk.username.clone().zip(trunk.password.clone()),
+        trunk: Some(trunk.clone()),
+    })
+}
+
+/// Giden çağrıyı, `bye::send_bye` ile kapatılabilecek bir `ActiveCallInfo`'ya çevirir.
+/// `to_*` alanları yerel tarafı, `from_header` ise uzak tarafı (etiketiyle) temsil eder.
+fn outbound_dialog(
+    response: &SipResponse,
+    invite: &OutgoingRequest,
+    local_uri: &str,
+    local_tag: &str,
+    route: &Route,
+    media: &MediaAllocation,
+    params: &OriginateParams,
+) -> ActiveCallInfo {
+    ActiveCallInfo {
+        remote_addr: route.next_hop,
+        rtp_port: media.rtp_port,
+        trace_id: params.trace_id.clone(),
+        to_tag: local_tag.to_string(),
+        created_at: std::time::Instant::now(),
+        headers: response.headers.clone(),
+        via_headers: Vec::new(),
+        call_id: invite.call_id.clone(),
+        from_header: response.headers.get("to").cloned().unwrap_or_else(|| invite.to.clone()),
+        to_header: local_uri.to_string(),
+        contact_header: response.headers.get("contact").cloned().unwrap_or_else(|| format!("<{}>", route.request_uri)),
+        record_route_header: response.headers.get("record-route").cloned(),
+        raw_body: response.body.clone(),
+        // ACK'i bu servis gönderdiği için `call.answered` burada yayınlanır.
+        answered_event_published: Arc::new(Mutex::new(true)),
+        bridged_call_id: None,
+        authenticated_user: None,
+        trunk: route.trunk.as_ref().map(|t| t.name.clone()),
+        tenant_id: None,
+        direction: CallDirection::Outbound,
+        on_hold: false,
+        pending_refer_to: None,
+        media_instance: Some(media.instance.clone()),
+        local_cseq: invite.cseq,
+        session_timer: SessionTimer::from_uac_response(response),
+        last_activity: std::time::Instant::now(),
+    }
+}
+
+async fn register_answered_call(call_info: &ActiveCallInfo, state: &AppState) {
+    state
+        .active_calls
+        .lock()
+        .await
+        .insert(call_info.call_id.clone(), call_info.clone());
+    info!("Giden çağrı cevaplandı ve aktif çağrılara eklendi.");
+    events::emit(state, CallEvent::for_call("call.started", call_info));
+    events::emit(state, CallEvent::for_call("call.answered", call_info));
+
+    let Some(rabbit_channel) = &state.rabbit else {
+        warn!("RabbitMQ bağlantısı aktif değil, giden çağrı olayları yayınlanamadı.");
+        return;
+    };
+    if let Err(e) = publish_call_event("call.started", call_info, None, rabbit_channel).await {
+        warn!(error = %e, "'call.started' olayı yayınlanamadı.");
+    }
+    if let Err(e) = publish_call_answered_event(call_info, None, rabbit_channel).await {
+        warn!(error = %e, "'call.answered' olayı yayınlanamadı.");
+    }
+}
@@ -0,0 +1,336 @@
+// sentiric-sip-signaling-service/src/sip/refer.rs
+// REFER ile çağrı aktarımı (RFC 3515, RFC 5589): gelen REFER'ların işlenmesi ve örtük abonelik
+// üzerinden `message/sipfrag` NOTIFY ile ilerleme bildirimi, gönderilen REFER'lara gelen
+// NOTIFY'ların işlenmesi ve `Replaces` (RFC 3891) ile danışmalı (attended) aktarım.
+
+use crate::app_state::AppState;
+use crate::rabbitmq::publisher::publish_event;
+use crate::sip::bye;
+use crate::sip::call_control::reserve_cseq;
+use crate::sip::invite::orchestrator::release_call_media;
+use crate::sip::originate::{self, OriginateOutcome, OriginateParams};
+use crate::sip::requests::build_in_dialog_request;
+use crate::sip::responses::{create_response_from_parts, parse_status_line};
+use crate::sip::transaction::ClientTransaction;
+use crate::sip::utils::{extract_raw_user_from_uri, extract_tag, get_uri_from_header, parse_sip_headers};
+use crate::state::ActiveCallInfo;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tracing::{error, info, instrument, warn, Span};
+
+/// `Replaces` başlığı: değiştirilecek diyaloğu, alıcının bakış açısından tanımlar (RFC 3891 6.1).
+#[derive(Debug, Clone)]
+pub struct Replaces {
+    pub call_id: String,
+    pub to_tag: String,
+    pub from_tag: String,
+}
+
+impl Replaces {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';');
+        let call_id = parts.next()?.trim().to_string();
+        let (mut to_tag, mut from_tag) = (None, None);
+        for param in parts {
+            match param.trim().split_once('=') {
+                Some(("to-tag", v)) => to_tag = Some(v.to_string()),
+                Some(("from-tag", v)) => from_tag = Some(v.to_string()),
+                _ => {}
+            }
+        }
+        Some(Self { call_id, to_tag: to_tag?, from_tag: from_tag? })
+    }
+
+    /// Bu servisin taraf olduğu bir diyalog için, karşı tarafa gönderilecek `Replaces` değeri.
+    pub fn for_dialog(call_info: &ActiveCallInfo) -> Self {
+        Self {
+            call_id: call_info.call_id.clone(),
+            // Alıcı (aktarım hedefi) için to-tag kendi etiketi, yani bizim gözümüzde uzak etikettir.
+            to_tag: extract_tag(&call_info.from_header).unwrap_or_default(),
+            from_tag: call_info.to_tag.clone(),
+        }
+    }
+
+    fn to_header_value(&self) -> String {
+        format!("{};to-tag={};from-tag={}", self.call_id, self.to_tag, self.from_tag)
+    }
+}
+
+/// Refer-To URI'sine gömülü `Replaces` başlığını ekler: `<sip:hedef?Replaces=...>`.
+pub fn refer_to_with_replaces(target_uri: &str, replaces: &Replaces) -> String {
+    format!("<{}?Replaces={}>", target_uri, percent_encode(&replaces.to_header_value()))
+}
+
+/// INVITE'ın `Replaces` başlığının işaret ettiği aktif diyaloğu bulur.
+/// Alıcı biz olduğumuz için to-tag bizim yerel etiketimiz, from-tag uzak etikettir.
+pub async fn find_replaced_dialog(value: &str, state: &AppState) -> Option<ActiveCallInfo> {
+    let replaces = Replaces::parse(value)?;
+    let active_calls = state.active_calls.lock().await;
+    let call_info = active_calls.get(&replaces.call_id)?;
+    let remote_tag = extract_tag(&call_info.from_header).unwrap_or_default();
+    (call_info.to_tag == replaces.to_tag && remote_tag == replaces.from_tag).then(|| call_info.clone())
+}
+
+/// `Replaces` içeren bir INVITE cevaplandıktan sonra eski diyaloğu kapatır (RFC 3891 3).
+pub fn complete_replacement(replaced: ActiveCallInfo, new_call: &ActiveCallInfo, sock: Arc<UdpSocket>, state: Arc<AppState>) {
+    let new_call_id = new_call.call_id.clone();
+    let refer_to = new_call.from_header.clone();
+    tokio::spawn(async move {
+        info!(old_call_id = %replaced.call_id, new_call_id = %new_call_id, "Değiştirilen diyalog kapatılıyor.");
+        if state.active_calls.lock().await.remove(&replaced.call_id).is_none() {
+            info!(old_call_id = %replaced.call_id, "Değiştirilen diyalog zaten kapatılmış.");
+            return;
+        }
+        release_call_media(&replaced, &state).await;
+        if bye::send_bye(&replaced, sock, &state).await.is_none() {
+            warn!(call_id = %replaced.call_id, "Değiştirilen diyalog BYE'a yanıt vermedi.");
+        }
+        bye::publish_call_ended(&state, &replaced, "replaced").await;
+        publish_call_transferred(&state, &replaced, Some(&new_call_id), "attended", &refer_to, "inbound").await;
+    });
+}
+
+/// Karşı tarafın gönderdiği REFER: hedefi arar ve sonucu NOTIFY ile bildirir.
+#[instrument(skip_all, fields(remote_addr = %addr, call_id, trace_id))]
+pub async fn handle(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (headers, via_headers) = parse_sip_headers(request_str).ok_or("Geçersiz başlıklar")?;
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    Span::current().record("call_id", &call_id as &str);
+    info!("REFER isteği alındı.");
+
+    let respond = |status_line: &str| create_response_from_parts(status_line, &headers, &via_headers, None, &state.config, addr);
+
+    let call_info = state.active_calls.lock().await.get(&call_id).cloned();
+    let Some(call_info) = call_info else {
+        warn!("REFER alınan çağrı aktif çağrılar listesinde bulunamadı.");
+        sock.send_to(respond("481 Call/Transaction Does Not Exist").as_bytes(), addr).await?;
+        return Ok(());
+    };
+    Span::current().record("trace_id", &call_info.trace_id as &str);
+
+    let Some(refer_to) = headers.get("refer-to").cloned() else {
+        warn!("Refer-To başlığı olmayan REFER reddediliyor.");
+        sock.send_to(respond("400 Bad Request").as_bytes(), addr).await?;
+        return Ok(());
+    };
+    let (target_uri, replaces) = split_refer_to(&refer_to);
+    sock.send_to(respond("202 Accepted").as_bytes(), addr).await?;
+
+    let referred_by = headers.get("referred-by").cloned();
+    tokio::spawn(perform_transfer(call_info, target_uri, replaces, referred_by, sock, state));
+    Ok(())
+}
+
+async fn perform_transfer(
+    call_info: ActiveCallInfo,
+    target_uri: String,
+    replaces: Option<String>,
+    referred_by: Option<String>,
+    sock: Arc<UdpSocket>,
+    state: Arc<AppState>,
+) {
+    let kind = if replaces.is_some() { "attended" } else { "blind" };
+    info!(target = %target_uri, kind, "Aktarım hedefi aranıyor.");
+    send_notify(&call_info.call_id, "SIP/2.0 100 Trying", false, &sock, &state).await;
+
+    let mut extra_headers = Vec::new();
+    if let Some(referred_by) = &referred_by {
+        extra_headers.push(format!("Referred-By: {}", referred_by));
+    }
+    if let Some(replaces) = &replaces {
+        extra_headers.push(format!("Replaces: {}", replaces));
+    }
+    let params = OriginateParams {
+        destination: target_uri.clone(),
+        caller_id: extract_raw_user_from_uri(call_info.caller_uri()),
+        trunk_name: None,
+        ring_timeout: originate::DEFAULT_RING_TIMEOUT,
+        trace_id: call_info.trace_id.clone(),
+        extra_headers,
+    };
+
+    let sipfrag = match originate::originate(params, sock.clone(), state.clone()).await {
+        Ok(OriginateOutcome::Answered(new_call)) => {
+            publish_call_transferred(&state, &call_info, Some(&new_call.call_id), kind, &target_uri, "inbound").await;
+            "SIP/2.0 200 OK".to_string()
+        }
+        Ok(OriginateOutcome::Failed(_, status_line)) => {
+            warn!(status = %status_line, "Aktarım hedefi çağrıyı kabul etmedi.");
+            // Karşı tarafın durum satırı sipfrag gövdesine yalnızca geçerliyse aynen taşınır.
+            let status_line = parse_status_line(&status_line).map(|(_, line)| line).unwrap_or_else(|| "503 Service Unavailable".to_string());
+            format!("SIP/2.0 {}", status_line)
+        }
+        Err(e) => {
+            error!(error = %e, "Aktarım hedefi aranamadı.");
+            "SIP/2.0 503 Service Unavailable".to_string()
+        }
+    };
+    // Aktarımı isteyen taraf, başarılı NOTIFY'dan sonra eski diyaloğu BYE ile kapatır.
+    send_notify(&call_info.call_id, &sipfrag, true, &sock, &state).await;
+}
+
+/// Örtük REFER aboneliği için NOTIFY gönderir (RFC 3515 2.4.4).
+async fn send_notify(call_id: &str, sipfrag: &str, terminated: bool, sock: &Arc<UdpSocket>, state: &AppState) {
+    let Some((call_info, cseq)) = reserve_cseq(call_id, state).await else {
+        warn!("NOTIFY gönderilecek çağrı artık aktif değil.");
+        return;
+    };
+    let subscription_state = if terminated { "terminated;reason=noresource" } else { "active;expires=60" };
+    let mut notify = build_in_dialog_request(&call_info, "NOTIFY", cseq);
+    notify.extra_headers = vec![
+        "Event: refer".to_string(),
+        format!("Subscription-State: {}", subscription_state),
+        "Content-Type: message/sipfrag;version=2.0".to_string(),
+    ];
+    notify.body = Some(format!("{}\r\n", sipfrag));
+
+    match ClientTransaction::start(notify, call_info.remote_addr, sock.clone(), state.config.clone(), state.client_transactions.clone()).await {
+        Ok(mut transaction) => {
+            if transaction.final_response().await.is_none() {
+                warn!("NOTIFY isteğine yanıt alınamadı.");
+            }
+        }
+        Err(e) => warn!(error = %e, "NOTIFY gönderilemedi."),
+    }
+}
+
+/// Bu servisin gönderdiği REFER için karşı taraftan gelen ilerleme bildirimleri.
+#[instrument(skip_all, fields(remote_addr = %addr, call_id, trace_id))]
+pub async fn handle_notify(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (header_part, body) = request_str.split_once("\r\n\r\n").unwrap_or((request_str, ""));
+    let (headers, via_headers) = parse_sip_headers(header_part).ok_or("Geçersiz başlıklar")?;
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    Span::current().record("call_id", &call_id as &str);
+
+    let call_info = state.active_calls.lock().await.get(&call_id).cloned();
+    let status_line = if call_info.is_some() { "200 OK" } else { "481 Call/Transaction Does Not Exist" };
+    let response = create_response_from_parts(status_line, &headers, &via_headers, None, &state.config, addr);
+    sock.send_to(response.as_bytes(), addr).await?;
+
+    let Some(call_info) = call_info else {
+        warn!("NOTIFY alınan çağrı aktif çağrılar listesinde bulunamadı.");
+        return Ok(());
+    };
+    Span::current().record("trace_id", &call_info.trace_id as &str);
+    if !headers.get("event").is_some_and(|e| e.trim().starts_with("refer")) {
+        return Ok(());
+    }
+
+    let Some(status_code) = body
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("SIP/2.0 "))
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|c| c.parse::<u16>().ok())
+    else {
+        warn!("NOTIFY gövdesi geçerli bir sipfrag değil.");
+        return Ok(());
+    };
+    info!(status = status_code, "Aktarım ilerleme bildirimi alındı.");
+    if status_code < 200 {
+        return Ok(());
+    }
+    if status_code >= 300 {
+        warn!(status = status_code, "Aktarım başarısız oldu, çağrı devam ediyor.");
+        return Ok(());
+    }
+
+    // Aktarım tamamlandı: aktaran taraf olarak eski diyaloğu kapatırız (RFC 5589 6.1).
+    if state.active_calls.lock().await.remove(&call_id).is_some() {
+        let refer_to = call_info.pending_refer_to.clone().unwrap_or_default();
+        let kind = if refer_to.contains("Replaces=") { "attended" } else { "blind" };
+        publish_call_transferred(&state, &call_info, None, kind, &refer_to, "outbound").await;
+        release_call_media(&call_info, &state).await;
+        if bye::send_bye(&call_info, sock, &state).await.is_none() {
+            warn!("Aktarılan çağrı BYE'a yanıt vermedi.");
+        }
+        bye::publish_call_ended(&state, &call_info, "transferred").await;
+    }
+    Ok(())
+}
+
+/// `Refer-To` değerini hedef URI'ye ve (varsa) gömülü `Replaces` başlığına ayırır.
+fn split_refer_to(refer_to: &str) -> (String, Option<String>) {
+    let uri = get_uri_from_header(refer_to).unwrap_or_else(|| refer_to.trim().to_string());
+    let Some((target, embedded_headers)) = uri.split_once('?') else {
+        return (uri, None);
+    };
+    let replaces = embedded_headers
+        .split('&')
+        .find_map(|h| h.split_once('=').filter(|(k, _)| k.eq_ignore_ascii_case("replaces")))
+        .map(|(_, v)| percent_decode(v));
+    (target.to_string(), replaces)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ';' => "%3B".to_string(),
+            '=' => "%3D".to_string(),
+            '@' => "%40".to_string(),
+            '%' => "%25".to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `call.transferred`: eski ve (biliniyorsa) yeni Call-ID'yi ilişkilendirir.
+/// `direction`: REFER'ı karşı taraf gönderdiyse `inbound`, biz gönderdiysek `outbound`.
+pub async fn publish_call_transferred(
+    state: &AppState,
+    call_info: &ActiveCallInfo,
+    new_call_id: Option<&str>,
+    kind: &str,
+    refer_to: &str,
+    direction: &str,
+) {
+    let Some(rabbit_channel) = &state.rabbit else {
+        warn!("RabbitMQ bağlantısı aktif değil, 'call.transferred' olayı yayınlanamadı.");
+        return;
+    };
+    let payload = serde_json::json!({
+        "eventType": "call.transferred",
+        "traceId": call_info.trace_id,
+        "oldCallId": call_info.call_id,
+        "newCallId": new_call_id,
+        "transferType": kind,
+        "referTo": refer_to,
+        "direction": direction,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    match publish_event(rabbit_channel, "call.transferred", &payload).await {
+        Ok(()) => info!("'call.transferred' olayı başarıyla yayınlandı."),
+        Err(e) => error!(error = %e, "'call.transferred' olayı yayınlanırken hata oluştu."),
+    }
+}
@@ -1,17 +1,19 @@
 use crate::app_state::AppState;
+use crate::rabbitmq::publisher::publish_event;
 use crate::redis::{self, AsyncCommands};
+use crate::sip::auth;
 use crate::sip::call_context::CallContext;
 use crate::sip::responses::create_response_from_parts; // DÜZELTME: create_response yerine bunu kullanacağız.
-use md5::compute;
-use rand::distributions::{Alphanumeric, DistString};
-use sentiric_contracts::sentiric::user::v1::GetSipCredentialsRequest;
+use crate::sip::utils::{contact_is_behind_nat, get_uri_from_header};
 use std::collections::HashMap;
 use std::error::Error;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
-use tonic::Request as TonicRequest;
-use tracing::{info, instrument, warn, Span};
+use tracing::{error, info, instrument, warn, Span};
+
+const REGISTRATION_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
 
 #[instrument(skip_all, fields(remote_addr = %addr, call_id))]
 pub async fn handle(
@@ -63,9 +65,8 @@ async fn challenge_client(
     state: Arc<AppState>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     info!("Authorization başlığı yok, 401 Unauthorized ile challenge gönderiliyor.");
-    let nonce = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-    let auth_challenge = format!(r#"Digest realm="{}", qop="auth", nonce="{}""#, state.config.sip_realm, nonce);
-    headers.insert("www-authenticate".to_string(), auth_challenge);
+    let nonce = auth::generate_nonce();
+    headers.insert("www-authenticate".to_string(), auth::build_challenge(&state.config.sip_realm, &nonce, false));
 
     // --- DÜZELTME BURADA ---
     let response = create_response_from_parts("401 Unauthorized", &headers, &via_headers, None, &state.config, addr);
@@ -82,21 +83,11 @@ async fn verify_authentication(
     state: Arc<AppState>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     info!("Authorization başlığı bulundu, kimlik bilgileri doğrulanıyor.");
-    let auth_parts: HashMap<_, _> = auth_header.strip_prefix("Digest ").unwrap_or("").split(',')
-        .filter_map(|s| s.trim().split_once('='))
-        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
-        .collect();
+    let credentials = auth::parse_credentials(auth_header)?;
+    let username = credentials.username.as_str();
+    let realm = credentials.realm.as_str();
 
-    let username = *auth_parts.get("username").ok_or("username eksik")?;
-    let realm = *auth_parts.get("realm").ok_or("realm eksik")?;
-    let nonce = *auth_parts.get("nonce").ok_or("nonce eksik")?;
-    let uri = *auth_parts.get("uri").ok_or("uri eksik")?;
-    let client_response = *auth_parts.get("response").ok_or("response eksik")?;
-    
-    let mut user_client = state.grpc.user.clone();
-    let creds_res = user_client.get_sip_credentials(TonicRequest::new(GetSipCredentialsRequest {
-        sip_username: username.to_string(), realm: realm.to_string(),
-    })).await;
+    let creds_res = auth::fetch_ha1(&state, username, realm).await;
 
     if let Err(e) = creds_res {
         warn!(error = %e, "SIP kullanıcısı bulunamadı veya user-service hatası.");
@@ -106,13 +97,8 @@ async fn verify_authentication(
         return Ok(());
     }
 
-    let ha1_hash = creds_res.unwrap().into_inner().ha1_hash;
-    let a2_str = format!("REGISTER:{}", uri);
-    let a2_hash = format!("{:x}", compute(a2_str.as_bytes()));
-    let response_str = format!("{}:{}:{}", ha1_hash, nonce, a2_hash);
-    let expected_response = format!("{:x}", compute(response_str.as_bytes()));
-
-    if client_response == expected_response {
+    let ha1_hash = creds_res.unwrap();
+    if credentials.response == auth::expected_response(&ha1_hash, "REGISTER", &credentials) {
         info!("Kimlik doğrulama başarılı. Kullanıcı kaydediliyor.");
         let call_id = headers.get("call-id").cloned().unwrap_or_default();
         let mut conn = state.redis.get_multiplexed_async_connection().await?;
@@ -122,9 +108,41 @@ async fn verify_authentication(
         let contact_uri = headers.get("contact").cloned().unwrap_or_default();
         let expires = headers.get("expires").and_then(|e| e.parse::<u64>().ok()).unwrap_or(3600);
 
+        let aor = format!("sip:{}@{}", username, realm);
         if expires > 0 {
-            let aor = format!("sip_registration:sip:{}@{}", username, realm);
-            redis::set_registration(&state.redis, &aor, &contact_uri, expires).await?;
+            let declared_uri = get_uri_from_header(&contact_uri).unwrap_or_else(|| contact_uri.clone());
+            let binding = redis::Binding {
+                contact: contact_uri.clone(),
+                source_addr: addr,
+                expires_at: chrono::Utc::now().timestamp() + expires as i64,
+                user_agent: headers.get("user-agent").cloned(),
+                behind_nat: contact_is_behind_nat(&declared_uri, addr),
+            };
+            if binding.behind_nat {
+                info!(contact = %declared_uri, source_addr = %addr, "İstemci NAT arkasında, istekler gözlenen adrese yönlendirilecek.");
+            }
+            // Eski tek-kayıt anahtarı, diğer servislerin doğrudan ulaşabileceği adresi tutar.
+            let reachable_contact = format!("<{}>", binding.request_uri(state.config.nat_contact_rewrite));
+            redis::set_registration(&state.redis, &format!("sip_registration:{}", aor), &reachable_contact, expires).await?;
+            let is_new = redis::upsert_binding(&state.redis, &aor, &binding).await?;
+            let event_type = if is_new { "user.registered" } else { "user.refreshed" };
+            publish_registration_event(&state, event_type, &aor, &binding).await;
+        } else {
+            let existing = redis::get_bindings(&state.redis, &aor).await?;
+            let removed: Vec<redis::Binding> = if contact_uri.trim() == "*" {
+                redis::clear_bindings(&state.redis, &aor).await?;
+                existing
+            } else {
+                let uri = get_uri_from_header(&contact_uri).unwrap_or_else(|| contact_uri.clone());
+                redis::remove_binding(&state.redis, &aor, &uri).await?;
+                existing.into_iter().filter(|b| b.contact_uri() == uri).collect()
+            };
+            if redis::get_bindings(&state.redis, &aor).await?.is_empty() {
+                let _: () = conn.del(format!("sip_registration:{}", aor)).await?;
+            }
+            for binding in &removed {
+                publish_registration_event(&state, "user.unregistered", &aor, binding).await;
+            }
         }
         
         headers.insert("contact".to_string(), format!("{};expires={}", contact_uri, expires)); // DÜZELTME: Küçük harf
@@ -138,4 +156,42 @@ async fn verify_authentication(
         sock.send_to(response.as_bytes(), addr).await?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Süresi dolan kayıtları periyodik olarak temizler ve `user.registration_expired` olayını yayınlar.
+pub async fn sweep_expired_registrations(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(REGISTRATION_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match redis::take_expired_bindings(&state.redis).await {
+            Ok(expired) => {
+                for (aor, binding) in &expired {
+                    info!(aor = %aor, contact = %binding.contact, "Kaydın süresi doldu.");
+                    publish_registration_event(&state, "user.registration_expired", aor, binding).await;
+                }
+            }
+            Err(e) => warn!(error = %e, "Süresi dolan kayıtlar taranamadı."),
+        }
+    }
+}
+
+async fn publish_registration_event(state: &AppState, event_type: &str, aor: &str, binding: &redis::Binding) {
+    let Some(rabbit_channel) = &state.rabbit else {
+        warn!("RabbitMQ bağlantısı aktif değil, '{}' olayı yayınlanamadı.", event_type);
+        return;
+    };
+    let payload = serde_json::json!({
+        "eventType": event_type,
+        "sipUri": aor,
+        "contact": binding.contact,
+        "userAgent": binding.user_agent,
+        "sourceAddr": binding.source_addr.to_string(),
+        "behindNat": binding.behind_nat,
+        "expiresAt": chrono::DateTime::from_timestamp(binding.expires_at, 0).map(|t| t.to_rfc3339()),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    match publish_event(rabbit_channel, event_type, &payload).await {
+        Ok(()) => info!("'{}' olayı başarıyla yayınlandı.", event_type),
+        Err(e) => error!(error = %e, "'{}' olayı yayınlanırken hata oluştu.", event_type),
+    }
+}
@@ -0,0 +1,241 @@
+// sentiric-sip-signaling-service/src/sip/requests.rs
+// Bu servisin UAC (istemci) rolünde gönderdiği isteklerin oluşturucuları.
+use crate::config::AppConfig;
+use crate::sip::transaction::SipResponse;
+use crate::sip::utils::get_uri_from_header;
+use crate::state::{ActiveCallInfo, CallDirection};
+use rand::Rng;
+
+/// Giden bir SIP isteğinin yeniden üretilebilir tanımı.
+/// Retransmission ve CANCEL/ACK üretimi için ham metin yerine parçalar saklanır.
+#[derive(Debug, Clone)]
+pub struct OutgoingRequest {
+    pub method: String,
+    pub request_uri: String,
+    pub branch: String,
+    pub from: String,
+    pub to: String,
+    pub call_id: String,
+    pub cseq: u32,
+    /// Diyaloğun route set'inden üretilen `Route` başlık değerleri (sırasıyla).
+    pub route: Vec<String>,
+    pub extra_headers: Vec<String>,
+    pub body: Option<String>,
+}
+
+impl OutgoingRequest {
+    pub fn render(&self, config: &AppConfig) -> String {
+        // Gövde SDP değilse (örn. NOTIFY sipfrag) Content-Type, `extra_headers` ile verilir.
+        let has_content_type = self.extra_headers.iter().any(|h| h.to_lowercase().starts_with("content-type:"));
+        let content_type = if self.body.is_some() && !has_content_type { "Content-Type: application/sdp\r\n" } else { "" };
+        let body = self.body.as_deref().unwrap_or("");
+        let extra_headers: String = self.extra_headers.iter().map(|h| format!("{}\r\n", h)).collect();
+        let route: String = self.route.iter().map(|r| format!("Route: {}\r\n", r)).collect();
+
+        format!(
+            "{} {} SIP/2.0\r\n\
+            Via: SIP/2.0/UDP {}:{};branch={};rport\r\n\
+            Max-Forwards: 70\r\n\
+            {}\
+            From: {}\r\n\
+            To: {}\r\n\
+            Call-ID: {}\r\n\
+            CSeq: {} {}\r\n\
+            Contact: <sip:sentiric@{}:{}>\r\n\
+            {}\
+            User-Agent: Sentiric Signaling v{}\r\n\
+            {}\
+            Content-Length: {}\r\n\
+            \r\n\
+            {}",
+            self.method, self.request_uri,
+            config.sip_public_ip, config.sip_listen_addr.port(), self.branch,
+            route,
+            self.from,
+            self.to,
+            self.call_id,
+            self.cseq, self.method,
+            config.sip_public_ip, config.sip_listen_addr.port(),
+            extra_headers,
+            config.service_version,
+            content_type,
+            body.len(),
+            body
+        )
+    }
+}
+
+pub fn generate_branch() -> String {
+    format!("z9hG4bK.{}", rand::thread_rng().gen::<u32>())
+}
+
+pub fn generate_tag() -> String {
+    rand::thread_rng().gen::<u32>().to_string()
+}
+
+/// Bir INVITE için aynı transaction'a ait CANCEL isteğini üretir (RFC 3261 9.1).
+pub fn build_cancel(invite: &OutgoingRequest) -> OutgoingRequest {
+    OutgoingRequest {
+        method: "CANCEL".to_string(),
+        request_uri: invite.request_uri.clone(),
+        branch: invite.branch.clone(),
+        from: invite.from.clone(),
+        to: invite.to.clone(),
+        call_id: invite.call_id.clone(),
+        cseq: invite.cseq,
+        route: invite.route.clone(),
+        extra_headers: Vec::new(),
+        body: None,
+    }
+}
+
+/// 2xx olmayan nihai yanıtlar için ACK; INVITE ile aynı branch'i kullanır (RFC 3261 17.1.1.3).
+pub fn build_ack_for_non_2xx(invite: &OutgoingRequest, response: &SipResponse) -> OutgoingRequest {
+    OutgoingRequest {
+        method: "ACK".to_string(),
+        request_uri: invite.request_uri.clone(),
+        branch: invite.branch.clone(),
+        from: invite.from.clone(),
+        to: response.headers.get("to").cloned().unwrap_or_else(|| invite.to.clone()),
+        call_id: invite.call_id.clone(),
+        cseq: invite.cseq,
+        route: invite.route.clone(),
+        extra_headers: Vec::new(),
+        body: None,
+    }
+}
+
+/// 2xx yanıtı için ACK; yeni bir branch ile uzak Contact adresine, 2xx'in Record-Route'undan
+/// kurulan route set üzerinden gönderilir (RFC 3261 13.2.2.4).
+pub fn build_ack_for_2xx(invite: &OutgoingRequest, response: &SipResponse) -> OutgoingRequest {
+    let remote_target = response
+        .headers
+        .get("contact")
+        .and_then(|c| get_uri_from_header(c))
+        .unwrap_or_else(|| invite.request_uri.clone());
+    let (request_uri, route) = route_request(remote_target, response.headers.get("record-route").map(String::as_str), true);
+
+    OutgoingRequest {
+        method: "ACK".to_string(),
+        request_uri,
+        branch: generate_branch(),
+        from: invite.from.clone(),
+        to: response.headers.get("to").cloned().unwrap_or_else(|| invite.to.clone()),
+        call_id: invite.call_id.clone(),
+        cseq: invite.cseq,
+        route,
+        extra_headers: Vec::new(),
+        body: None,
+    }
+}
+
+/// Kurulu bir diyalog içinde gönderilen istek (BYE, re-INVITE, REFER...).
+/// `ActiveCallInfo`'da `to_*` alanları yerel tarafı, `from_header` uzak tarafı temsil eder (bkz. `bye::send_bye`).
+pub fn build_in_dialog_request(call_info: &ActiveCallInfo, method: &str, cseq: u32) -> OutgoingRequest {
+    let remote_target = get_uri_from_header(&call_info.contact_header).unwrap_or_else(|| call_info.contact_header.clone());
+    let uac = call_info.direction == CallDirection::Outbound;
+    let (request_uri, route) = route_request(remote_target, call_info.record_route_header.as_deref(), uac);
+
+    OutgoingRequest {
+        method: method.to_string(),
+        request_uri,
+        branch: generate_branch(),
+        from: format!("{};tag={}", call_info.to_header, call_info.to_tag),
+        to: call_info.from_header.clone(),
+        call_id: call_info.call_id.clone(),
+        cseq,
+        route,
+        extra_headers: Vec::new(),
+        body: None,
+    }
+}
+
+/// Diyaloğun route set'ine göre Request-URI'yi ve `Route` başlıklarını belirler (RFC 3261 12.2.1.1).
+/// Route set, UAS tarafında INVITE'ın Record-Route sırasıyla, UAC tarafında ise 2xx'inkinin tersiyle
+/// kurulur (12.1.1, 12.1.2). İlk öğe `lr` taşımıyorsa (katı yönlendirme) Request-URI o öğe olur ve
+/// uzak hedef son `Route` olarak eklenir.
+fn route_request(remote_target: String, record_route: Option<&str>, uac: bool) -> (String, Vec<String>) {
+    let mut route_set: Vec<String> = record_route.map(split_header_values).unwrap_or_default();
+    if uac {
+        route_set.reverse();
+    }
+    let Some(first) = route_set.first() else {
+        return (remote_target, route_set);
+    };
+    let first_uri = get_uri_from_header(first).unwrap_or_else(|| first.clone());
+    if first_uri.split(';').skip(1).any(|p| p.trim().eq_ignore_ascii_case("lr")) {
+        return (remote_target, route_set);
+    }
+    route_set.remove(0);
+    route_set.push(format!("<{}>", remote_target));
+    (first_uri, route_set)
+}
+
+/// Virgülle birleştirilmiş bir başlık değerini, `<...>` ve tırnak içindeki virgülleri bölmeden ayırır.
+fn split_header_values(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let (mut in_angle, mut in_quotes, mut start) = (false, false, 0);
+    for (i, c) in value.char_indices() {
+        match c {
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_angle && !in_quotes => {
+                values.push(value[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(value[start..].trim().to_string());
+    values.retain(|v| !v.is_empty());
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip::utils::parse_sip_headers;
+
+    const RECORD_ROUTE: &str = "<sip:p1.example.com;lr>, <sip:p2.example.com;lr>";
+
+    #[test]
+    fn uas_route_set_keeps_record_route_order() {
+        let (uri, route) = route_request("sip:alice@10.0.0.1".to_string(), Some(RECORD_ROUTE), false);
+        assert_eq!(uri, "sip:alice@10.0.0.1");
+        assert_eq!(route, vec!["<sip:p1.example.com;lr>", "<sip:p2.example.com;lr>"]);
+    }
+
+    #[test]
+    fn uac_route_set_is_reversed() {
+        let (uri, route) = route_request("sip:bob@10.0.0.2".to_string(), Some(RECORD_ROUTE), true);
+        assert_eq!(uri, "sip:bob@10.0.0.2");
+        assert_eq!(route, vec!["<sip:p2.example.com;lr>", "<sip:p1.example.com;lr>"]);
+    }
+
+    #[test]
+    fn strict_router_becomes_request_uri() {
+        let (uri, route) = route_request("sip:bob@10.0.0.2".to_string(), Some("<sip:strict.example.com>, <sip:p1.example.com;lr>"), false);
+        assert_eq!(uri, "sip:strict.example.com");
+        assert_eq!(route, vec!["<sip:p1.example.com;lr>", "<sip:bob@10.0.0.2>"]);
+    }
+
+    #[test]
+    fn no_record_route_means_no_route_headers() {
+        let (uri, route) = route_request("sip:bob@10.0.0.2".to_string(), None, true);
+        assert_eq!(uri, "sip:bob@10.0.0.2");
+        assert!(route.is_empty());
+    }
+
+    #[test]
+    fn record_route_lines_are_combined_in_order() {
+        let (headers, _) = parse_sip_headers(
+            "INVITE sip:1001@sentiric.test SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK-rr\r\n\
+             Record-Route: <sip:p1.example.com;lr>\r\n\
+             Record-Route: <sip:p2.example.com;lr>",
+        )
+        .unwrap();
+        assert_eq!(headers.get("record-route").map(String::as_str), Some(RECORD_ROUTE));
+    }
+}
@@ -1,6 +1,7 @@
 // sentiric-sip-signaling-service/src/sip/responses.rs
 use crate::config::AppConfig;
 use crate::sip::call_context::CallContext;
+use crate::sip::extensions;
 use rand::Rng;
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -20,11 +21,22 @@ pub fn build_180_ringing(
 pub fn build_200_ok_with_sdp(
     headers: &HashMap<String, String>,
     via_headers: &[String],
-    rtp_port: u32,
+    sdp_body: &str,
+    extra_headers: &[String],
     config: &AppConfig,
     remote_addr: SocketAddr,
 ) -> String {
-    let sdp_body = format!(
+    create_response_with_headers("200 OK", headers, via_headers, extra_headers, Some(sdp_body), config, remote_addr)
+}
+
+// Bu servisin medya servisi portunu gösteren SDP teklifi/cevabı
+pub fn build_sdp(rtp_port: u32, config: &AppConfig) -> String {
+    build_sdp_with_direction(rtp_port, config, "sendrecv")
+}
+
+// Beklemeye alma (RFC 3264 8.4) için medya yönü belirtilebilen SDP teklifi: sendrecv | sendonly | inactive
+pub fn build_sdp_with_direction(rtp_port: u32, config: &AppConfig, direction: &str) -> String {
+    format!(
         "v=0\r\n\
         o=- {0} {0} IN IP4 {1}\r\n\
         s=Sentiric\r\n\
@@ -36,13 +48,41 @@ pub fn build_200_ok_with_sdp(
         a=rtpmap:18 G729/8000\r\n\
         a=rtpmap:101 telephone-event/8000\r\n\
         a=fmtp:101 0-16\r\n\
-        a=sendrecv\r\n",
+        a={3}\r\n",
         rand::thread_rng().gen::<u32>(),
         &config.sip_public_ip, 
-        rtp_port
-    );
-    
-    create_response_from_parts("200 OK", headers, via_headers, Some(&sdp_body), config, remote_addr)
+        rtp_port,
+        direction
+    )
+}
+
+// Teklifteki medya yönüne karşılık gelen cevap yönü (RFC 3264 6.1). Teklif yoksa (gövdesiz
+// re-INVITE) 200 OK bir tekliftir ve medya her iki yönde açılır.
+pub fn answer_direction(offer: &str) -> &'static str {
+    offer
+        .lines()
+        .rev()
+        .find_map(|line| match line.trim() {
+            "a=sendonly" => Some("recvonly"),
+            "a=recvonly" => Some("sendonly"),
+            "a=inactive" => Some("inactive"),
+            "a=sendrecv" => Some("sendrecv"),
+            _ => None,
+        })
+        .unwrap_or("sendrecv")
+}
+
+// Dialplan'den veya karşı taraftan gelen bir durum satırını doğrular: 100-699 aralığında üç haneli kod,
+// ardından boş olmayan bir gerekçe. Kontrol karakteri (CR/LF) içeren değerler başlık enjekte edebileceği
+// için reddedilir. Geçerliyse kodu ve kırpılmış satırı döndürür.
+pub fn parse_status_line(value: &str) -> Option<(u16, String)> {
+    let value = value.trim();
+    let (code, reason) = value.split_once(' ')?;
+    if code.len() != 3 || reason.trim().is_empty() || value.chars().any(char::is_control) {
+        return None;
+    }
+    let code = code.parse::<u16>().ok().filter(|c| (100..700).contains(c))?;
+    Some((code, value.to_string()))
 }
 
 pub fn create_response(
@@ -61,25 +101,40 @@ pub fn create_response_from_parts(
     body: Option<&str>,
     config: &AppConfig,
     remote_addr: SocketAddr,
+) -> String {
+    create_response_with_headers(status_line, headers, via_headers, &[], body, config, remote_addr)
+}
+
+// İsteğe özgü ek başlıklarla (örn. `Session-Expires`, `Min-SE`) yanıt
+pub fn create_response_with_headers(
+    status_line: &str,
+    headers: &HashMap<String, String>,
+    via_headers: &[String],
+    extra_headers: &[String],
+    body: Option<&str>,
+    config: &AppConfig,
+    remote_addr: SocketAddr,
 ) -> String {
     let response_body = body.unwrap_or("");
     let empty_string = String::new();
     
     let mut via_lines_vec = Vec::new();
-    for via in via_headers {
-        let mut temp_via = via.clone();
+    for (i, via) in via_headers.iter().enumerate() {
+        let mut temp_via = if i == 0 { apply_rport(via, remote_addr) } else { via.clone() };
         if !temp_via.contains(";received=") {
              temp_via = format!("{};received={}", temp_via, remote_addr.ip());
         }
-        via_lines_vec.push(temp_via);
+        via_lines_vec.push(format!("Via: {}", temp_via));
     }
     let via_lines = via_lines_vec.join("\r\n");
 
     let contact_header = format!("<sip:sentiric@{}:{}>", config.sip_public_ip, config.sip_listen_addr.port());
 
-    let server_header = format!("Server: Sentiric Signaling v{}", config.service_version);
+    let server_header = format!("Server: Sentiric Signaling v{}\r\n", config.service_version);
     let content_type = if body.is_some() { "Content-Type: application/sdp\r\n" } else { "" };
     let www_auth = headers.get("www-authenticate").map(|v| format!("WWW-Authenticate: {}\r\n", v)).unwrap_or_default();
+    let proxy_auth = headers.get("proxy-authenticate").map(|v| format!("Proxy-Authenticate: {}\r\n", v)).unwrap_or_default();
+    let extra: String = extra_headers.iter().map(|h| format!("{}\r\n", h)).collect();
 
     // Debug yerine info kullanabiliriz veya hiç loglamayabiliriz. 
     // Performans için şimdilik loglamayı kaldırıyorum.
@@ -93,7 +148,11 @@ pub fn create_response_from_parts(
         Call-ID: {}\r\n\
         CSeq: {}\r\n\
         {}\
+        {}\
+        {}\
         Contact: {}\r\n\
+        {}\r\n\
+        {}\r\n\
         {}\
         {}\
         Content-Length: {}\r\n\
@@ -106,10 +165,49 @@ pub fn create_response_from_parts(
         headers.get("call-id").unwrap_or(&empty_string),
         headers.get("cseq").unwrap_or(&empty_string),
         www_auth,
+        proxy_auth,
+        extra,
         contact_header,
+        extensions::allow_header(),
+        extensions::supported_header(),
         server_header,
         content_type,
         response_body.len(),
         response_body
     )
-}
\ No newline at end of file
+}
+
+// RFC 3581: En üstteki Via değersiz bir `rport` parametresi içeriyorsa,
+// isteğin gerçekten geldiği port ile doldurulur; yanıt da bu porta gönderilir.
+fn apply_rport(via: &str, remote_addr: SocketAddr) -> String {
+    via.split(';')
+        .map(|param| {
+            if param.trim().eq_ignore_ascii_case("rport") {
+                format!("rport={}", remote_addr.port())
+            } else {
+                param.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_line_must_be_code_and_reason() {
+        assert_eq!(parse_status_line(" 486 Busy Here "), Some((486, "486 Busy Here".to_string())));
+        assert_eq!(parse_status_line("486"), None);
+        assert_eq!(parse_status_line("48 Busy"), None);
+        assert_eq!(parse_status_line("786 Busy Here"), None);
+        assert_eq!(parse_status_line("Busy Here"), None);
+    }
+
+    #[test]
+    fn status_line_with_control_characters_is_rejected() {
+        assert_eq!(parse_status_line("486 Busy\r\nX-Injected: 1"), None);
+        assert_eq!(parse_status_line("486 Busy\u{0}Here"), None);
+    }
+}
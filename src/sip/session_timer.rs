@@ -0,0 +1,325 @@
+// sentiric-sip-signaling-service/src/sip/session_timer.rs
+// Oturum zamanlayıcıları (RFC 4028) ve diyalog canlılığı. Çağrılar yaşlarına göre değil, karşı
+// tarafın hâlâ orada olup olmadığına göre temizlenir: yenileyici bu servisse oturum süresinin
+// yarısında UPDATE veya re-INVITE gönderilir, karşı tarafsa süre içinde yenileme gelmezse diyalog
+// BYE ile kapatılır. Zamanlayıcı üzerinde anlaşılmamış diyaloglar ise belirli aralıklarla OPTIONS ile yoklanır.
+
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::sip::bye;
+use crate::sip::call_control;
+use crate::sip::extensions;
+use crate::sip::invite::orchestrator::release_call_media;
+use crate::sip::requests::{build_ack_for_2xx, build_in_dialog_request};
+use crate::sip::responses::build_sdp_with_direction;
+use crate::sip::transaction::{SipResponse, TRANSACTION_TIMEOUT};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tracing::{error, info, instrument, warn};
+
+/// Diyalogların zamanlayıcılarının kontrol edilme aralığı.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+/// ACK ile kurulmamış bir diyaloğun sızıntı sayılacağı yaş. Normalde kurulum geri alma adımları
+/// bu diyalogları çok daha önce kapatır.
+const UNCONFIRMED_DIALOG_MAX_AGE: Duration = Duration::from_secs(300);
+/// Tek bir diyalog kontrolünün sürebileceği en uzun süre. Kontroller diyalog içi istek gönderir;
+/// süreyi aşan bir kontrol diyaloğu bir sonraki turda yeniden denetlenebilir kılmak için kesilir.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(TRANSACTION_TIMEOUT.as_secs() * 2);
+
+/// Oturumu yenilemekle yükümlü taraf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Refresher {
+    /// Bu servis yeniler (re-INVITE).
+    Local,
+    /// Karşı taraf yeniler; yenileme gelmezse oturum sona erer.
+    Remote,
+}
+
+/// Bir diyalog için anlaşılan oturum zamanlayıcısı.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionTimer {
+    pub interval: Duration,
+    pub refresher: Refresher,
+}
+
+impl SessionTimer {
+    /// `since` anındaki yenilemeden sonra, yerel yenileyici için bir sonraki yenilemenin zamanı;
+    /// uzak yenileyici için oturumun sona ereceği an (RFC 4028 10: süre bitmeden 32 sn veya
+    /// sürenin üçte biri kadar önce, hangisi küçükse).
+    pub fn due(&self, since: Instant) -> Instant {
+        match self.refresher {
+            Refresher::Local => since + self.interval / 2,
+            Refresher::Remote => since + self.interval - TRANSACTION_TIMEOUT.min(self.interval / 3),
+        }
+    }
+
+    /// Bu servisin gönderdiği bir isteğe (yenileme re-INVITE'ı) eklenecek başlıklar.
+    pub fn request_headers(&self) -> Vec<String> {
+        let refresher = match self.refresher {
+            Refresher::Local => "uac",
+            Refresher::Remote => "uas",
+        };
+        vec![
+            "Supported: timer".to_string(),
+            format!("Session-Expires: {};refresher={}", self.interval.as_secs(), refresher),
+        ]
+    }
+
+    /// Bu servisin bir INVITE'a verdiği 2xx yanıta eklenecek başlıklar.
+    pub fn response_headers(&self) -> Vec<String> {
+        match self.refresher {
+            Refresher::Local => vec![format!("Session-Expires: {};refresher=uas", self.interval.as_secs())],
+            // Yenileme arayana bırakıldığında arayanın bunu desteklemesi zorunludur.
+            Refresher::Remote => vec![
+                "Require: timer".to_string(),
+                format!("Session-Expires: {};refresher=uac", self.interval.as_secs()),
+            ],
+        }
+    }
+
+    /// Bu servisin gönderdiği bir INVITE'ın 2xx yanıtından anlaşılan zamanlayıcı.
+    pub fn from_uac_response(response: &SipResponse) -> Option<Self> {
+        let (secs, refresher) = parse_session_expires(response.headers.get("session-expires")?)?;
+        let refresher = match refresher.as_deref() {
+            Some("uas") => Refresher::Remote,
+            _ => Refresher::Local,
+        };
+        Some(Self { interval: Duration::from_secs(secs), refresher })
+    }
+}
+
+/// Bu servisin gönderdiği ilk INVITE'lara eklenecek başlıklar; yenileyiciyi karşı taraf seçer.
+pub fn offer_headers(config: &AppConfig) -> Vec<String> {
+    let mut headers = vec!["Supported: timer".to_string()];
+    if let Some(interval) = config.session_expires {
+        headers.push(format!("Session-Expires: {}", interval.as_secs()));
+        headers.push(format!("Min-SE: {}", config.session_min_se.as_secs()));
+    }
+    headers
+}
+
+/// Gelen bir INVITE (veya re-INVITE) için oturum zamanlayıcısını belirler (RFC 4028 9).
+/// İstenen süre Min-SE'nin altındaysa `Err` ile 422 yanıtında bildirilecek Min-SE döner.
+pub fn negotiate_uas(headers: &HashMap<String, String>, config: &AppConfig) -> Result<Option<SessionTimer>, u64> {
+    let min_se = config.session_min_se.as_secs();
+    let remote_supports = extensions::lists(headers, "supported", "timer");
+
+    let Some((requested, refresher)) = headers.get("session-expires").and_then(|v| parse_session_expires(v)) else {
+        // Arayan zamanlayıcı önermediyse süreyi bu servis belirler; arayan destekliyorsa yenilemeyi ona bırakır.
+        return Ok(config.session_expires.map(|interval| SessionTimer {
+            interval,
+            refresher: if remote_supports { Refresher::Remote } else { Refresher::Local },
+        }));
+    };
+    if requested < min_se {
+        return Err(min_se);
+    }
+
+    // UAS süreyi yalnızca kısaltabilir, ancak iki tarafın Min-SE'sinin altına indiremez.
+    let remote_min_se = headers.get("min-se").and_then(|v| parse_session_expires(v)).map_or(0, |(secs, _)| secs);
+    let interval = config
+        .session_expires
+        .map_or(requested, |local| local.as_secs().min(requested))
+        .max(min_se)
+        .max(remote_min_se);
+    let refresher = match refresher.as_deref() {
+        Some("uac") => Refresher::Remote,
+        Some("uas") => Refresher::Local,
+        _ if remote_supports => Refresher::Remote,
+        _ => Refresher::Local,
+    };
+    Ok(Some(SessionTimer { interval: Duration::from_secs(interval), refresher }))
+}
+
+/// `1800;refresher=uac` -> (1800, Some("uac")). `Min-SE` başlığı da aynı biçimdedir.
+fn parse_session_expires(value: &str) -> Option<(u64, Option<String>)> {
+    let mut parts = value.split(';');
+    let secs = parts.next()?.trim().parse().ok()?;
+    let refresher = parts
+        .find_map(|p| p.trim().strip_prefix("refresher="))
+        .map(|r| r.trim().to_ascii_lowercase());
+    Some((secs, refresher))
+}
+
+/// Diyaloğun canlı olduğu görüldü (yenileme veya diyalog içi yanıt).
+pub async fn touch(call_id: &str, state: &AppState) {
+    if let Some(call) = state.active_calls.lock().await.get_mut(call_id) {
+        call.last_activity = Instant::now();
+    }
+}
+
+enum Check {
+    Refresh,
+    Expired,
+    Probe,
+    Leaked,
+}
+
+/// Aktif diyalogları periyodik olarak denetler; her diyalog için aynı anda tek bir kontrol yürütülür.
+pub async fn supervise_dialogs(state: Arc<AppState>, sock: Arc<UdpSocket>) {
+    let in_flight: Arc<Mutex<HashSet<String>>> = Arc::default();
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Instant::now();
+        let checks: Vec<(String, Check)> = {
+            let active_calls = state.active_calls.lock().await;
+            let in_flight = in_flight.lock().unwrap();
+            active_calls
+                .iter()
+                .filter(|(call_id, _)| !in_flight.contains(*call_id))
+                .filter_map(|(call_id, call)| {
+                    // Kilit ACK işleyicisinde tutuluyorsa diyalog bir sonraki turda denetlenir.
+                    let confirmed = *call.answered_event_published.try_lock().ok()?;
+                    let check = match (confirmed, call.session_timer) {
+                        (false, _) if call.created_at.elapsed() >= UNCONFIRMED_DIALOG_MAX_AGE => Check::Leaked,
+                        (false, _) => return None,
+                        (true, Some(timer)) if now < timer.due(call.last_activity) => return None,
+                        (true, Some(timer)) if timer.refresher == Refresher::Local => Check::Refresh,
+                        (true, Some(_)) => Check::Expired,
+                        (true, None) if call.last_activity.elapsed() >= state.config.dialog_probe_interval => Check::Probe,
+                        (true, None) => return None,
+                    };
+                    Some((call_id.clone(), check))
+                })
+                .collect()
+        };
+
+        for (call_id, check) in checks {
+            let guard = InFlight::insert(&in_flight, &call_id);
+            let (state, sock) = (state.clone(), sock.clone());
+            tokio::spawn(async move {
+                let run = async {
+                    match check {
+                        Check::Refresh => refresh(&call_id, sock, &state).await,
+                        Check::Expired => {
+                            warn!(call_id = %call_id, "Karşı taraf oturumu süresi içinde yenilemedi, çağrı kapatılıyor.");
+                            bye::hang_up(&call_id, "session_expired", sock, &state).await;
+                        }
+                        Check::Probe => probe(&call_id, sock, &state).await,
+                        Check::Leaked => remove_leaked(&call_id, &state).await,
+                    }
+                };
+                if tokio::time::timeout(CHECK_TIMEOUT, run).await.is_err() {
+                    error!(call_id = %call_id, "Diyalog kontrolü süre sınırını aştı, bir sonraki turda yeniden denenecek.");
+                }
+                drop(guard);
+            });
+        }
+    }
+}
+
+/// Bir diyalog için yürüyen kontrolün kaydı; kontrol bittiğinde (veya görev iptal edildiğinde) silinir.
+struct InFlight {
+    call_id: String,
+    set: Arc<Mutex<HashSet<String>>>,
+}
+
+impl InFlight {
+    fn insert(set: &Arc<Mutex<HashSet<String>>>, call_id: &str) -> Self {
+        set.lock().unwrap().insert(call_id.to_string());
+        Self { call_id: call_id.to_string(), set: set.clone() }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut set) = self.set.lock() {
+            set.remove(&self.call_id);
+        }
+    }
+}
+
+/// Oturumu yeniler (RFC 4028 7.4). Karşı taraf `Allow` ile UPDATE'i destekliyorsa medyaya dokunmayan
+/// gövdesiz UPDATE, aksi halde mevcut medya yönünü koruyan re-INVITE gönderilir.
+#[instrument(skip(sock, state))]
+async fn refresh(call_id: &str, sock: Arc<UdpSocket>, state: &AppState) {
+    let Some((call_info, cseq)) = call_control::reserve_cseq(call_id, state).await else { return };
+    let Some(timer) = call_info.session_timer else { return };
+
+    let use_update = extensions::lists(&call_info.headers, "allow", "UPDATE");
+    let mut request = build_in_dialog_request(&call_info, if use_update { "UPDATE" } else { "INVITE" }, cseq);
+    request.extra_headers = timer.request_headers();
+    if !use_update {
+        let direction = if call_info.on_hold { "sendonly" } else { "sendrecv" };
+        request.body = Some(build_sdp_with_direction(call_info.rtp_port, &state.config, direction));
+    }
+
+    let response = match call_control::send(request.clone(), &call_info, sock.clone(), state).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!(error = %e, method = %request.method, "Oturum yenileme isteği gönderilemedi.");
+            return;
+        }
+    };
+    match response {
+        Some(response) if response.is_success() => {
+            if !use_update {
+                let ack = build_ack_for_2xx(&request, &response).render(&state.config);
+                if let Err(e) = sock.send_to(ack.as_bytes(), call_info.remote_addr).await {
+                    warn!(error = %e, "Yenileme re-INVITE'ının ACK'i gönderilemedi.");
+                }
+            }
+            if let Some(call) = state.active_calls.lock().await.get_mut(call_id) {
+                call.session_timer = SessionTimer::from_uac_response(&response).or(Some(timer));
+                call.last_activity = Instant::now();
+                if !response.body.is_empty() {
+                    call.raw_body = response.body.clone();
+                }
+            }
+            info!("Oturum yenilendi.");
+        }
+        Some(response) if response.status_code == 422 => {
+            // Karşı tarafın Min-SE'si benimsenir; yenileme bir sonraki turda tekrar denenir.
+            let min_se = response.headers.get("min-se").and_then(|v| parse_session_expires(v)).map(|(secs, _)| secs);
+            warn!(?min_se, "Oturum süresi karşı taraf için çok kısa.");
+            if let Some(call) = state.active_calls.lock().await.get_mut(call_id) {
+                match min_se {
+                    Some(secs) => call.session_timer = Some(SessionTimer { interval: Duration::from_secs(secs), ..timer }),
+                    None => call.last_activity = Instant::now(),
+                }
+            }
+        }
+        // RFC 4028 10: yenilemeye 408 veya 481 yanıtı ya da hiç yanıt gelmemesi oturumu sonlandırır.
+        Some(response) if matches!(response.status_code, 408 | 481) => {
+            warn!(status = response.status_code, "Oturum yenilemesi reddedildi, çağrı kapatılıyor.");
+            bye::hang_up(call_id, "session_refresh_failed", sock, state).await;
+        }
+        None => {
+            warn!("Oturum yenilemesine yanıt alınamadı, çağrı kapatılıyor.");
+            bye::hang_up(call_id, "session_refresh_failed", sock, state).await;
+        }
+        // Diğer hatalar (örn. 491) diyaloğun yaşadığını gösterir; yenileme bir sonraki aralıkta denenir.
+        Some(response) => {
+            warn!(status = response.status_code, "Oturum yenilemesi başarısız oldu.");
+            touch(call_id, state).await;
+        }
+    }
+}
+
+/// Zamanlayıcısı olmayan diyaloğu diyalog içi OPTIONS ile yoklar. Herhangi bir yanıt (405 dahil)
+/// karşı tarafın diyaloğu hâlâ tanıdığını gösterir; 408/481 veya yanıtsızlık diyaloğu kapatır.
+#[instrument(skip(sock, state))]
+async fn probe(call_id: &str, sock: Arc<UdpSocket>, state: &AppState) {
+    let Some((call_info, cseq)) = call_control::reserve_cseq(call_id, state).await else { return };
+    let options = build_in_dialog_request(&call_info, "OPTIONS", cseq);
+    match call_control::send(options, &call_info, sock.clone(), state).await {
+        Ok(Some(response)) if !matches!(response.status_code, 408 | 481) => touch(call_id, state).await,
+        Ok(response) => {
+            warn!(status = response.map(|r| r.status_code), "Diyalog yoklamasına yanıt alınamadı, çağrı kapatılıyor.");
+            bye::hang_up(call_id, "dialog_unreachable", sock, state).await;
+        }
+        Err(e) => error!(error = %e, "Diyalog yoklaması gönderilemedi."),
+    }
+}
+
+/// ACK ile hiç kurulmamış ve kurulum akışı tarafından da kapatılmamış diyaloğu listeden çıkarır.
+/// Temizlik, çağrıyı listeden çıkaran akış olarak portun da sahibidir.
+async fn remove_leaked(call_id: &str, state: &AppState) {
+    let Some(call_info) = state.active_calls.lock().await.remove(call_id) else { return };
+    warn!(call_id, "🧹 Kurulmamış diyalog temizlendi.");
+    release_call_media(&call_info, state).await;
+    bye::publish_call_ended(state, &call_info, "stale_call_timeout").await;
+}
@@ -0,0 +1,357 @@
+// sentiric-sip-signaling-service/src/sip/transaction.rs
+// UAC rolündeki istemci transaction katmanı (RFC 3261 17.1).
+// Giden isteklerin yanıtları UDP dinleyicisinden buraya yönlendirilir.
+use crate::config::AppConfig;
+use crate::sip::requests::{build_ack_for_2xx, build_ack_for_non_2xx, OutgoingRequest};
+use crate::sip::utils::parse_sip_headers;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, warn};
+
+pub const T1: Duration = Duration::from_millis(500);
+pub const T2: Duration = Duration::from_secs(4);
+/// Timer B / Timer F: 64*T1
+pub const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(32);
+
+#[derive(Debug, Clone)]
+pub struct SipResponse {
+    pub status_code: u16,
+    pub status_line: String,
+    pub headers: HashMap<String, String>,
+    pub via_headers: Vec<String>,
+    pub body: String,
+}
+
+impl SipResponse {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (header_part, body) = raw.split_once("\r\n\r\n").unwrap_or((raw, ""));
+        let (first_line, rest) = header_part.split_once("\r\n").unwrap_or((header_part, ""));
+        let status_line = first_line.strip_prefix("SIP/2.0 ")?.trim().to_string();
+        let status_code = status_line.split_whitespace().next()?.parse::<u16>().ok()?;
+        let (headers, via_headers) = parse_sip_headers(rest)?;
+        Some(Self { status_code, status_line, headers, via_headers, body: body.to_string() })
+    }
+
+    pub fn is_provisional(&self) -> bool {
+        self.status_code < 200
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_code)
+    }
+
+    pub fn cseq_method(&self) -> Option<&str> {
+        self.headers.get("cseq").and_then(|c| c.split_whitespace().nth(1))
+    }
+}
+
+/// Transaction anahtarı -> yanıt kanalı. `Drop` içinde temizlenebilmesi için std Mutex kullanılır;
+/// kilit hiçbir zaman `await` boyunca tutulmaz.
+pub type ClientTransactions = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<SipResponse>>>>;
+
+fn transaction_key(branch: &str, method: &str) -> String {
+    format!("{}:{}", branch, method)
+}
+
+pub fn get_via_branch(via: &str) -> Option<&str> {
+    via.split(';')
+        .find_map(|p| p.trim().strip_prefix("branch="))
+}
+
+/// UDP dinleyicisine gelen bir yanıtı ilgili istemci transaction'ına iletir.
+pub fn dispatch_response(raw: &str, transactions: &ClientTransactions) {
+    let Some(response) = SipResponse::parse(raw) else {
+        warn!("Ayrıştırılamayan SIP yanıtı alındı, görmezden geliniyor.");
+        return;
+    };
+    let branch = response.via_headers.first().and_then(|v| get_via_branch(v)).unwrap_or_default();
+    let method = response.cseq_method().unwrap_or_default();
+    let key = transaction_key(branch, method);
+
+    let sender = transactions.lock().unwrap().get(&key).cloned();
+    match sender {
+        Some(tx) => {
+            let _ = tx.send(response);
+        }
+        None => debug!(transaction = %key, status = response.status_code, "Eşleşen istemci transaction'ı olmayan yanıt görmezden geliniyor."),
+    }
+}
+
+/// Tek bir giden isteğin yaşam döngüsü. Retransmission (Timer A/E), zaman aşımı (Timer B/F),
+/// 2xx olmayan INVITE yanıtlarının ACK'lenmesi ve yeniden iletilen 2xx'lerin tekrar ACK'lenmesi burada yapılır.
+pub struct ClientTransaction {
+    key: String,
+    request: OutgoingRequest,
+    raw: String,
+    destination: SocketAddr,
+    sock: Arc<UdpSocket>,
+    config: Arc<AppConfig>,
+    registry: ClientTransactions,
+    rx: mpsc::UnboundedReceiver<SipResponse>,
+    retransmit_interval: Duration,
+    next_retransmit: Instant,
+    deadline: Option<Instant>,
+    proceeding: bool,
+    completed: bool,
+    /// INVITE 2xx ile cevaplandı; transaction bırakıldıktan sonra 2xx yeniden iletimleri ACK'lenir.
+    answered: bool,
+}
+
+impl ClientTransaction {
+    pub async fn start(
+        request: OutgoingRequest,
+        destination: SocketAddr,
+        sock: Arc<UdpSocket>,
+        config: Arc<AppConfig>,
+        registry: ClientTransactions,
+    ) -> std::io::Result<Self> {
+        let key = transaction_key(&request.branch, &request.method);
+        let (tx, rx) = mpsc::unbounded_channel();
+        registry.lock().unwrap().insert(key.clone(), tx);
+
+        let raw = request.render(&config);
+        // Kayıt, hata durumunda `Drop` ile geri alınır.
+        let transaction = Self {
+            key,
+            request,
+            raw,
+            destination,
+            sock,
+            config,
+            registry,
+            rx,
+            retransmit_interval: T1,
+            next_retransmit: Instant::now() + T1,
+            deadline: Some(Instant::now() + TRANSACTION_TIMEOUT),
+            proceeding: false,
+            completed: false,
+            answered: false,
+        };
+        transaction.sock.send_to(transaction.raw.as_bytes(), destination).await?;
+        Ok(transaction)
+    }
+
+    pub fn request(&self) -> &OutgoingRequest {
+        &self.request
+    }
+
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    fn is_invite(&self) -> bool {
+        self.request.method == "INVITE"
+    }
+
+    fn should_retransmit(&self) -> bool {
+        if self.completed {
+            return false;
+        }
+        // INVITE için geçici yanıttan sonra retransmission durur (Timer A).
+        !(self.is_invite() && self.proceeding)
+    }
+
+    /// Bir sonraki yanıtı bekler. Zaman aşımında veya transaction tamamlandıktan sonra `None` döner.
+    /// INVITE için ilk geçici yanıttan sonra zaman aşımını çağıran taraf uygular.
+    pub async fn recv(&mut self) -> Option<SipResponse> {
+        if self.completed {
+            return None;
+        }
+        loop {
+            let far_future = Instant::now() + Duration::from_secs(86400);
+            let retransmit_at = if self.should_retransmit() { self.next_retransmit } else { far_future };
+            let deadline = self.deadline.unwrap_or(far_future);
+
+            tokio::select! {
+                msg = self.rx.recv() => {
+                    let response = msg?;
+                    if response.is_provisional() {
+                        self.proceeding = true;
+                        if self.is_invite() {
+                            self.deadline = None;
+                        } else {
+                            self.retransmit_interval = T2;
+                        }
+                    } else {
+                        self.completed = true;
+                        self.answered = self.is_invite() && response.is_success();
+                        if self.is_invite() && response.status_code >= 300 {
+                            let ack = build_ack_for_non_2xx(&self.request, &response).render(&self.config);
+                            if let Err(e) = self.sock.send_to(ack.as_bytes(), self.destination).await {
+                                warn!(error = %e, "2xx olmayan yanıt için ACK gönderilemedi.");
+                            }
+                        }
+                    }
+                    return Some(response);
+                }
+                _ = sleep_until(retransmit_at) => {
+                    debug!(transaction = %self.key, "İstek yeniden gönderiliyor.");
+                    if let Err(e) = self.sock.send_to(self.raw.as_bytes(), self.destination).await {
+                        warn!(error = %e, "İstek yeniden gönderilemedi.");
+                    }
+                    self.retransmit_interval = if self.is_invite() {
+                        self.retransmit_interval * 2
+                    } else {
+                        (self.retransmit_interval * 2).min(T2)
+                    };
+                    self.next_retransmit = Instant::now() + self.retransmit_interval;
+                }
+                _ = sleep_until(deadline) => {
+                    warn!(transaction = %self.key, "İstemci transaction'ı zaman aşımına uğradı.");
+                    self.completed = true;
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Geçici yanıtları atlayarak nihai yanıtı bekler.
+    pub async fn final_response(&mut self) -> Option<SipResponse> {
+        loop {
+            let response = self.recv().await?;
+            if !response.is_provisional() {
+                return Some(response);
+            }
+        }
+    }
+}
+
+impl Drop for ClientTransaction {
+    fn drop(&mut self) {
+        // 2xx'in ACK'i kaybolursa karşı taraf 2xx'i yeniden iletir. Bu yeniden iletimler 64*T1 boyunca
+        // yeniden ACK'lenir (RFC 3261 13.2.2.4); kayıt bu sürenin sonunda silinir.
+        if self.answered {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let (_, closed_rx) = mpsc::unbounded_channel();
+                runtime.spawn(acknowledge_2xx_retransmissions(
+                    self.key.clone(),
+                    self.request.clone(),
+                    self.destination,
+                    self.sock.clone(),
+                    self.config.clone(),
+                    self.registry.clone(),
+                    std::mem::replace(&mut self.rx, closed_rx),
+                ));
+                return;
+            }
+        }
+        if let Ok(mut registry) = self.registry.lock() {
+            registry.remove(&self.key);
+        }
+    }
+}
+
+async fn acknowledge_2xx_retransmissions(
+    key: String,
+    invite: OutgoingRequest,
+    destination: SocketAddr,
+    sock: Arc<UdpSocket>,
+    config: Arc<AppConfig>,
+    registry: ClientTransactions,
+    mut rx: mpsc::UnboundedReceiver<SipResponse>,
+) {
+    let deadline = Instant::now() + TRANSACTION_TIMEOUT;
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let Some(response) = msg else { break };
+                if !response.is_success() {
+                    continue;
+                }
+                debug!(transaction = %key, "Yeniden iletilen 2xx için ACK tekrar gönderiliyor.");
+                let ack = build_ack_for_2xx(&invite, &response).render(&config);
+                if let Err(e) = sock.send_to(ack.as_bytes(), destination).await {
+                    warn!(error = %e, "Yeniden iletilen 2xx için ACK gönderilemedi.");
+                }
+            }
+            _ = sleep_until(deadline) => break,
+        }
+    }
+    if let Ok(mut registry) = registry.lock() {
+        registry.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::AppState;
+
+    fn invite(branch: &str) -> OutgoingRequest {
+        OutgoingRequest {
+            method: "INVITE".to_string(),
+            request_uri: "sip:1001@127.0.0.1".to_string(),
+            branch: branch.to_string(),
+            from: "<sip:sentiric@127.0.0.1>;tag=local".to_string(),
+            to: "<sip:1001@127.0.0.1>".to_string(),
+            call_id: "uac-2xx-test".to_string(),
+            cseq: 1,
+            route: Vec::new(),
+            extra_headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    fn ok_response(branch: &str) -> String {
+        format!(
+            "SIP/2.0 200 OK\r\n\
+             Via: SIP/2.0/UDP 127.0.0.1:5060;branch={}\r\n\
+             From: <sip:sentiric@127.0.0.1>;tag=local\r\n\
+             To: <sip:1001@127.0.0.1>;tag=remote\r\n\
+             Call-ID: uac-2xx-test\r\n\
+             CSeq: 1 INVITE\r\n\
+             Content-Length: 0\r\n\r\n",
+            branch
+        )
+    }
+
+    async fn recv_method(peer: &UdpSocket) -> String {
+        let mut buf = [0u8; 4096];
+        let len = tokio::time::timeout(Duration::from_secs(1), peer.recv(&mut buf))
+            .await
+            .expect("istek bekleniyordu")
+            .unwrap();
+        String::from_utf8_lossy(&buf[..len]).split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn retransmitted_2xx_is_acknowledged_after_the_transaction_is_dropped() {
+        let state = AppState::for_tests();
+        let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let registry = state.client_transactions.clone();
+
+        let mut transaction = ClientTransaction::start(invite("z9hG4bK-2xx"), peer.local_addr().unwrap(), sock, state.config.clone(), registry.clone())
+            .await
+            .unwrap();
+        assert_eq!(recv_method(&peer).await, "INVITE");
+
+        dispatch_response(&ok_response("z9hG4bK-2xx"), &registry);
+        assert!(transaction.recv().await.unwrap().is_success());
+        drop(transaction);
+
+        // İlk ACK kayboldu; karşı taraf 2xx'i yeniden iletir.
+        dispatch_response(&ok_response("z9hG4bK-2xx"), &registry);
+        assert_eq!(recv_method(&peer).await, "ACK");
+        assert!(registry.lock().unwrap().contains_key("z9hG4bK-2xx:INVITE"));
+    }
+
+    #[tokio::test]
+    async fn transaction_without_2xx_is_unregistered_on_drop() {
+        let state = AppState::for_tests();
+        let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let registry = state.client_transactions.clone();
+
+        let transaction = ClientTransaction::start(invite("z9hG4bK-drop"), peer.local_addr().unwrap(), sock, state.config.clone(), registry.clone())
+            .await
+            .unwrap();
+        drop(transaction);
+        assert!(registry.lock().unwrap().is_empty());
+    }
+}
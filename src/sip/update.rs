@@ -0,0 +1,92 @@
+// sentiric-sip-signaling-service/src/sip/update.rs
+// Diyalog içinde oturumun değiştirilmesi: UPDATE (RFC 3311), hem erken (200 OK öncesi) hem de kurulu
+// diyaloglarda, ve re-INVITE. SDP teklifi çağrının mevcut medya portuyla cevaplanır; karşı tarafın
+// RTP adresi değiştiyse media-service'e bildirilir. Her iki istek de oturum zamanlayıcısını yeniler (RFC 4028).
+
+use crate::app_state::AppState;
+use crate::sip::invite::orchestrator::update_rtp_target;
+use crate::sip::responses;
+use crate::sip::session_timer;
+use crate::sip::utils::{extract_sdp_media_info_from_body, extract_tag, parse_sip_headers};
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tracing::{info, instrument, warn, Span};
+
+#[instrument(skip_all, fields(remote_addr = %addr, call_id))]
+pub async fn handle(
+    request_str: &str,
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (header_part, body) = request_str.split_once("\r\n\r\n").unwrap_or((request_str, ""));
+    let (headers, via_headers) = parse_sip_headers(header_part).ok_or("Geçersiz başlıklar")?;
+    Span::current().record("call_id", headers.get("call-id").map_or("", String::as_str));
+    modify_session("UPDATE", &headers, &via_headers, body, addr, &sock, &state).await
+}
+
+/// Diyalog içi bir UPDATE veya re-INVITE'ı işler. Gövdesiz re-INVITE'ın 200 OK'i bir teklif
+/// içermelidir (RFC 3261 14.2); gövdesiz UPDATE ise yalnızca oturumu yeniler.
+pub async fn modify_session(
+    method: &str,
+    headers: &HashMap<String, String>,
+    via_headers: &[String],
+    body: &str,
+    remote_addr: SocketAddr,
+    sock: &UdpSocket,
+    state: &AppState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let reply = |status_line: &str, extra_headers: &[String], body: Option<&str>| {
+        responses::create_response_with_headers(status_line, headers, via_headers, extra_headers, body, &state.config, remote_addr)
+    };
+
+    let timer = match session_timer::negotiate_uas(headers, &state.config) {
+        Ok(timer) => timer,
+        Err(min_se) => {
+            warn!(method, min_se, "İstenen oturum süresi çok kısa.");
+            let response = reply("422 Session Interval Too Small", &[format!("Min-SE: {}", min_se)], None);
+            sock.send_to(response.as_bytes(), remote_addr).await?;
+            return Ok(());
+        }
+    };
+
+    let call_id = headers.get("call-id").cloned().unwrap_or_default();
+    let local_tag = headers.get("to").and_then(|to| extract_tag(to));
+    let modified = {
+        let mut active_calls = state.active_calls.lock().await;
+        match active_calls.get_mut(&call_id).filter(|call| Some(&call.to_tag) == local_tag.as_ref()) {
+            Some(call) => {
+                let previous_target = extract_sdp_media_info_from_body(&call.raw_body);
+                call.session_timer = timer;
+                call.last_activity = Instant::now();
+                if !body.is_empty() {
+                    call.raw_body = body.to_string();
+                }
+                Some((call.clone(), previous_target))
+            }
+            None => None,
+        }
+    };
+    let Some((call_info, previous_target)) = modified else {
+        warn!(method, "İsteğin ait olduğu diyalog bulunamadı.");
+        let response = reply("481 Call/Transaction Does Not Exist", &[], None);
+        sock.send_to(response.as_bytes(), remote_addr).await?;
+        return Ok(());
+    };
+
+    let sdp = (!body.is_empty() || method == "INVITE")
+        .then(|| responses::build_sdp_with_direction(call_info.rtp_port, &state.config, responses::answer_direction(body)));
+    let extra_headers = timer.map(|t| t.response_headers()).unwrap_or_default();
+    let response = reply("200 OK", &extra_headers, sdp.as_deref());
+    sock.send_to(response.as_bytes(), remote_addr).await?;
+    info!(method, session_timer = ?timer, offer = !body.is_empty(), "Oturum değişikliği kabul edildi.");
+
+    if let Some(target) = extract_sdp_media_info_from_body(body).filter(|target| Some(target) != previous_target.as_ref()) {
+        update_rtp_target(&call_info, &target, state).await;
+    }
+    Ok(())
+}
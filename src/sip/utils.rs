@@ -1,14 +1,20 @@
 // sentiric-sip-signaling-service/src/sip/utils.rs
 
-use crate::config::AppConfig;
-use crate::state::ActiveCallInfo;
 use once_cell::sync::Lazy;
-use rand::Rng;
 use regex::Regex;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use tracing::{warn}; // Info gerekirse eklenebilir
 
 static USER_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"sip:\+?(\d+)@").unwrap());
+static RAW_USER_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"sips?:([^@;>]+)@").unwrap());
+static DOMAIN_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"sips?:[^@;>]+@([^:;>]+)").unwrap());
+static HOST_PORT_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(sips?:(?:[^@;>]+@)?)([^:;>?]+)(?::(\d+))?").unwrap());
+// Dışarıdan (gRPC) gelen kullanıcı adları ve URI'ler için izin verilen biçimler (RFC 3261 25.1'in alt kümesi).
+static SIP_USER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9\-_.!~*'()&=+$,/%]+$").unwrap());
+static SIP_URI_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^sips?:(?:[A-Za-z0-9\-_.!~*'()&=+$,/%]+@)?(?:[A-Za-z0-9.\-]+|\[[0-9A-Fa-f:.]+\])(?::\d{1,5})?(?:;[A-Za-z0-9\-_.!~*'()%=+]+)*$").unwrap()
+});
 
 // Header'ları ve özellikle VIA header'larını (sıralı olarak) ayrıştırır.
 pub fn parse_sip_headers(header_section: &str) -> Option<(HashMap<String, String>, Vec<String>)> {
@@ -31,11 +37,25 @@ pub fn parse_sip_headers(header_section: &str) -> Option<(HashMap<String, String
                 "m" => "contact",
                 "l" => "content-length",
                 "c" => "content-type",
+                "o" => "event",
+                "r" => "refer-to",
+                "b" => "referred-by",
+                "k" => "supported",
+                "x" => "session-expires",
                 _ => key_trimmed.as_str(),
             };
 
             if key_normalized == "via" {
                 via_headers.push(val_trimmed);
+            } else if key_normalized == "record-route" {
+                // Birden fazla Record-Route satırı sırası korunarak tek değerde birleştirilir (RFC 3261 7.3.1).
+                headers
+                    .entry(key_normalized.to_string())
+                    .and_modify(|route_set: &mut String| {
+                        route_set.push_str(", ");
+                        route_set.push_str(&val_trimmed);
+                    })
+                    .or_insert_with(|| val_trimmed.clone());
             } else {
                 headers.insert(key_normalized.to_string(), val_trimmed);
             }
@@ -71,6 +91,69 @@ pub fn extract_user_from_uri(uri: &str) -> Option<String> {
     })
 }
 
+// Bir SIP kullanıcı adının (numara veya dahili) başlık ve Request-URI'ye güvenle yazılabilir olup olmadığı.
+pub fn is_valid_sip_user(user: &str) -> bool {
+    SIP_USER_RE.is_match(user)
+}
+
+// Açılı parantezsiz, boşluk ve kontrol karakteri içermeyen bir SIP/SIPS URI'si olup olmadığı.
+pub fn is_valid_sip_uri(uri: &str) -> bool {
+    SIP_URI_RE.is_match(uri)
+}
+
+// Normalizasyon yapmadan URI'nin kullanıcı kısmını döndürür (kimlik doğrulama karşılaştırmaları için)
+pub fn extract_raw_user_from_uri(uri: &str) -> Option<String> {
+    RAW_USER_EXTRACT_RE.captures(uri).and_then(|caps| caps.get(1)).map(|m| m.as_str().to_string())
+}
+
+pub fn extract_domain_from_uri(uri: &str) -> Option<String> {
+    DOMAIN_EXTRACT_RE.captures(uri).and_then(|caps| caps.get(1)).map(|m| m.as_str().to_string())
+}
+
+// Bir From/To başlığındaki `tag` parametresini döndürür.
+pub fn extract_tag(header: &str) -> Option<String> {
+    header
+        .split(';')
+        .skip(1)
+        .find_map(|p| p.trim().strip_prefix("tag="))
+        .map(|t| t.trim_end_matches('>').to_string())
+}
+
+// URI'nin host ve (varsa) port kısmını döndürür.
+pub fn extract_host_port_from_uri(uri: &str) -> Option<(String, Option<u16>)> {
+    HOST_PORT_EXTRACT_RE.captures(uri).and_then(|caps| {
+        let host = caps.get(2)?.as_str().to_string();
+        let port = caps.get(3).and_then(|p| p.as_str().parse::<u16>().ok());
+        Some((host, port))
+    })
+}
+
+// URI'nin host:port kısmını verilen adresle değiştirir (kullanıcı kısmı ve parametreler korunur).
+pub fn rewrite_uri_host_port(uri: &str, addr: SocketAddr) -> String {
+    HOST_PORT_EXTRACT_RE
+        .replace(uri, |caps: &regex::Captures| format!("{}{}", &caps[1], addr))
+        .into_owned()
+}
+
+// Contact adresi özel (RFC 1918 vb.) bir IP ise veya isteğin gerçekten geldiği adresle
+// eşleşmiyorsa istemcinin NAT arkasında olduğu kabul edilir. Alan adı içeren Contact'lar değerlendirilmez.
+pub fn contact_is_behind_nat(contact_uri: &str, source_addr: SocketAddr) -> bool {
+    let Some((host, port)) = extract_host_port_from_uri(contact_uri) else { return false };
+    let Ok(ip) = host.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>() else { return false };
+    is_private_ip(&ip) || ip != source_addr.ip() || port.unwrap_or(5060) != source_addr.port()
+}
+
+pub fn is_private_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            // 100.64.0.0/10: Carrier-grade NAT
+            v4.is_private() || v4.is_loopback() || v4.is_link_local() || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        }
+        // fc00::/7: Unique local
+        IpAddr::V6(v6) => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    }
+}
+
 // SDP Body içinden Media IP ve Portunu çeker
 pub fn extract_sdp_media_info_from_body(sip_body: &str) -> Option<String> {
     let mut ip_addr: Option<&str> = None;
@@ -92,34 +175,28 @@ pub fn extract_sdp_media_info_from_body(sip_body: &str) -> Option<String> {
     }
 }
 
-// BYE Paketi Oluşturucu
-pub fn create_bye_request(call_info: &ActiveCallInfo, config: &AppConfig) -> String {
-    let cseq_line = call_info.headers.get("cseq").cloned().unwrap_or_else(|| "1 INVITE".to_string());
-    // CSeq numarasını artır
-    let cseq_num = cseq_line.split_whitespace().next().unwrap_or("1").parse::<u32>().unwrap_or(1) + 1;
-    
-    let request_uri = get_uri_from_header(&call_info.contact_header)
-        .unwrap_or_else(|| call_info.contact_header.clone());
-    
-    let branch: u32 = rand::thread_rng().gen();
-    
-    format!(
-        "BYE {} SIP/2.0\r\n\
-        Via: SIP/2.0/UDP {}:{};branch=z9hG4bK.{}\r\n\
-        Max-Forwards: 70\r\n\
-        From: {};tag={}\r\n\
-        To: {}\r\n\
-        Call-ID: {}\r\n\
-        CSeq: {} BYE\r\n\
-        User-Agent: Sentiric Signaling v{}\r\n\
-        Content-Length: 0\r\n\
-        \r\n",
-        request_uri,
-        config.sip_public_ip, config.sip_listen_addr.port(), branch,
-        call_info.to_header, call_info.to_tag,
-        call_info.from_header,
-        call_info.call_id,
-        cseq_num,
-        config.service_version
-    )
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sip_user_accepts_numbers_and_rejects_header_injection() {
+        assert!(is_valid_sip_user("+905551112233"));
+        assert!(is_valid_sip_user("1001"));
+        assert!(!is_valid_sip_user("1001\r\nX-Injected: 1"));
+        assert!(!is_valid_sip_user("1001@evil.test"));
+        assert!(!is_valid_sip_user("alice bob"));
+        assert!(!is_valid_sip_user(""));
+    }
+
+    #[test]
+    fn sip_uri_accepts_plain_uris_and_rejects_header_injection() {
+        assert!(is_valid_sip_uri("sip:1001@sentiric.test"));
+        assert!(is_valid_sip_uri("sips:alice@10.0.0.1:5061;transport=tls"));
+        assert!(is_valid_sip_uri("sip:[2001:db8::1]:5060"));
+        assert!(!is_valid_sip_uri("sip:1001@sentiric.test\r\nX-Injected: 1"));
+        assert!(!is_valid_sip_uri("<sip:1001@sentiric.test>"));
+        assert!(!is_valid_sip_uri("sip:1001@sentiric.test SIP/2.0"));
+        assert!(!is_valid_sip_uri("tel:1001"));
+    }
+}
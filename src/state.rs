@@ -1,9 +1,17 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use tokio::sync::Mutex;
-use tracing::info;
+use crate::sip::session_timer::SessionTimer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallDirection {
+    /// Bu servise gelen INVITE ile kurulan çağrı (UAS).
+    Inbound,
+    /// Bu servisin gönderdiği INVITE ile kurulan çağrı veya bacak (UAC).
+    Outbound,
+}
 
 #[derive(Clone, Debug)]
 pub struct ActiveCallInfo {
@@ -18,31 +26,57 @@ pub struct ActiveCallInfo {
     pub from_header: String,
     pub to_header: String,
     pub contact_header: String,
-    #[allow(dead_code)]
     pub record_route_header: Option<String>,
     pub raw_body: String,
     pub answered_event_published: Arc<Mutex<bool>>, 
+    /// Çatallama (forking) sonucu cevaplanan karşı bacağın Call-ID'si. İki bacak birbirini gösterir.
+    pub bridged_call_id: Option<String>,
+    /// INVITE Digest doğrulamasıyla kimliği doğrulanan arayan kullanıcı adı.
+    pub authenticated_user: Option<String>,
+    /// Giden çağrının gönderildiği trunk'ın adı.
+    pub trunk: Option<String>,
+    /// Dialplan çözümlemesinden gelen kiracı (tenant).
+    pub tenant_id: Option<String>,
+    pub direction: CallDirection,
+    /// Çağrı re-INVITE ile beklemeye alındı mı?
+    pub on_hold: bool,
+    /// Bu servisin diyalog içinde gönderdiği son isteğin CSeq numarası.
+    pub local_cseq: u32,
+    /// Bu servisin gönderdiği ve sonucu NOTIFY ile beklenen REFER'ın `Refer-To` değeri.
+    pub pending_refer_to: Option<String>,
+    /// Medya portunu ayıran media-service örneği; port üzerindeki tüm medya istekleri buraya gider.
+    pub media_instance: Option<String>,
+    /// Diyalog için anlaşılan oturum zamanlayıcısı (RFC 4028); yoksa diyalog OPTIONS ile yoklanır.
+    pub session_timer: Option<SessionTimer>,
+    /// Diyaloğun son canlılık kanıtı: kurulum, oturum yenilemesi veya yoklamaya gelen yanıt.
+    pub last_activity: Instant,
     // [YENİ] Retransmission için son üretilen başarılı yanıtı saklayabiliriz (Optimization)
     // Şimdilik sadece gerekli alanları tutuyoruz.
 }
 
-pub type ActiveCalls = Arc<Mutex<HashMap<String, ActiveCallInfo>>>;
+impl CallDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallDirection::Inbound => "inbound",
+            CallDirection::Outbound => "outbound",
+        }
+    }
+}
 
-pub async fn cleanup_old_transactions(transactions: ActiveCalls) {
-    let mut interval = tokio::time::interval(Duration::from_secs(60));
-    loop {
-        interval.tick().await;
-        let mut guard = transactions.lock().await;
-        let before_count = guard.len();
-        // 5 dakikadan eski çağrıları temizle (Normalde BYE ile silinmeli ama sızıntı koruması)
-        guard.retain(|_call_id, call_info| call_info.created_at.elapsed() < Duration::from_secs(300));
-        let after_count = guard.len();
-        if before_count > after_count {
-            info!(
-                cleaned = before_count - after_count,
-                remaining = after_count,
-                "🧹 Eski çağrı kayıtları temizlendi."
-            );
+impl ActiveCallInfo {
+    /// Çağrıyı başlatan tarafın URI'si. Giden çağrılarda yerel taraf (`to_header`) arayandır.
+    pub fn caller_uri(&self) -> &str {
+        match self.direction {
+            CallDirection::Inbound => &self.from_header,
+            CallDirection::Outbound => &self.to_header,
         }
     }
-}
\ No newline at end of file
+
+    /// Diyalog içi yeni bir istek için CSeq ayırır (RFC 3261 12.2.1.1).
+    pub fn next_local_cseq(&mut self) -> u32 {
+        self.local_cseq += 1;
+        self.local_cseq
+    }
+}
+
+pub type ActiveCalls = Arc<Mutex<HashMap<String, ActiveCallInfo>>>;
@@ -0,0 +1,24 @@
+// File: sentiric-sip-signaling-service/src/telemetry.rs
+// Prometheus metrikleri. Metrik adları tek yerde tutulur; kayıt `metrics` makrolarıyla yapılır.
+use anyhow::Result;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use std::net::SocketAddr;
+
+/// Trunk başına UAC kayıt durumu (1 = kayıtlı, 0 = kayıtsız).
+pub const TRUNK_REGISTRATION_STATE: &str = "sip_trunk_registration_state";
+/// Trunk başına başarısız kayıt denemesi sayısı.
+pub const TRUNK_REGISTRATION_FAILURES: &str = "sip_trunk_registration_failures_total";
+/// Bağımlılık başına devre kesici durumu (0 = kapalı, 1 = açık, 2 = yarı açık).
+pub const GRPC_CIRCUIT_STATE: &str = "sip_grpc_circuit_state";
+/// Açık devre nedeniyle hiç gönderilmeden reddedilen gRPC çağrısı sayısı.
+pub const GRPC_CIRCUIT_REJECTIONS: &str = "sip_grpc_circuit_rejections_total";
+/// Geçici hatalar nedeniyle tekrar edilen gRPC çağrısı sayısı.
+pub const GRPC_RETRIES: &str = "sip_grpc_retries_total";
+/// Medya örneği başına sağlık durumu (1 = sağlıklı, 0 = sağlıksız).
+pub const MEDIA_INSTANCE_HEALTHY: &str = "sip_media_instance_healthy";
+
+/// Prometheus exporter'ını `/metrics` HTTP dinleyicisiyle kurar. Tokio runtime içinde çağrılmalıdır.
+pub fn install_exporter(addr: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new().with_http_listener(addr).install()?;
+    Ok(())
+}
@@ -0,0 +1,17 @@
+// File: src/trunk/mod.rs
+// Upstream SIP trunk'ları (taşıyıcılar) ile ilgili mantık.
+
+use crate::config::TrunkConfig;
+use std::net::SocketAddr;
+
+pub mod registration;
+pub mod routing;
+
+/// Trunk'ın `host:port` adresini (gerekirse DNS ile) çözer.
+pub async fn resolve_addr(trunk: &TrunkConfig) -> Result<SocketAddr, String> {
+    tokio::net::lookup_host((trunk.host.as_str(), trunk.port))
+        .await
+        .map_err(|e| format!("Trunk adresi çözümlenemedi: {}", e))?
+        .next()
+        .ok_or_else(|| format!("Trunk adresi çözümlenemedi: {}", trunk.host))
+}
@@ -0,0 +1,215 @@
+// File: src/trunk/registration.rs
+// Upstream trunk'lara UAC olarak REGISTER gönderen kayıt yöneticisi.
+// Her trunk kendi görevinde kaydolur, süresi dolmadan yeniler ve hata durumunda üstel geri çekilme uygular.
+
+use crate::app_state::AppState;
+use crate::config::TrunkConfig;
+use crate::telemetry::{TRUNK_REGISTRATION_FAILURES, TRUNK_REGISTRATION_STATE};
+use crate::sip::auth;
+use crate::sip::requests::{generate_branch, generate_tag, OutgoingRequest};
+use crate::sip::transaction::{ClientTransaction, SipResponse};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tracing::{info, instrument, warn};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Kaydın süresi dolmadan en fazla bu kadar önce yenilenir.
+const REFRESH_MARGIN_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationState {
+    Registering,
+    Registered,
+    Failed,
+}
+
+impl RegistrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationState::Registering => "REGISTERING",
+            RegistrationState::Registered => "REGISTERED",
+            RegistrationState::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrunkRegistrationStatus {
+    pub trunk_name: String,
+    pub registrar_uri: String,
+    pub state: RegistrationState,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+pub type TrunkRegistrations = Arc<Mutex<HashMap<String, TrunkRegistrationStatus>>>;
+
+/// `register: true` olan her trunk için bir kayıt görevi başlatır.
+pub fn spawn_all(state: Arc<AppState>, sock: Arc<UdpSocket>) {
+    for trunk in state.config.trunks.iter().filter(|t| t.register) {
+        info!(trunk = %trunk.name, host = %trunk.host, "Trunk kayıt görevi başlatılıyor.");
+        tokio::spawn(run_trunk_registration(trunk.clone(), state.clone(), sock.clone()));
+    }
+}
+
+#[instrument(skip_all, fields(trunk = %trunk.name))]
+async fn run_trunk_registration(trunk: TrunkConfig, state: Arc<AppState>, sock: Arc<UdpSocket>) {
+    let mut session = RegistrationSession {
+        call_id: uuid::Uuid::new_v4().to_string(),
+        from_tag: generate_tag(),
+        cseq: 1,
+    };
+    let registrar_uri = format!("sip:{}", trunk.domain());
+    let mut failures: u32 = 0;
+    let mut refreshing = false;
+
+    loop {
+        // Yenileme sırasında trunk kayıtlı kalır; durum yalnızca ilk kayıtta veya hatadan sonra değişir.
+        if !refreshing {
+            update_status(&state, &trunk, &registrar_uri, RegistrationState::Registering, None, failures, None).await;
+        }
+
+        match register_once(&trunk, &registrar_uri, &mut session, &state, &sock).await {
+            Ok(granted_expires) => {
+                failures = 0;
+                refreshing = true;
+                let expires_at = Utc::now() + chrono::Duration::seconds(granted_expires as i64);
+                info!(expires = granted_expires, "Trunk kaydı başarılı.");
+                update_status(&state, &trunk, &registrar_uri, RegistrationState::Registered, Some(expires_at), 0, None).await;
+                let margin = REFRESH_MARGIN_SECS.min(granted_expires / 2);
+                tokio::time::sleep(Duration::from_secs(granted_expires - margin)).await;
+            }
+            Err(e) => {
+                failures += 1;
+                refreshing = false;
+                metrics::counter!(TRUNK_REGISTRATION_FAILURES, "trunk" => trunk.name.clone()).increment(1);
+                let delay = backoff(failures);
+                warn!(error = %e, attempt = failures, retry_in_secs = delay.as_secs(), "Trunk kaydı başarısız.");
+                update_status(&state, &trunk, &registrar_uri, RegistrationState::Failed, None, failures, Some(e)).await;
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Bir trunk kaydı boyunca sabit kalan diyalog kimliği (RFC 3261 10.2: aynı Call-ID, artan CSeq).
+struct RegistrationSession {
+    call_id: String,
+    from_tag: String,
+    cseq: u32,
+}
+
+/// Tek bir REGISTER döngüsü: gerekirse 401/407 challenge'ına bir kez yanıt verir.
+/// Başarıda sunucunun onayladığı süreyi (saniye) döndürür.
+async fn register_once(
+    trunk: &TrunkConfig,
+    registrar_uri: &str,
+    session: &mut RegistrationSession,
+    state: &AppState,
+    sock: &Arc<UdpSocket>,
+) -> Result<u64, String> {
+    let destination = super::resolve_addr(trunk).await?;
+    let username = trunk.username.clone().unwrap_or_else(|| "sentiric".to_string());
+    let aor = format!("<sip:{}@{}>", username, trunk.domain());
+    let mut authorization: Option<String> = None;
+
+    for _ in 0..2 {
+        let mut extra_headers = vec![format!("Expires: {}", trunk.register_expires)];
+        extra_headers.extend(authorization.clone());
+
+        let request = OutgoingRequest {
+            method: "REGISTER".to_string(),
+            request_uri: registrar_uri.to_string(),
+            branch: generate_branch(),
+            from: format!("{};tag={}", aor, session.from_tag),
+            to: aor.clone(),
+            call_id: session.call_id.clone(),
+            cseq: session.cseq,
+            route: Vec::new(),
+            extra_headers,
+            body: None,
+        };
+        session.cseq += 1;
+
+        let mut transaction = ClientTransaction::start(
+            request,
+            destination,
+            sock.clone(),
+            state.config.clone(),
+            state.client_transactions.clone(),
+        )
+        .await
+        .map_err(|e| format!("REGISTER gönderilemedi: {}", e))?;
+
+        let response = transaction
+            .final_response()
+            .await
+            .ok_or_else(|| "REGISTER zaman aşımına uğradı".to_string())?;
+
+        match response.status_code {
+            200..=299 => return Ok(granted_expires(&response, trunk.register_expires)),
+            401 | 407 if authorization.is_none() => {
+                let (Some(user), Some(password)) = (&trunk.username, &trunk.password) else {
+                    return Err(format!("{} (kimlik bilgisi tanımlı değil)", response.status_line));
+                };
+                authorization = auth::answer_challenge(&response, user, password, "REGISTER", registrar_uri);
+                if authorization.is_none() {
+                    return Err(format!("{} (challenge ayrıştırılamadı)", response.status_line));
+                }
+            }
+            _ => return Err(response.status_line),
+        }
+    }
+    Err("Kimlik doğrulama reddedildi".to_string())
+}
+
+/// Sunucunun onayladığı süre: Contact `expires` parametresi, yoksa `Expires` başlığı.
+fn granted_expires(response: &SipResponse, requested: u64) -> u64 {
+    response
+        .headers
+        .get("contact")
+        .and_then(|c| c.split(';').find_map(|p| p.trim().strip_prefix("expires=")))
+        .or_else(|| response.headers.get("expires").map(String::as_str))
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(requested)
+}
+
+/// Üstel geri çekilme (5sn, 10sn, 20sn ... en fazla 5dk) ve ±%20 rastgele sapma.
+fn backoff(failures: u32) -> Duration {
+    let base = MIN_BACKOFF.saturating_mul(2u32.saturating_pow(failures.saturating_sub(1))).min(MAX_BACKOFF);
+    let jitter = rand::thread_rng().gen_range(0.8..1.2);
+    base.mul_f64(jitter)
+}
+
+async fn update_status(
+    state: &AppState,
+    trunk: &TrunkConfig,
+    registrar_uri: &str,
+    registration_state: RegistrationState,
+    expires_at: Option<DateTime<Utc>>,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+) {
+    let registered = if registration_state == RegistrationState::Registered { 1.0 } else { 0.0 };
+    metrics::gauge!(TRUNK_REGISTRATION_STATE, "trunk" => trunk.name.clone()).set(registered);
+
+    state.trunk_registrations.lock().await.insert(
+        trunk.name.clone(),
+        TrunkRegistrationStatus {
+            trunk_name: trunk.name.clone(),
+            registrar_uri: registrar_uri.to_string(),
+            state: registration_state,
+            expires_at,
+            consecutive_failures,
+            last_error,
+        },
+    );
+}
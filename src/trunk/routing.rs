@@ -0,0 +1,91 @@
+// File: src/trunk/routing.rs
+// Giden çağrılar için trunk seçimi (prefix + öncelik) ve eşzamanlı kanal sınırı.
+
+use crate::app_state::AppState;
+use crate::config::{AppConfig, TrunkConfig, TrunkTransport};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Trunk adı -> şu anda çaldırılmakta olan (henüz `ActiveCalls`'a girmemiş) giden çağrı sayısı.
+/// `Drop` içinde azaltılabilmesi için std Mutex kullanılır.
+pub type TrunkAttempts = Arc<Mutex<HashMap<String, u32>>>;
+
+/// Hedef numara için denenecek trunk'ları sırasıyla döndürür.
+/// Yönlendirme tablosu boşsa tüm trunk'lar tanım sırasıyla denenir.
+pub fn candidates<'a>(config: &'a AppConfig, destination: &str) -> Vec<&'a TrunkConfig> {
+    let mut names: Vec<&str> = if config.trunk_routes.is_empty() {
+        config.trunks.iter().map(|t| t.name.as_str()).collect()
+    } else {
+        let mut routes: Vec<_> = config
+            .trunk_routes
+            .iter()
+            .filter(|r| destination.starts_with(&r.prefix))
+            .collect();
+        routes.sort_by(|a, b| b.prefix.len().cmp(&a.prefix.len()).then(a.priority.cmp(&b.priority)));
+        routes.into_iter().map(|r| r.trunk.as_str()).collect()
+    };
+    let mut seen = HashSet::new();
+    names.retain(|n| seen.insert(*n));
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let trunk = config.trunks.iter().find(|t| t.name == name);
+            if trunk.is_none() {
+                warn!(trunk = %name, "Yönlendirme tablosu tanımsız bir trunk'a işaret ediyor.");
+            }
+            trunk
+        })
+        .filter(|t| {
+            let supported = t.transport == TrunkTransport::Udp;
+            if !supported {
+                warn!(trunk = %t.name, transport = ?t.transport, "Trunk taşıma türü desteklenmiyor, atlanıyor.");
+            }
+            supported
+        })
+        .collect()
+}
+
+/// Bir trunk üzerindeki aktif (cevaplanmış) çağrı sayısı.
+pub async fn active_channels(state: &AppState, trunk_name: &str) -> u32 {
+    state
+        .active_calls
+        .lock()
+        .await
+        .values()
+        .filter(|c| c.trunk.as_deref() == Some(trunk_name))
+        .count() as u32
+}
+
+/// Çaldırma süresince bir trunk kanalını ayırır. Cevaplanan çağrı `ActiveCalls`'a girdiğinde
+/// kanal orada sayılmaya devam eder; ayırma `Drop` ile bırakılır. Tutan taraf beklemesini sınırlamalıdır
+/// (bkz. `originate::attempt_limit`): aksi halde kanal `max_channels` dolana kadar kalıcı olarak kaybolur.
+pub struct ChannelReservation {
+    trunk: String,
+    attempts: TrunkAttempts,
+}
+
+impl Drop for ChannelReservation {
+    fn drop(&mut self) {
+        if let Ok(mut attempts) = self.attempts.lock() {
+            if let Some(count) = attempts.get_mut(&self.trunk) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Trunk'ta boş kanal varsa ayırır; `max_channels` doluysa `None` döner.
+pub async fn reserve_channel(state: &AppState, trunk: &TrunkConfig) -> Option<ChannelReservation> {
+    let active = active_channels(state, &trunk.name).await;
+    let mut attempts = state.trunk_attempts.lock().unwrap();
+    let in_flight = attempts.entry(trunk.name.clone()).or_insert(0);
+    if let Some(max) = trunk.max_channels {
+        if active + *in_flight >= max {
+            return None;
+        }
+    }
+    *in_flight += 1;
+    Some(ChannelReservation { trunk: trunk.name.clone(), attempts: state.trunk_attempts.clone() })
+}